@@ -26,6 +26,14 @@ pub fn derive_file_ext_plugin(input: TokenStream) -> TokenStream {
         pub static IRIDIS_FILE_EXT_PLUGIN: DynamicallyLinkedFileExtPluginInstance =
             || <#name>::new();
 
+        #[cfg(feature = "cdylib")]
+        #[doc(hidden)]
+        #[unsafe(no_mangle)]
+        pub static IRIDIS_FILE_EXT_PLUGIN_METADATA: iridis_file_ext::prelude::LibraryMetadata =
+            iridis_file_ext::prelude::LibraryMetadata::new(
+                iridis_file_ext::prelude::FILE_EXT_PLUGIN_INTERFACE,
+            );
+
         static DEFAULT_TOKIO_RUNTIME: std::sync::LazyLock<iridis_file_ext::prelude::thirdparty::tokio::runtime::Runtime> =
             std::sync::LazyLock::new(|| {
                 iridis_file_ext::prelude::thirdparty::tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime")
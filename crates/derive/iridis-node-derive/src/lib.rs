@@ -11,44 +11,510 @@ use syn::{
     punctuated::Punctuated,
 };
 
-/// Apply this macro to a struct to generate the `C` symbols
+/// A field of the node declared as a port with `#[input]`, `#[output]`, `#[query]` or `#[queryable]`.
+struct PortField {
+    kind: syn::Ident,
+    label: String,
+    ty: syn::Type,
+}
+
+/// A field of the node read from a key of its configuration with `#[config]`, or from
+/// the whole configuration with `#[config(flatten)]` (`key` is `None`).
+struct ConfigField {
+    key: Option<String>,
+    default: Option<syn::Expr>,
+    ty: syn::Type,
+}
+
+/// How a field is built by the generated constructor.
+enum FieldKind {
+    Port(PortField),
+    Config(ConfigField),
+    Default,
+}
+
+struct NodeField {
+    member: syn::Member,
+    kind: FieldKind,
+}
+
+/// The arguments of `#[config]`: `flatten`, or an optional key then an optional `default = expr`.
+struct ConfigArgs {
+    key: Option<String>,
+    default: Option<syn::Expr>,
+    flatten: bool,
+}
+
+impl Parse for ConfigArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut key = None;
+        let mut default = None;
+
+        if input.peek(syn::Ident) && input.fork().parse::<syn::Ident>()? == "flatten" {
+            input.parse::<syn::Ident>()?;
+
+            return Ok(ConfigArgs {
+                key,
+                default,
+                flatten: true,
+            });
+        }
+
+        if input.peek(syn::LitStr) {
+            key = Some(input.parse::<syn::LitStr>()?.value());
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        if !input.is_empty() {
+            let name = input.parse::<syn::Ident>()?;
+            if name != "default" {
+                return Err(syn::Error::new_spanned(name, "Expected `default = ...`"));
+            }
+
+            input.parse::<Token![=]>()?;
+            default = Some(input.parse::<syn::Expr>()?);
+        }
+
+        Ok(ConfigArgs {
+            key,
+            default,
+            flatten: false,
+        })
+    }
+}
+
+/// Parse the attributes of the fields. Labels and keys default to the name of the field.
+fn node_fields(data: &syn::Data) -> syn::Result<Vec<NodeField>> {
+    let syn::Data::Struct(data) = data else {
+        return Ok(Vec::new());
+    };
+
+    let mut fields = Vec::new();
+
+    for (index, field) in data.fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(index.into()),
+        };
+
+        let name = |attr: &syn::Attribute| {
+            field
+                .ident
+                .as_ref()
+                .map(|ident| ident.to_string())
+                .ok_or_else(|| syn::Error::new_spanned(attr, "A label is required on tuple fields"))
+        };
+
+        let mut kind = FieldKind::Default;
+
+        for attr in &field.attrs {
+            let declaring = ["config", "input", "output", "query", "queryable"]
+                .iter()
+                .any(|kind| attr.path().is_ident(kind));
+
+            if declaring && !matches!(kind, FieldKind::Default) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "A field can only have one of `#[input]`, `#[output]`, `#[query]`, `#[queryable]` or `#[config]`",
+                ));
+            }
+
+            if attr.path().is_ident("config") {
+                let args = match &attr.meta {
+                    syn::Meta::Path(_) => ConfigArgs {
+                        key: None,
+                        default: None,
+                        flatten: false,
+                    },
+                    _ => attr.parse_args::<ConfigArgs>()?,
+                };
+
+                kind = FieldKind::Config(ConfigField {
+                    key: match (args.key, args.flatten) {
+                        (_, true) => None,
+                        (Some(key), _) => Some(key),
+                        (None, _) => Some(name(attr)?),
+                    },
+                    default: args.default,
+                    ty: field.ty.clone(),
+                });
+
+                continue;
+            }
+
+            let Some(port) = ["input", "output", "query", "queryable"]
+                .iter()
+                .find(|kind| attr.path().is_ident(kind))
+            else {
+                continue;
+            };
+
+            let label = match &attr.meta {
+                syn::Meta::Path(_) => name(attr)?,
+                _ => attr.parse_args::<syn::LitStr>()?.value(),
+            };
+
+            kind = FieldKind::Port(PortField {
+                kind: syn::Ident::new(port, proc_macro2::Span::call_site()),
+                label,
+                ty: field.ty.clone(),
+            });
+        }
+
+        fields.push(NodeField { member, kind });
+    }
+
+    Ok(fields)
+}
+
+/// Generate the constructor of the node: ports are claimed by their label, configuration
+/// fields are deserialized from their key, and the other fields use `Default::default()`.
+/// Unless a field reads the whole configuration, only the `keys` are accepted in it.
+fn constructor(
+    name: &syn::Ident,
+    fields: &[NodeField],
+    keys: &[String],
+) -> proc_macro2::TokenStream {
+    let flattened = fields
+        .iter()
+        .any(|field| matches!(field.kind, FieldKind::Config(ConfigField { key: None, .. })));
+
+    let check = match flattened {
+        true => quote! {},
+        false => quote! {
+            iridis_node::prelude::check_configuration_keys(&configuration, &[#(#keys),*], inputs.source())?;
+        },
+    };
+
+    let values = fields.iter().map(|NodeField { member, kind }| {
+        let value = match kind {
+            FieldKind::Port(PortField { kind, label, ty }) => {
+                let (primitives, port) = match kind.to_string().as_str() {
+                    "input" => (quote! { inputs }, quote! { InputPort }),
+                    "output" => (quote! { outputs }, quote! { OutputPort }),
+                    "query" => (quote! { queries }, quote! { QueryPort }),
+                    _ => (quote! { queryables }, quote! { QueryablePort }),
+                };
+
+                let context = format!("Failed to create {} '{}'", kind, label);
+
+                quote! {
+                    <#ty as iridis_node::prelude::#port>::claim(&mut #primitives, #label.to_string())
+                        .await
+                        .map_err(|error| error.wrap_err(#context))?
+                }
+            }
+            FieldKind::Config(ConfigField { key: None, ty, .. }) => {
+                quote! {
+                    iridis_node::prelude::deserialize_configuration::<#ty>(configuration.clone())?
+                }
+            }
+            FieldKind::Config(ConfigField {
+                key: Some(key),
+                default,
+                ty,
+            }) => {
+                match default {
+                    Some(default) => quote! {
+                        iridis_node::prelude::configuration_field::<#ty>(&configuration, #key, inputs.source())?
+                            .unwrap_or_else(|| #default)
+                    },
+                    None => quote! {
+                        iridis_node::prelude::required_configuration_field::<#ty>(&configuration, #key, inputs.source())?
+                    },
+                }
+            }
+            FieldKind::Default => quote! { Default::default() },
+        };
+
+        quote! { #member: #value }
+    });
+
+    quote! {
+        impl iridis_node::prelude::NodeFields for #name {
+            #[allow(unused_mut, unused_variables)]
+            async fn from_fields(
+                mut inputs: iridis_node::prelude::Inputs,
+                mut outputs: iridis_node::prelude::Outputs,
+                mut queries: iridis_node::prelude::Queries,
+                mut queryables: iridis_node::prelude::Queryables,
+                configuration: iridis_node::prelude::thirdparty::serde_yml::Value,
+            ) -> iridis_node::prelude::thirdparty::eyre::Result<Self> {
+                #check
+
+                Ok(Self { #(#values),* })
+            }
+        }
+    }
+}
+
+/// Parse the `#[configuration(key = "type", ...)]` attribute of the struct into a YAML mapping,
+/// along with the `#[config]` fields. Returns the keys too.
+fn configuration_schema(
+    attrs: &[syn::Attribute],
+    fields: &[NodeField],
+) -> syn::Result<(Vec<String>, Option<proc_macro2::TokenStream>)> {
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    let mut flattened = Vec::new();
+
+    for field in fields {
+        match &field.kind {
+            FieldKind::Config(ConfigField {
+                key: Some(key), ty, ..
+            }) => {
+                keys.push(key.clone());
+                values.push(quote! {
+                    iridis_node::prelude::configuration_schema::<#ty>()
+                        .unwrap_or(iridis_node::prelude::thirdparty::serde_yml::Value::Null)
+                });
+            }
+            FieldKind::Config(ConfigField { key: None, ty, .. }) => flattened.push(ty.clone()),
+            _ => {}
+        }
+    }
+
+    if let Some(attr) = attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("configuration"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let key = meta
+                .path
+                .get_ident()
+                .ok_or_else(|| meta.error("Expected a configuration key"))?
+                .to_string();
+
+            let value = meta.value()?.parse::<syn::LitStr>()?.value();
+
+            keys.push(key);
+            values.push(quote! { #value });
+
+            Ok(())
+        })?;
+    } else if keys.is_empty() && flattened.is_empty() {
+        return Ok((keys, None));
+    }
+
+    let schema = quote! {
+        {
+            let mut schema = iridis_node::prelude::thirdparty::serde_yml::Mapping::new();
+            #(
+                schema.insert(#keys.into(), #values.into());
+            )*
+            #(
+                if let Some(iridis_node::prelude::thirdparty::serde_yml::Value::Mapping(flattened)) =
+                    iridis_node::prelude::configuration_schema::<#flattened>()
+                {
+                    schema.extend(flattened);
+                }
+            )*
+
+            Some(iridis_node::prelude::thirdparty::serde_yml::Value::Mapping(schema))
+        }
+    };
+
+    Ok((keys, Some(schema)))
+}
+
+/// Apply this macro to a struct to generate the `C` symbols, its `NodeManifest`
 /// and the according `tokio::runtime::Runtime`.
-#[proc_macro_derive(Node)]
+///
+/// Ports are declared on the fields with `#[input]`, `#[output]`, `#[query]` and
+/// `#[queryable]`, the label defaults to the name of the field: `#[input("in")]`.
+/// Fields read from the configuration are declared with `#[config]`, `#[config("key")]`
+/// or `#[config(default = 1.0)]`, or `#[config(flatten)]` to read the whole configuration,
+/// and other keys can be described on the struct: `#[configuration(frequency = "F64")]`.
+///
+/// When at least one field is declared, the constructor of the node is generated too as an
+/// implementation of `NodeFields`, the other fields are initialized with `Default::default()`.
+/// A field takes a single one of these attributes.
+#[proc_macro_derive(
+    Node,
+    attributes(input, output, query, queryable, config, configuration)
+)]
 pub fn derive_node(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = input.ident;
 
+    let fields = match node_fields(&input.data) {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let (keys, configuration) = match configuration_schema(&input.attrs, &fields) {
+        Ok(configuration) => configuration,
+        Err(error) => return error.to_compile_error().into(),
+    };
+
+    let ports = fields
+        .iter()
+        .filter_map(|field| match &field.kind {
+            FieldKind::Port(port) => Some(port),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let constructor = match fields
+        .iter()
+        .any(|field| !matches!(field.kind, FieldKind::Default))
+    {
+        true => constructor(&name, &fields, &keys),
+        false => quote! {},
+    };
+
+    let manifest = match (ports.is_empty(), configuration) {
+        (true, None) => quote! { None },
+        (_, configuration) => {
+            let configuration = configuration.unwrap_or(quote! { None });
+
+            // Without any port attribute the ports are not declared, rather than declared empty
+            let ports = match ports.is_empty() {
+                true => quote! { None },
+                false => {
+                    let ports = ports.iter().map(|PortField { kind, label, ty }| {
+                        quote! { iridis_node::prelude::PortManifest::#kind::<#ty>(#label) }
+                    });
+
+                    quote! { Some(vec![#(#ports),*]) }
+                }
+            };
+
+            quote! {
+                Some(iridis_node::prelude::NodeManifest {
+                    name: std::any::type_name::<Self>().to_string(),
+                    ports: #ports,
+                    configuration: #configuration,
+                })
+            }
+        }
+    };
+
     let expanded = quote! {
+        impl #name {
+            #[doc(hidden)]
+            pub fn __iridis_manifest() -> Option<iridis_node::prelude::NodeManifest> {
+                #manifest
+            }
+        }
+
+        #constructor
+
         #[cfg(feature = "cdylib")]
         #[doc(hidden)]
         #[unsafe(no_mangle)]
-        pub static IRIDIS_NODE: iridis_node::prelude::DynamicallyLinkedNodeInstance = |inputs, outputs, queries, queryables, configuration| {
+        pub static IRIDIS_NODE: iridis_node::prelude::DynamicallyLinkedNodeInstance = |runtime, inputs, outputs, queries, queryables, configuration| {
+            NODE_EXECUTOR.set_host(runtime);
+
             <#name>::new(inputs, outputs, queries, queryables, configuration)
         };
 
-        static DEFAULT_TOKIO_RUNTIME: std::sync::LazyLock<iridis_node::prelude::thirdparty::tokio::runtime::Runtime> =
-            std::sync::LazyLock::new(|| iridis_node::prelude::thirdparty::tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime"));
+        #[cfg(feature = "cdylib")]
+        #[doc(hidden)]
+        #[unsafe(no_mangle)]
+        pub static IRIDIS_NODE_METADATA: iridis_node::prelude::LibraryMetadata =
+            iridis_node::prelude::LibraryMetadata::new(iridis_node::prelude::NODE_INTERFACE);
+
+        #[cfg(feature = "cdylib")]
+        #[doc(hidden)]
+        #[unsafe(no_mangle)]
+        pub static IRIDIS_NODE_ABI: iridis_node::prelude::AbiNode = iridis_node::prelude::AbiNode::new::<#name>();
+
+        #[cfg(feature = "cdylib")]
+        #[doc(hidden)]
+        #[unsafe(no_mangle)]
+        pub static IRIDIS_NODE_MANIFEST: iridis_node::prelude::NodeManifestInstance = {
+            extern "C" fn manifest() -> iridis_node::prelude::AbiStr {
+                static MANIFEST: std::sync::LazyLock<String> = std::sync::LazyLock::new(|| {
+                    iridis_node::prelude::NodeManifest::export(<#name as iridis_node::prelude::Node>::manifest().as_ref())
+                });
+
+                iridis_node::prelude::AbiStr::new(&MANIFEST)
+            }
+
+            manifest
+        };
+
+        static NODE_EXECUTOR: iridis_node::prelude::NodeExecutor = iridis_node::prelude::NodeExecutor::new();
 
         fn default_runtime<T: Send + 'static>(
             task: impl Future<Output = T> + Send + 'static,
         ) -> iridis_node::prelude::thirdparty::tokio::task::JoinHandle<T> {
-            match iridis_node::prelude::thirdparty::tokio::runtime::Handle::try_current() {
-                Ok(handle) => handle.spawn(task),
-                Err(_) => DEFAULT_TOKIO_RUNTIME.spawn(task)
-            }
+            NODE_EXECUTOR.spawn(task)
         }
     };
 
     TokenStream::from(expanded)
 }
 
+/// The lifecycle hook of the `Node` trait, it's left `async` and called inside `run`.
+const HOOK: &str = "on_ready";
+
+/// Make a `new` method receive a checked configuration: its last parameter is replaced by
+/// the raw `serde_yml::Value`, and deserialized into the type of the parameter unless it's
+/// marked `#[config(raw)]`. Without a configuration type, the marker is only removed.
+fn configure(method: &mut syn::ImplItemFn, typed: bool) -> syn::Result<()> {
+    let Some(syn::FnArg::Typed(parameter)) = method.sig.inputs.last_mut() else {
+        return Ok(());
+    };
+
+    let mut raw = false;
+
+    for attr in &parameter.attrs {
+        if attr.path().is_ident("config") {
+            let arg = attr.parse_args::<syn::Ident>()?;
+            if arg != "raw" {
+                return Err(syn::Error::new_spanned(arg, "Expected `#[config(raw)]`"));
+            }
+
+            raw = true;
+        }
+    }
+
+    parameter
+        .attrs
+        .retain(|attr| !attr.path().is_ident("config"));
+
+    if !typed {
+        return Ok(());
+    }
+
+    let pat = parameter.pat.clone();
+    let ty = parameter.ty.clone();
+
+    *parameter = syn::parse_quote! {
+        __iridis_configuration: iridis_node::prelude::thirdparty::serde_yml::Value
+    };
+
+    let statement: syn::Stmt = match raw {
+        true => syn::parse_quote! {
+            let #pat: #ty = {
+                <Self as iridis_node::prelude::Node>::check_configuration(&__iridis_configuration)?;
+                __iridis_configuration
+            };
+        },
+        false => syn::parse_quote! {
+            let #pat: #ty = iridis_node::prelude::deserialize_configuration(__iridis_configuration)?;
+        },
+    };
+
+    method.block.stmts.insert(0, statement);
+
+    Ok(())
+}
+
 struct MacroArgs {
     runtime: String,
+    config: Option<syn::Path>,
 }
 
 impl Parse for MacroArgs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         let mut runtime = String::new();
+        let mut config = None;
 
         let vars = Punctuated::<syn::Meta, Token![,]>::parse_terminated(input)?;
 
@@ -63,31 +529,51 @@ impl Parse for MacroArgs {
                         }
                     }
                 }
+
+                if name == "config" {
+                    match &name_value.value {
+                        syn::Expr::Path(path) => config = Some(path.path.clone()),
+                        value => {
+                            return Err(syn::Error::new_spanned(
+                                value,
+                                "Expected the configuration type: `config = MyConfig`",
+                            ));
+                        }
+                    }
+                }
             }
         }
 
-        Ok(MacroArgs { runtime })
+        Ok(MacroArgs { runtime, config })
     }
 }
 
 /// Use this macro to mark an `impl` block on a node. This will alter
 /// the `new` and `start` methods to return a `tokio::task::JoinHandle` with the provided
 /// runtime. The parameter must be a function that takes an `async` closure and returns
-/// a `JoinHandle`.
+/// a `JoinHandle`. When `new` is omitted, the constructor generated by `#[derive(Node)]`
+/// from the declared fields is used.
+///
+/// The lifecycle hook `on_ready` stays `async`: it's called inside `run`, spawned with the
+/// runtime, which then spawns `start` in its own task and forwards the lifecycle events to the
+/// `LifecycleEvents` given to `on_ready`.
+///
+/// With `#[node(runtime = "default_runtime", config = MyConfig)]`, where `MyConfig` implements
+/// `Deserialize`, the configuration is checked against `MyConfig` before the node is created:
+/// unknown fields are rejected and errors name the failing YAML path. The last parameter of
+/// `new` can then be typed `MyConfig`, or be the checked `serde_yml::Value` itself with
+/// `#[config(raw)]`, and the schema of `MyConfig` is added to the manifest.
+///
+/// The `default_runtime` generated by `#[derive(Node)]` spawns on the current runtime of the
+/// host, given to `IRIDIS_NODE`, so dynamically linked nodes run on the executor of the host:
 ///
 /// ```rust,ignore
-/// static DEFAULT_TOKIO_RUNTIME: std::sync::LazyLock<tokio::runtime::Runtime> =
-///     std::sync::LazyLock::new(|| {
-///         tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime")
-///     });
+/// static NODE_EXECUTOR: NodeExecutor = NodeExecutor::new();
 ///
 /// fn default_runtime<T: Send + 'static>(
 ///     task: impl Future<Output = T> + Send + 'static,
 /// ) -> tokio::task::JoinHandle<T> {
-///     match tokio::runtime::Handle::try_current() {
-///         Ok(handle) => handle.spawn(task),
-///         Err(_) => DEFAULT_TOKIO_RUNTIME.spawn(task),
-///     }
+///     NODE_EXECUTOR.spawn(task)
 /// }
 /// ```
 #[proc_macro_attribute]
@@ -97,11 +583,29 @@ pub fn node(attr: TokenStream, item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(attr as MacroArgs);
     let runtime_tokens = args.runtime.parse::<proc_macro2::TokenStream>().unwrap();
 
+    let hooked = impl_block
+        .items
+        .iter()
+        .any(|item| matches!(item, ImplItem::Fn(method) if method.sig.ident == HOOK));
+
     for item in &mut impl_block.items {
         if let ImplItem::Fn(method) = item {
+            if method.sig.ident == "manifest"
+                || method.sig.ident == "check_configuration"
+                || method.sig.ident == HOOK
+            {
+                continue;
+            }
+
             let was_async = method.sig.asyncness.is_some();
             method.sig.asyncness = None;
 
+            if method.sig.ident == "new" {
+                if let Err(error) = configure(method, args.config.is_some()) {
+                    return error.to_compile_error().into();
+                }
+            }
+
             let old_block = method.block.clone();
 
             if was_async {
@@ -143,6 +647,103 @@ pub fn node(attr: TokenStream, item: TokenStream) -> TokenStream {
         }
     }
 
+    let declared = |impl_block: &ItemImpl, name: &str| {
+        impl_block
+            .items
+            .iter()
+            .any(|item| matches!(item, ImplItem::Fn(method) if method.sig.ident == name))
+    };
+
+    if !declared(&impl_block, "new") {
+        // Without fields to build it from, the node doesn't implement `NodeFields`: the error
+        // points to the `impl` and explains what's missing
+        let self_ty = &impl_block.self_ty;
+        let from_fields = quote::quote_spanned! { syn::spanned::Spanned::span(self_ty) =>
+            <#self_ty as iridis_node::prelude::NodeFields>::from_fields(
+                inputs,
+                outputs,
+                queries,
+                queryables,
+                configuration,
+            )
+            .await
+        };
+
+        impl_block.items.push(syn::parse_quote! {
+            fn new(
+                inputs: iridis_node::prelude::Inputs,
+                outputs: iridis_node::prelude::Outputs,
+                queries: iridis_node::prelude::Queries,
+                queryables: iridis_node::prelude::Queryables,
+                configuration: iridis_node::prelude::thirdparty::serde_yml::Value,
+            ) -> iridis_node::prelude::thirdparty::tokio::task::JoinHandle<
+                iridis_node::prelude::thirdparty::eyre::Result<Box<dyn iridis_node::prelude::Node>>,
+            > {
+                #runtime_tokens(async move {
+                    <Self as iridis_node::prelude::Node>::check_configuration(&configuration)?;
+
+                    #from_fields.map(|node| Box::new(node) as Box<dyn iridis_node::prelude::Node>)
+                })
+            }
+        });
+    }
+
+    if !declared(&impl_block, "manifest") {
+        let manifest = match &args.config {
+            // The configuration type alone is enough for the node to have a manifest
+            Some(config) => quote! {
+                let mut manifest = Self::__iridis_manifest().unwrap_or_else(|| {
+                    iridis_node::prelude::NodeManifest {
+                        name: std::any::type_name::<Self>().to_string(),
+                        ports: None,
+                        configuration: None,
+                    }
+                });
+
+                manifest.configuration = iridis_node::prelude::configuration_schema::<#config>();
+
+                Some(manifest)
+            },
+            None => quote! { Self::__iridis_manifest() },
+        };
+
+        impl_block.items.push(syn::parse_quote! {
+            fn manifest() -> Option<iridis_node::prelude::NodeManifest> {
+                #manifest
+            }
+        });
+    }
+
+    if let (Some(config), false) = (&args.config, declared(&impl_block, "check_configuration")) {
+        impl_block.items.push(syn::parse_quote! {
+            fn check_configuration(
+                configuration: &iridis_node::prelude::thirdparty::serde_yml::Value,
+            ) -> iridis_node::prelude::thirdparty::eyre::Result<()> {
+                iridis_node::prelude::deserialize_configuration::<#config>(configuration.clone())
+                    .map(|_| ())
+            }
+        });
+    }
+
+    if hooked {
+        impl_block.items.push(syn::parse_quote! {
+            fn has_hooks(&self) -> bool {
+                true
+            }
+        });
+
+        impl_block.items.push(syn::parse_quote! {
+            fn run(
+                self: Box<Self>,
+                lifecycle: iridis_node::prelude::Lifecycle,
+            ) -> iridis_node::prelude::thirdparty::tokio::task::JoinHandle<
+                iridis_node::prelude::thirdparty::eyre::Result<()>,
+            > {
+                #runtime_tokens(iridis_node::prelude::run_with_hooks(self, lifecycle))
+            }
+        });
+    }
+
     quote! {
         #impl_block
     }
@@ -26,6 +26,14 @@ pub fn derive_url_scheme_plugin(input: TokenStream) -> TokenStream {
         pub static IRIDIS_URL_SCHEME_PLUGIN: DynamicallyLinkedUrlSchemePluginInstance =
             || <#name>::new();
 
+        #[cfg(feature = "cdylib")]
+        #[doc(hidden)]
+        #[unsafe(no_mangle)]
+        pub static IRIDIS_URL_SCHEME_PLUGIN_METADATA: iridis_url_scheme::prelude::LibraryMetadata =
+            iridis_url_scheme::prelude::LibraryMetadata::new(
+                iridis_url_scheme::prelude::URL_SCHEME_PLUGIN_INTERFACE,
+            );
+
         static DEFAULT_TOKIO_RUNTIME: std::sync::LazyLock<iridis_file_ext::prelude::thirdparty::tokio::runtime::Runtime> =
             std::sync::LazyLock::new(|| {
                 iridis_url_scheme::prelude::thirdparty::tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime")
@@ -1,35 +1,50 @@
-//! This module contains the built-in `Tomer` node, which is a simple
+//! This module contains the built-in `Timer` node, which is a simple
 //! source node that emits a "tick" message at a specified frequency.
 
 use std::time::Duration;
 
-use crate::prelude::*;
+use crate::prelude::{iridis_node::prelude::thirdparty::serde::Deserialize, *};
+
+/// The configuration of the `Timer` node.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(crate = "iridis_node::prelude::thirdparty::serde")]
+pub struct TimerConfig {
+    /// The number of ticks per second, 1.0 by default
+    #[serde(default = "TimerConfig::default_frequency")]
+    pub frequency: f64,
+}
+
+impl TimerConfig {
+    fn default_frequency() -> f64 {
+        1.0
+    }
+
+    /// Check the values that are not constrained by their type
+    fn validate(&self) -> Result<()> {
+        if !(self.frequency > 0.0 && self.frequency.is_finite()) {
+            eyre::bail!(
+                "Invalid configuration at 'frequency': expected a finite value > 0, got {}",
+                self.frequency
+            );
+        }
+
+        Ok(())
+    }
+}
 
 /// Simple source node that emits a "tick" message at a specified frequency.
 #[derive(Node)]
 pub struct Timer {
+    #[output("out")]
     pub output: Output<String>,
-    pub frequency: f64,
+    #[config(flatten)]
+    pub configuration: TimerConfig,
 }
 
-#[node(runtime = "default_runtime")]
+#[node(runtime = "default_runtime", config = TimerConfig)]
 impl Node for Timer {
-    async fn new(
-        _: Inputs,
-        mut outputs: Outputs,
-        _: Queries,
-        _: Queryables,
-        configuration: serde_yml::Value,
-    ) -> Result<Self> {
-        let frequency = match configuration.get("frequency") {
-            Some(serde_yml::Value::Number(number)) => number.as_f64().unwrap_or(1.0),
-            _ => 1.0,
-        };
-
-        Ok(Self {
-            output: outputs.with("out").await?,
-            frequency,
-        })
+    fn check_configuration(configuration: &serde_yml::Value) -> Result<()> {
+        deserialize_configuration::<TimerConfig>(configuration.clone())?.validate()
     }
 
     async fn start(self: Box<Self>) -> Result<()> {
@@ -39,7 +54,10 @@ impl Node for Timer {
             .await
             .wrap_err("Failed to send message")
         {
-            tokio::time::sleep(Duration::from_millis((1000.0 / self.frequency) as u64)).await;
+            NodeClock::sleep(Duration::from_millis(
+                (1000.0 / self.configuration.frequency) as u64,
+            ))
+            .await;
         }
 
         Ok(())
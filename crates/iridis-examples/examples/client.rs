@@ -2,31 +2,14 @@ use iridis_node::prelude::{thirdparty::*, *};
 
 #[derive(Node)]
 pub struct MyClient {
+    #[query]
     pub ask_128: Query<u8, String>,
+    #[query]
     pub ask_64: Query<u8, String>,
 }
 
 #[node(runtime = "default_runtime")]
 impl Node for MyClient {
-    async fn new(
-        _: Inputs,
-        _: Outputs,
-        mut queries: Queries,
-        _: Queryables,
-        _: serde_yml::Value,
-    ) -> Result<Self> {
-        Ok(Self {
-            ask_128: queries
-                .with("ask_128")
-                .await
-                .wrap_err("Failed to create ask_128 queryable")?,
-            ask_64: queries
-                .with("ask_64")
-                .await
-                .wrap_err("Failed to create compare_to_64 queryable")?,
-        })
-    }
-
     async fn start(mut self: Box<Self>) -> Result<()> {
         let TypedDataflowMessage {
             header: _,
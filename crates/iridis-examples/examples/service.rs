@@ -2,31 +2,14 @@ use iridis_node::prelude::{thirdparty::*, *};
 
 #[derive(Node)]
 pub struct MyService {
+    #[queryable]
     pub compare_to_128: Queryable<u8, String>,
+    #[queryable]
     pub compare_to_64: Queryable<u8, String>,
 }
 
 #[node(runtime = "default_runtime")]
 impl Node for MyService {
-    async fn new(
-        _: Inputs,
-        _: Outputs,
-        _: Queries,
-        mut queryables: Queryables,
-        _: serde_yml::Value,
-    ) -> Result<Self> {
-        Ok(Self {
-            compare_to_128: queryables
-                .with("compare_to_128")
-                .await
-                .wrap_err("Failed to create compare_to_128 queryable")?,
-            compare_to_64: queryables
-                .with("compare_to_64")
-                .await
-                .wrap_err("Failed to create compare_to_64 queryable")?,
-        })
-    }
-
     async fn start(self: Box<Self>) -> Result<()> {
         let mut compare_to_128 = self.compare_to_128;
         let task_128: tokio::task::JoinHandle<Result<()>> = tokio::spawn(async move {
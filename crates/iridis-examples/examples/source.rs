@@ -4,26 +4,12 @@ use iridis_node::prelude::{thirdparty::*, *};
 
 #[derive(Node)]
 pub struct MySource {
+    #[output("out")]
     pub output: Output<String>,
 }
 
 #[node(runtime = "default_runtime")]
 impl Node for MySource {
-    async fn new(
-        _: Inputs,
-        mut outputs: Outputs,
-        _: Queries,
-        _: Queryables,
-        _: serde_yml::Value,
-    ) -> Result<Self> {
-        Ok(Self {
-            output: outputs
-                .with("out")
-                .await
-                .wrap_err("Failed to create output")?,
-        })
-    }
-
     async fn start(self: Box<Self>) -> Result<()> {
         while let Ok(()) = self
             .output
@@ -15,6 +15,11 @@ pub struct DataLayout {
     pub outputs: HashSet<Uuid>,
     pub queries: HashSet<Uuid>,
     pub queryables: HashSet<Uuid>,
+
+    /// The placement of the nodes that don't run on the shared executor
+    pub placements: HashMap<Uuid, Placement>,
+    /// The machine of each node, for a distributed dataflow
+    pub machines: HashMap<Uuid, String>,
 }
 
 /// Represents the debug layout of the application: the labels
@@ -59,6 +64,8 @@ impl DataflowLayout {
                 outputs: HashSet::new(),
                 queryables: HashSet::new(),
                 queries: HashSet::new(),
+                placements: HashMap::new(),
+                machines: HashMap::new(),
             })),
             debug: Arc::new(Mutex::new(DebugLayout {
                 labels: HashMap::new(),
@@ -71,6 +78,20 @@ impl DataflowLayout {
     pub fn label(&self, uuid: impl AsRef<Uuid>) -> String {
         self.debug.label(uuid)
     }
+
+    /// Gets the placement of a node, `Placement::Shared` if it has none.
+    pub fn placement(&self, node: &NodeID) -> Placement {
+        self.data
+            .placements
+            .get(&node.uuid)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Gets the machine of a node, `None` if it has none.
+    pub fn machine(&self, node: &NodeID) -> Option<&str> {
+        self.data.machines.get(&node.uuid).map(String::as_str)
+    }
 }
 
 impl SharedDataLayout {
@@ -107,6 +128,14 @@ impl SharedDataLayout {
         data.queries.extend(layout.data.queries);
         data.queryables.extend(layout.data.queryables);
 
+        if let Some(placement) = layout.data.placement {
+            data.placements.insert(id.uuid, placement);
+        }
+
+        if let Some(machine) = layout.data.machine {
+            data.machines.insert(id.uuid, machine);
+        }
+
         debug.labels.extend(layout.debug.labels);
         debug.labels.insert(id.uuid, label.clone());
 
@@ -7,6 +7,7 @@
 pub(crate) mod flows;
 pub(crate) mod layout;
 pub(crate) mod node;
+pub(crate) mod placement;
 pub(crate) mod primitives;
 
 /// This prelude contains everything you need to use this crate.
@@ -14,6 +15,7 @@ pub mod prelude {
     pub use crate::flows::*;
     pub use crate::layout::*;
     pub use crate::node::*;
+    pub use crate::placement::*;
     pub use crate::primitives::*;
 
     pub(crate) use thirdparty::*;
@@ -75,6 +75,9 @@ pub struct NodeDataLayout {
     pub outputs: HashSet<Uuid>,
     pub queries: HashSet<Uuid>,
     pub queryables: HashSet<Uuid>,
+
+    pub placement: Option<Placement>,
+    pub machine: Option<String>,
 }
 
 /// For internal use, this struct represents the debug layout of a node.
@@ -101,6 +104,8 @@ impl NodeLayout {
                 outputs: HashSet::new(),
                 queries: HashSet::new(),
                 queryables: HashSet::new(),
+                placement: None,
+                machine: None,
             },
             debug: NodeDebugLayout {
                 labels: HashMap::new(),
@@ -108,6 +113,17 @@ impl NodeLayout {
         }
     }
 
+    /// Sets the executor this node runs on, see `Placement`. The loader can override it.
+    pub fn place(&mut self, placement: Placement) {
+        self.data.placement = Some(placement);
+    }
+
+    /// Sets the machine this node runs on, when the dataflow is distributed across several
+    /// runtimes.
+    pub fn machine(&mut self, machine: impl Into<String>) {
+        self.data.machine = Some(machine.into());
+    }
+
     /// Adds a new input to the node layout. It returns a generic
     /// enum `PrimitiveID` that can be used to identify the input.
     pub fn input(&mut self, input: impl Into<String>) -> PrimitiveID {
@@ -0,0 +1,16 @@
+//! This module defines where the tasks of a node run. It's part of the layout, so
+//! latency-sensitive nodes can be isolated from the heavy ones before the runtime starts.
+
+/// The executor a node runs on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Placement {
+    /// The `tokio` runtime of the host, shared with every other node
+    #[default]
+    Shared,
+    /// A dedicated OS thread with a current-thread `tokio` runtime, optionally pinned to a CPU core
+    Dedicated { core: Option<usize> },
+    /// A named pool of worker threads, declared on the runtime
+    Pool(String),
+    /// A current-thread `tokio` runtime on a thread of the blocking pool, for nodes that block
+    Blocking,
+}
@@ -0,0 +1,20 @@
+//! Record the compiler and the target of this build, they're exported in the metadata
+//! of the dynamically linked libraries.
+
+fn main() {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+
+    let version = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+
+    println!("cargo:rustc-env=IRIDIS_RUSTC_VERSION={}", version);
+    println!("cargo:rustc-env=IRIDIS_TARGET={}", target);
+    println!("cargo:rerun-if-changed=build.rs");
+}
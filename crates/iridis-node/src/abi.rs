@@ -0,0 +1,13 @@
+//! This module defines the stable C ABI between the runtime and the dynamically linked
+//! nodes. Unlike the `IRIDIS_NODE` symbol, it doesn't rely on the Rust ABI or on the
+//! layout of the `tokio` channels, so a node built with another compiler, another version
+//! of `iridis` or another version of `tokio` can still be loaded by the runtime.
+//!
+//! - Messages are passed through the Arrow C Data Interface.
+//! - Primitives are opaque handles owned by the runtime, used with the `extern "C"` functions of `AbiHost`.
+//! - The configuration is passed as a YAML string.
+
+pub(crate) mod guest;
+pub(crate) mod types;
+
+pub use types::*;
@@ -0,0 +1,400 @@
+//! This module implements the node side of the C ABI. It bridges the opaque handles of
+//! the runtime to the regular primitives, so any `Node` can be exported through the C ABI.
+
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::prelude::{
+    thirdparty::tokio::{
+        self,
+        runtime::Runtime,
+        sync::RwLock,
+        task::{AbortHandle, JoinHandle},
+    },
+    *,
+};
+
+/// The runtime of the nodes exported through the C ABI. The host can't share its own `tokio`
+/// through the C ABI, so these nodes always run here and ignore the `Placement` chosen by the host:
+/// only the `AbiLinkedNode` that drives them is placed.
+static ABI_RUNTIME: LazyLock<Runtime> =
+    LazyLock::new(|| Runtime::new().expect("Failed to create Tokio runtime"));
+
+/// A handle of the runtime, it can be used from any thread.
+#[derive(Clone, Copy)]
+struct Handle(*mut c_void);
+
+unsafe impl Send for Handle {}
+unsafe impl Sync for Handle {}
+
+/// The bridges between the handles of the runtime and the channels of the node. Each one is
+/// a task of `ABI_RUNTIME`, and calls the blocking functions of `AbiHost` on its blocking pool.
+struct Bridges {
+    host: AbiHost,
+
+    handles: Mutex<Vec<(AbiPrimitive, Handle)>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Bridges {
+    fn new(host: AbiHost) -> Self {
+        Self {
+            host,
+            handles: Mutex::new(Vec::new()),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn report(&self, report: eyre::Report) {
+        let message = format!("{:?}", report);
+
+        unsafe { (self.host.report)(self.host.context, AbiStr::new(&message)) }
+    }
+
+    fn open(&self, kind: AbiPrimitive, primitive: PrimitiveID) -> Option<Handle> {
+        let label = primitive.label();
+        let handle = unsafe { (self.host.open)(self.host.context, kind, AbiStr::new(&label)) };
+
+        if handle.is_null() {
+            return None;
+        }
+
+        self.handles
+            .lock()
+            .expect("Bridges lock poisoned")
+            .push((kind, Handle(handle)));
+
+        Some(Handle(handle))
+    }
+
+    /// Close every handle and wait for the bridges to end. The channels of the node must
+    /// have been dropped already. Only the receiving handles are closed before waiting, so
+    /// the messages still buffered for the outputs are sent.
+    fn close(&self) {
+        let handles = std::mem::take(&mut *self.handles.lock().expect("Bridges lock poisoned"));
+        let (receiving, sending): (Vec<_>, Vec<_>) = handles.into_iter().partition(|(kind, _)| {
+            *kind == AbiPrimitive::INPUT || *kind == AbiPrimitive::QUERYABLE
+        });
+
+        for (_, handle) in receiving {
+            unsafe { (self.host.close)(handle.0) };
+        }
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("Bridges lock poisoned"));
+        ABI_RUNTIME.block_on(async {
+            for task in tasks {
+                let _ = task.await;
+            }
+        });
+
+        for (_, handle) in sending {
+            unsafe { (self.host.close)(handle.0) };
+        }
+    }
+
+    fn spawn(&self, bridge: impl Future<Output = ()> + Send + 'static) {
+        self.tasks
+            .lock()
+            .expect("Bridges lock poisoned")
+            .push(ABI_RUNTIME.spawn(bridge));
+    }
+
+    /// Call a blocking function of the host without blocking the runtime
+    async fn call<T: Send + 'static>(
+        call: impl FnOnce() -> Option<T> + Send + 'static,
+    ) -> Option<T> {
+        tokio::task::spawn_blocking(call).await.ok().flatten()
+    }
+
+    async fn recv(host: AbiHost, handle: Handle) -> Option<DataflowMessage> {
+        Self::call(move || {
+            let handle = handle;
+            let mut message = AbiMessage::empty();
+
+            match unsafe { (host.recv)(handle.0, &mut message) } {
+                AbiStatus::OK => unsafe { message.try_into_message() }.ok(),
+                _ => None,
+            }
+        })
+        .await
+    }
+
+    async fn send(host: AbiHost, handle: Handle, message: DataflowMessage) -> bool {
+        Self::call(move || {
+            let handle = handle;
+            let mut message = AbiMessage::try_from_message(message).ok()?;
+
+            match unsafe { (host.send)(handle.0, &mut message) } {
+                AbiStatus::OK => Some(()),
+                _ => None,
+            }
+        })
+        .await
+        .is_some()
+    }
+
+    async fn query(
+        host: AbiHost,
+        handle: Handle,
+        request: DataflowMessage,
+    ) -> Option<DataflowMessage> {
+        Self::call(move || {
+            let handle = handle;
+            let mut request = AbiMessage::try_from_message(request).ok()?;
+            let mut response = AbiMessage::empty();
+
+            match unsafe { (host.query)(handle.0, &mut request, &mut response) } {
+                AbiStatus::OK => unsafe { response.try_into_message() }.ok(),
+                _ => None,
+            }
+        })
+        .await
+    }
+
+    fn input(&self, handle: Handle) -> MessageReceiver {
+        let (sender, receiver) = tokio::sync::mpsc::channel(128);
+        let host = self.host;
+
+        self.spawn(async move {
+            while let Some(message) = Self::recv(host, handle).await {
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        receiver
+    }
+
+    fn output(&self, handle: Handle) -> SharedOutputChannels {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(128);
+        let host = self.host;
+
+        self.spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if !Self::send(host, handle, message).await {
+                    break;
+                }
+            }
+        });
+
+        let mut channels = OutputChannels::default();
+        channels.connections.insert(Uuid::new_v4(), sender);
+
+        Arc::new(RwLock::new(channels))
+    }
+
+    fn query_channels(&self, handle: Handle) -> (MessageSender, MessageReceiver) {
+        let (request_sender, mut requests) = tokio::sync::mpsc::channel(128);
+        let (responses, response_receiver) = tokio::sync::mpsc::channel(128);
+        let host = self.host;
+
+        self.spawn(async move {
+            while let Some(request) = requests.recv().await {
+                let Some(response) = Self::query(host, handle, request).await else {
+                    break;
+                };
+
+                if responses.send(response).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        (request_sender, response_receiver)
+    }
+
+    fn queryable(&self, handle: Handle) -> (HashMap<Uuid, MessageSender>, MessageReceiver) {
+        let (requests, request_receiver) = tokio::sync::mpsc::channel(128);
+        let (response_sender, mut responses) = tokio::sync::mpsc::channel(128);
+        let host = self.host;
+
+        // The queryable answers to its requests one by one, so every query can share the
+        // same response channel
+        let mut senders = HashMap::new();
+        let mut uuid = AbiUuid::from(Uuid::nil());
+        while unsafe { (host.connected)(handle.0, senders.len(), &mut uuid) } {
+            senders.insert(uuid.into(), response_sender.clone());
+        }
+
+        self.spawn(async move {
+            while let Some(request) = Self::recv(host, handle).await {
+                if requests.send(request).await.is_err() {
+                    break;
+                }
+
+                let Some(response) = responses.recv().await else {
+                    break;
+                };
+
+                if !Self::send(host, handle, response).await {
+                    break;
+                }
+            }
+        });
+
+        (senders, request_receiver)
+    }
+}
+
+/// A node exported through the C ABI, with its bridges.
+struct GuestNode {
+    node: Mutex<Option<Box<dyn Node>>>,
+    task: Mutex<Option<AbortHandle>>,
+    stopped: AtomicBool,
+
+    bridges: Arc<Bridges>,
+}
+
+impl Drop for GuestNode {
+    fn drop(&mut self) {
+        drop(self.node.lock().map(|mut node| node.take()));
+
+        self.bridges.close();
+    }
+}
+
+unsafe extern "C" fn abi_new<T: Node + 'static>(
+    host: AbiHost,
+    label: AbiStr,
+    uuid: AbiUuid,
+    configuration: AbiStr,
+) -> *mut c_void {
+    let bridges = Arc::new(Bridges::new(host));
+
+    let node = (|| {
+        let source = NodeID {
+            label: unsafe { label.as_str()? }.to_string(),
+            uuid: uuid.into(),
+        };
+
+        let configuration: serde_yml::Value =
+            serde_yml::from_str(unsafe { configuration.as_str()? })
+                .wrap_err("Failed to parse the configuration passed through the C ABI")?;
+
+        let clock = Arc::new(HLC::default());
+
+        let resolver = bridges.clone();
+        let inputs = Inputs::new(Default::default(), source.clone()).with_resolver(Resolver::new(
+            move |primitive| Some(resolver.input(resolver.open(AbiPrimitive::INPUT, primitive)?)),
+        ));
+
+        let resolver = bridges.clone();
+        let outputs = Outputs::new(Default::default(), clock.clone(), source.clone())
+            .with_resolver(Resolver::new(move |primitive| {
+                Some(resolver.output(resolver.open(AbiPrimitive::OUTPUT, primitive)?))
+            }));
+
+        let resolver = bridges.clone();
+        let queries = Queries::new(
+            Default::default(),
+            Default::default(),
+            clock.clone(),
+            source.clone(),
+        )
+        .with_resolver(Resolver::new(move |primitive| {
+            Some(resolver.query_channels(resolver.open(AbiPrimitive::QUERY, primitive)?))
+        }));
+
+        let resolver = bridges.clone();
+        let queryables = Queryables::new(Default::default(), Default::default(), clock, source)
+            .with_resolver(Resolver::new(move |primitive| {
+                Some(resolver.queryable(resolver.open(AbiPrimitive::QUERYABLE, primitive)?))
+            }));
+
+        let task = {
+            let _guard = ABI_RUNTIME.enter();
+
+            T::new(inputs, outputs, queries, queryables, configuration)
+        };
+
+        ABI_RUNTIME.block_on(task)?
+    })();
+
+    match node {
+        Ok(node) => Box::into_raw(Box::new(GuestNode {
+            node: Mutex::new(Some(node)),
+            task: Mutex::new(None),
+            stopped: AtomicBool::new(false),
+            bridges,
+        })) as *mut c_void,
+        Err(report) => {
+            bridges.report(report);
+            bridges.close();
+
+            std::ptr::null_mut()
+        }
+    }
+}
+
+unsafe extern "C" fn abi_start(node: *mut c_void) -> AbiStatus {
+    let guest = unsafe { &*(node as *const GuestNode) };
+
+    let Some(node) = guest.node.lock().ok().and_then(|mut node| node.take()) else {
+        return AbiStatus::ERROR;
+    };
+
+    let task = {
+        let _guard = ABI_RUNTIME.enter();
+
+        node.start()
+    };
+
+    if let Ok(mut abort) = guest.task.lock() {
+        *abort = Some(task.abort_handle());
+    }
+
+    // `stop` may have been called before the task was stored
+    if guest.stopped.load(Ordering::SeqCst) {
+        task.abort();
+    }
+
+    match ABI_RUNTIME.block_on(task) {
+        Ok(Ok(())) => AbiStatus::OK,
+        Err(error) if error.is_cancelled() => AbiStatus::OK,
+        Ok(Err(report)) => {
+            guest.bridges.report(report);
+
+            AbiStatus::ERROR
+        }
+        Err(error) => {
+            guest.bridges.report(error.into());
+
+            AbiStatus::ERROR
+        }
+    }
+}
+
+unsafe extern "C" fn abi_stop(node: *mut c_void) {
+    let guest = unsafe { &*(node as *const GuestNode) };
+
+    guest.stopped.store(true, Ordering::SeqCst);
+
+    if let Some(task) = guest.task.lock().ok().and_then(|task| task.clone()) {
+        task.abort();
+    }
+}
+
+unsafe extern "C" fn abi_drop(node: *mut c_void) {
+    drop(unsafe { Box::from_raw(node as *mut GuestNode) });
+}
+
+impl AbiNode {
+    /// The C ABI of a `Node`, to export as the `IRIDIS_NODE_ABI` symbol. The node runs on its
+    /// own `tokio` runtime, inside the library.
+    pub const fn new<T: Node + 'static>() -> Self {
+        Self {
+            version: IRIDIS_ABI_VERSION,
+            new: abi_new::<T>,
+            start: abi_start,
+            stop: abi_stop,
+            drop: abi_drop,
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! This module contains the `#[repr(C)]` types shared by the runtime and the nodes.
+
+use std::ffi::c_void;
+
+use crate::prelude::{
+    thirdparty::{
+        arrow_array::ffi::{FFI_ArrowArray, FFI_ArrowSchema, from_ffi, to_ffi},
+        uhlc::{ID, NTP64, Timestamp},
+    },
+    *,
+};
+
+/// The version of the C ABI. The runtime refuses nodes built for another version.
+pub const IRIDIS_ABI_VERSION: u32 = 4;
+
+/// A borrowed UTF-8 string, valid for the duration of the call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AbiStr {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl AbiStr {
+    /// Borrow a Rust string, the string must outlive the call it's passed to
+    pub const fn new(value: &str) -> Self {
+        Self {
+            ptr: value.as_ptr(),
+            len: value.len(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The pointer must be valid for `len` bytes during the lifetime `'a`.
+    pub unsafe fn as_str<'a>(&self) -> Result<&'a str> {
+        if self.ptr.is_null() {
+            return Ok("");
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(self.ptr, self.len) };
+
+        std::str::from_utf8(bytes).wrap_err("Invalid UTF-8 string passed through the C ABI")
+    }
+}
+
+/// Bytes owned by the receiver of a message, released with the function of their sender.
+#[repr(C)]
+#[derive(Debug)]
+pub struct AbiBytes {
+    pub ptr: *mut u8,
+    pub len: usize,
+    pub release: Option<unsafe extern "C" fn(*mut u8, usize)>,
+}
+
+unsafe extern "C" fn release_bytes(ptr: *mut u8, len: usize) {
+    drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)) });
+}
+
+impl AbiBytes {
+    pub const fn empty() -> Self {
+        Self {
+            ptr: std::ptr::null_mut(),
+            len: 0,
+            release: None,
+        }
+    }
+
+    pub fn new(bytes: Vec<u8>) -> Self {
+        if bytes.is_empty() {
+            return Self::empty();
+        }
+
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+
+        Self {
+            ptr: bytes as *mut u8,
+            len: bytes.len(),
+            release: Some(release_bytes),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The pointer must be valid for `len` bytes, or null.
+    pub unsafe fn to_vec(&self) -> Vec<u8> {
+        match self.ptr.is_null() {
+            true => Vec::new(),
+            false => unsafe { std::slice::from_raw_parts(self.ptr, self.len) }.to_vec(),
+        }
+    }
+}
+
+impl Drop for AbiBytes {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            unsafe { release(self.ptr, self.len) };
+        }
+    }
+}
+
+/// A uuid, as its 16 bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiUuid {
+    pub bytes: [u8; 16],
+}
+
+impl From<Uuid> for AbiUuid {
+    fn from(uuid: Uuid) -> Self {
+        Self {
+            bytes: uuid.into_bytes(),
+        }
+    }
+}
+
+impl From<AbiUuid> for Uuid {
+    fn from(uuid: AbiUuid) -> Self {
+        Uuid::from_bytes(uuid.bytes)
+    }
+}
+
+/// The status returned by every function of the C ABI. It's a plain integer, not an enum,
+/// because the other side can return any value: check it with `AbiStatus::validate`.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiStatus(pub i32);
+
+impl AbiStatus {
+    pub const OK: Self = Self(0);
+    /// The channel is closed, or the node has been stopped by the runtime
+    pub const CLOSED: Self = Self(1);
+    pub const ERROR: Self = Self(2);
+
+    /// Fail if the status is not one of the known ones
+    pub fn validate(self) -> Result<Self> {
+        match self {
+            Self::OK | Self::CLOSED | Self::ERROR => Ok(self),
+            Self(status) => Err(eyre::eyre!(
+                "Invalid status {} returned through the C ABI",
+                status
+            )),
+        }
+    }
+}
+
+/// The kind of primitive to open with `AbiHost::open`, a plain integer like `AbiStatus`.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiPrimitive(pub i32);
+
+impl AbiPrimitive {
+    pub const INPUT: Self = Self(0);
+    pub const OUTPUT: Self = Self(1);
+    pub const QUERY: Self = Self(2);
+    pub const QUERYABLE: Self = Self(3);
+}
+
+/// The `Header` of a message: the `HLC` timestamp, the uuids of the source node and primitive,
+/// the `TraceContext`, all zeros without one, the sequence number and the encoded metadata.
+#[repr(C)]
+#[derive(Debug)]
+pub struct AbiHeader {
+    pub time: u64,
+    pub id: [u8; 16],
+
+    pub node: [u8; 16],
+    pub primitive: [u8; 16],
+
+    pub trace: [u8; 24],
+
+    pub sequence: u64,
+    pub metadata: AbiBytes,
+}
+
+/// A message passed through the C ABI. The data is exported with the Arrow C Data Interface,
+/// the receiver of an `AbiMessage` owns it and is responsible for releasing it.
+#[repr(C)]
+#[derive(Debug)]
+pub struct AbiMessage {
+    pub header: AbiHeader,
+
+    pub array: FFI_ArrowArray,
+    pub schema: FFI_ArrowSchema,
+}
+
+impl AbiMessage {
+    /// An empty message, used as the destination of a call
+    pub fn empty() -> Self {
+        Self {
+            header: AbiHeader {
+                time: 0,
+                id: [0; 16],
+                node: [0; 16],
+                primitive: [0; 16],
+                trace: [0; 24],
+                sequence: 0,
+                metadata: AbiBytes::empty(),
+            },
+            array: FFI_ArrowArray::empty(),
+            schema: FFI_ArrowSchema::empty(),
+        }
+    }
+
+    /// Export a `DataflowMessage` to the C ABI
+    pub fn try_from_message(message: DataflowMessage) -> Result<Self> {
+        let (array, schema) = to_ffi(&message.data)?;
+
+        Ok(Self {
+            header: AbiHeader {
+                time: message.header.timestamp.get_time().as_u64(),
+                id: message.header.timestamp.get_id().to_le_bytes(),
+                node: message.header.source.0.into_bytes(),
+                primitive: message.header.source.1.into_bytes(),
+                trace: message
+                    .header
+                    .trace
+                    .map(|trace| trace.to_bytes())
+                    .unwrap_or_default(),
+                sequence: message.header.sequence,
+                metadata: AbiBytes::new(message.header.metadata.encode()),
+            },
+            array,
+            schema,
+        })
+    }
+
+    /// Import a `DataflowMessage` from the C ABI
+    ///
+    /// # Safety
+    ///
+    /// The message must have been filled according to the Arrow C Data Interface.
+    pub unsafe fn try_into_message(self) -> Result<DataflowMessage> {
+        let data = unsafe { from_ffi(self.array, &self.schema)? };
+
+        Ok(DataflowMessage {
+            header: Header {
+                timestamp: Timestamp::new(
+                    NTP64(self.header.time),
+                    ID::try_from(self.header.id).map_err(eyre::Report::msg)?,
+                ),
+                source: (
+                    Uuid::from_bytes(self.header.node),
+                    Uuid::from_bytes(self.header.primitive),
+                ),
+                trace: TraceContext::from_bytes(self.header.trace),
+                sequence: self.header.sequence,
+                metadata: HeaderMetadata::decode(&unsafe { self.header.metadata.to_vec() })?,
+            },
+            data,
+        })
+    }
+
+    /// Take the message out of a pointer, leaving an empty message behind
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be valid and point to a message filled according to the Arrow C Data Interface.
+    pub unsafe fn take(message: *mut AbiMessage) -> Result<DataflowMessage> {
+        unsafe { std::ptr::replace(message, AbiMessage::empty()).try_into_message() }
+    }
+}
+
+/// The functions of the runtime a node can call. Every function is blocking, and can be
+/// called from any thread. The handles returned by `open` are owned by the runtime and
+/// stay valid until `drop` returns, or until `new` returns null.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AbiHost {
+    pub context: *mut c_void,
+
+    /// Claim a primitive of the node by its label. Returns a null handle if it doesn't exist.
+    pub open: unsafe extern "C" fn(*mut c_void, AbiPrimitive, AbiStr) -> *mut c_void,
+    /// Write the uuid of the n-th query connected to a queryable. Returns `false` when out of range.
+    pub connected: unsafe extern "C" fn(*mut c_void, usize, *mut AbiUuid) -> bool,
+
+    /// Receive a message from an input, or the next request of a queryable.
+    pub recv: unsafe extern "C" fn(*mut c_void, *mut AbiMessage) -> AbiStatus,
+    /// Send a message with an output, or the response of a queryable to its last request.
+    pub send: unsafe extern "C" fn(*mut c_void, *mut AbiMessage) -> AbiStatus,
+    /// Send a request with a query and wait for the response.
+    pub query: unsafe extern "C" fn(*mut c_void, *mut AbiMessage, *mut AbiMessage) -> AbiStatus,
+    /// Close a handle, pending and future calls with it return `AbiStatus::CLOSED`.
+    pub close: unsafe extern "C" fn(*mut c_void),
+
+    /// Report an error of the node to the runtime.
+    pub report: unsafe extern "C" fn(*mut c_void, AbiStr),
+}
+
+unsafe impl Send for AbiHost {}
+unsafe impl Sync for AbiHost {}
+
+/// The functions a node exports through the `IRIDIS_NODE_ABI` symbol.
+///
+/// The runtime calls `new` then `start` on a thread of its blocking pool, and `start` only returns
+/// when the node ends. `stop` can be called from any thread while `start` is running. Finally
+/// `drop` is called once. When `new` returns null or when `drop` returns, the node must not use
+/// its handles anymore: close them and wait for the calls in progress.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AbiNode {
+    pub version: u32,
+
+    /// Create the node from the host, the label and uuid of the node, and its configuration.
+    /// Returns null on failure, after reporting the error.
+    pub new: unsafe extern "C" fn(AbiHost, AbiStr, AbiUuid, AbiStr) -> *mut c_void,
+    pub start: unsafe extern "C" fn(*mut c_void) -> AbiStatus,
+    pub stop: unsafe extern "C" fn(*mut c_void),
+    pub drop: unsafe extern "C" fn(*mut c_void),
+}
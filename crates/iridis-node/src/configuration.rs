@@ -0,0 +1,84 @@
+//! This module deserializes the typed configuration of a node, declared with
+//! `#[node(config = MyConfig)]`, and describes its schema for the `NodeManifest`.
+
+use crate::prelude::{thirdparty::serde::de::DeserializeOwned, *};
+
+use serde_reflection::{ContainerFormat, Format, Registry, Tracer, TracerConfig};
+
+/// Deserialize the configuration of a node. An empty configuration is read as an empty mapping,
+/// unknown fields are rejected and errors name the failing YAML path.
+pub fn deserialize_configuration<C: DeserializeOwned>(
+    configuration: serde_yml::Value,
+) -> Result<C> {
+    let configuration = match configuration {
+        serde_yml::Value::Null => serde_yml::Value::Mapping(serde_yml::Mapping::new()),
+        configuration => configuration,
+    };
+
+    let mut unknown = Vec::new();
+    let mut callback = |path: serde_ignored::Path| unknown.push(path.to_string());
+
+    let result: std::result::Result<C, _> = serde_path_to_error::deserialize(
+        serde_ignored::Deserializer::new(configuration, &mut callback),
+    );
+
+    let configuration = result.map_err(|error| {
+        eyre::eyre!(
+            "Invalid configuration at '{}': {}",
+            error.path(),
+            error.inner()
+        )
+    })?;
+
+    match unknown.is_empty() {
+        true => Ok(configuration),
+        false => Err(eyre::eyre!(
+            "Unknown configuration field(s) {} for {}",
+            unknown
+                .iter()
+                .map(|path| format!("'{}'", path))
+                .collect::<Vec<_>>()
+                .join(", "),
+            std::any::type_name::<C>()
+        )),
+    }
+}
+
+/// The schema of a configuration type: a YAML mapping of its fields to their formats, with
+/// nested structs expanded. Returns `None` if the type can't be traced, e.g. `serde_yml::Value`.
+pub fn configuration_schema<C: DeserializeOwned>() -> Option<serde_yml::Value> {
+    let mut tracer = Tracer::new(TracerConfig::default());
+
+    let (format, _) = tracer.trace_simple_type::<C>().ok()?;
+    let registry = tracer.registry().ok()?;
+
+    Some(format_schema(&format, &registry))
+}
+
+fn format_schema(format: &Format, registry: &Registry) -> serde_yml::Value {
+    match format {
+        Format::TypeName(name) => match registry.get(name) {
+            Some(ContainerFormat::Struct(fields)) => serde_yml::Value::Mapping(
+                fields
+                    .iter()
+                    .map(|field| {
+                        (
+                            field.name.clone().into(),
+                            format_schema(&field.value, registry),
+                        )
+                    })
+                    .collect(),
+            ),
+            Some(ContainerFormat::NewTypeStruct(format)) => format_schema(format, registry),
+            Some(container) => serde_yml::to_value(container).unwrap_or(serde_yml::Value::Null),
+            None => name.clone().into(),
+        },
+        Format::Option(format) => {
+            let mut mapping = serde_yml::Mapping::new();
+            mapping.insert("OPTION".into(), format_schema(format, registry));
+
+            serde_yml::Value::Mapping(mapping)
+        }
+        format => serde_yml::to_value(format).unwrap_or(serde_yml::Value::Null),
+    }
+}
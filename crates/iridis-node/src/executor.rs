@@ -0,0 +1,142 @@
+//! This module defines the executor used by `#[derive(Node)]` to spawn the tasks of a node.
+//! A dynamically linked node has its own copy of `tokio`, whose thread-local context is never
+//! set by the host: the runtime gives a `HostRuntime` to `IRIDIS_NODE`, and the tasks of the
+//! node are spawned on the current runtime of the host and enter it on every poll.
+
+use std::{
+    pin::Pin,
+    sync::{LazyLock, OnceLock},
+    task::{Context, Poll},
+};
+
+use tracing::Instrument;
+
+use crate::prelude::{
+    thirdparty::{
+        Context as _,
+        tokio::{
+            runtime::{Handle, Runtime},
+            task::JoinHandle,
+        },
+    },
+    *,
+};
+
+fn fallback_runtime() -> Runtime {
+    Runtime::new().expect("Failed to create Tokio runtime")
+}
+
+/// The `tokio` context of the host, given to dynamically linked nodes. It must be created by
+/// the host, so it reads the context of the `tokio` used by the host. The node uses the `Handle`
+/// with its own copy of `tokio`, which is only sound if both are the exact same build: it can
+/// only be created for a library whose metadata matches `NODE_INTERFACE`.
+#[derive(Debug, Clone)]
+pub struct HostRuntime {
+    handle: Handle,
+    current: fn() -> Option<Handle>,
+
+    #[cfg(target_os = "linux")]
+    arena: fn() -> crate::prelude::thirdparty::Result<crate::prelude::SharedArena>,
+}
+
+impl HostRuntime {
+    /// The context of the host for a library with the given metadata, `handle` is used when the
+    /// host calls the node from outside of any runtime. Fails if the library doesn't exchange the
+    /// same types as the host, `tokio` included.
+    pub fn new(handle: Handle, library: &Metadata) -> Result<Self> {
+        library
+            .check(&Metadata::current(NODE_INTERFACE))
+            .wrap_err("Refusing to share the runtime of the host")?;
+
+        Ok(Self {
+            handle,
+            current: || Handle::try_current().ok(),
+
+            #[cfg(target_os = "linux")]
+            arena: crate::prelude::SharedArena::local,
+        })
+    }
+
+    /// The runtime the host is currently running on, e.g. the executor of the node.
+    pub fn current(&self) -> Handle {
+        (self.current)().unwrap_or_else(|| self.handle.clone())
+    }
+}
+
+/// Spawns the tasks of a node: on the current `tokio` runtime, or on the current runtime of
+/// the host for a dynamically linked node, and as a last resort on a runtime owned by the node.
+pub struct NodeExecutor {
+    host: OnceLock<HostRuntime>,
+    fallback: LazyLock<Runtime>,
+}
+
+impl Default for NodeExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NodeExecutor {
+    pub const fn new() -> Self {
+        Self {
+            host: OnceLock::new(),
+            fallback: LazyLock::new(fallback_runtime),
+        }
+    }
+
+    /// Use the runtime of the host when there's no current runtime, and its `SharedArena`.
+    /// Only the first `HostRuntime` is kept, a library is only ever loaded by one runtime.
+    pub fn set_host(&self, host: HostRuntime) {
+        #[cfg(target_os = "linux")]
+        crate::prelude::SharedArena::set_host(host.arena);
+
+        let _ = self.host.set(host);
+    }
+
+    /// The context given by the host, if any
+    pub fn host(&self) -> Option<&HostRuntime> {
+        self.host.get()
+    }
+
+    pub fn spawn<T: Send + 'static>(
+        &self,
+        task: impl Future<Output = T> + Send + 'static,
+    ) -> JoinHandle<T> {
+        // The task runs in the span of the node, entered by the runtime while starting it
+        let task = task.in_current_span();
+
+        if let Ok(handle) = Handle::try_current() {
+            return handle.spawn(task);
+        }
+
+        match self.host.get() {
+            Some(host) => {
+                let handle = host.current();
+
+                handle.spawn(Entered {
+                    handle: handle.clone(),
+                    task: Box::pin(task),
+                })
+            }
+            None => self.fallback.spawn(task),
+        }
+    }
+}
+
+/// A task that enters the runtime of the host each time it's polled, so `tokio` timers,
+/// IOs and `tokio::spawn` work from the code of a dynamically linked node.
+struct Entered<F> {
+    handle: Handle,
+    task: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Entered<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let _guard = this.handle.enter();
+
+        this.task.as_mut().poll(cx)
+    }
+}
@@ -1,15 +1,37 @@
 //! This module contains everything to write an `iridis` node.
 
+pub(crate) mod abi;
+pub(crate) mod configuration;
+pub(crate) mod executor;
+pub(crate) mod lifecycle;
+pub(crate) mod manifest;
 pub(crate) mod message;
+pub(crate) mod metadata;
+pub(crate) mod metrics;
 pub(crate) mod node;
 pub(crate) mod primitives;
 pub(crate) mod report;
+#[cfg(target_os = "linux")]
+pub(crate) mod shm;
+pub(crate) mod simulation;
+pub(crate) mod trace;
 
 /// This prelude contains everything you need to use this crate.
 pub mod prelude {
+    pub use crate::abi::*;
+    pub use crate::configuration::*;
+    pub use crate::executor::*;
+    pub use crate::lifecycle::*;
+    pub use crate::manifest::*;
     pub use crate::message::*;
+    pub use crate::metadata::*;
+    pub use crate::metrics::*;
     pub use crate::node::*;
     pub use crate::primitives::*;
+    #[cfg(target_os = "linux")]
+    pub use crate::shm::*;
+    pub use crate::simulation::*;
+    pub use crate::trace::*;
 
     pub use iridis_node_derive::*;
 
@@ -22,7 +44,9 @@ pub mod prelude {
 
     pub mod thirdparty {
         pub use arrow_array;
+        pub use arrow_buffer;
         pub use arrow_data;
+        pub use serde;
         pub use serde_yml;
         pub use tokio;
         pub use uhlc::{self, HLC};
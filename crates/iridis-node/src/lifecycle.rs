@@ -0,0 +1,129 @@
+//! This module defines the lifecycle of a node: the events sent by the runtime to a running
+//! node, and the loop that forwards them to the node while its `start` function is running.
+
+use crate::prelude::{
+    thirdparty::tokio::sync::{mpsc, oneshot, watch},
+    *,
+};
+
+/// An event sent by the runtime to a running node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    /// Graceful shutdown request, the node is aborted if it doesn't end in time
+    Stop,
+    /// The node should stop processing its inputs until it's resumed
+    Pause,
+    Resume,
+    /// Another node of the dataflow failed, with its report
+    PeerError(NodeID, String),
+}
+
+/// The node side of the lifecycle, given to `Node::run`.
+#[derive(Debug)]
+pub struct Lifecycle {
+    ready: Option<oneshot::Sender<()>>,
+    start: watch::Receiver<bool>,
+
+    events: mpsc::UnboundedReceiver<LifecycleEvent>,
+}
+
+impl Lifecycle {
+    /// Create the lifecycle of a node, it's allowed to start once `start` is set. Returns the
+    /// sender of its events, and the receiver notified when the node is ready (or gone).
+    pub fn new(
+        start: watch::Receiver<bool>,
+    ) -> (
+        Self,
+        mpsc::UnboundedSender<LifecycleEvent>,
+        oneshot::Receiver<()>,
+    ) {
+        let (ready, ready_receiver) = oneshot::channel();
+        let (events, events_receiver) = mpsc::unbounded_channel();
+
+        (
+            Self {
+                ready: Some(ready),
+                start,
+                events: events_receiver,
+            },
+            events,
+            ready_receiver,
+        )
+    }
+
+    /// Tell the runtime the node is ready, and `await` for every other node of the dataflow
+    /// to be ready too. Returns `false` if the node is stopped before.
+    pub async fn ready(&mut self) -> bool {
+        if let Some(ready) = self.ready.take() {
+            let _ = ready.send(());
+        }
+
+        loop {
+            tokio::select! {
+                started = self.start.wait_for(|start| *start) => return started.is_ok(),
+                event = self.events.recv() => match event {
+                    Some(LifecycleEvent::Stop) | None => return false,
+                    Some(_) => {}
+                },
+            }
+        }
+    }
+
+    /// Receive the next event, `None` if the runtime is gone.
+    pub async fn recv(&mut self) -> Option<LifecycleEvent> {
+        self.events.recv().await
+    }
+}
+
+/// The events of a running node, given to `Node::on_ready`. The node receives them while
+/// `start` is running, alongside its inputs.
+#[derive(Debug)]
+pub struct LifecycleEvents {
+    events: mpsc::UnboundedReceiver<LifecycleEvent>,
+}
+
+impl LifecycleEvents {
+    /// Receive the next event, `None` if the runtime is gone or the node is ending.
+    pub async fn recv(&mut self) -> Option<LifecycleEvent> {
+        self.events.recv().await
+    }
+}
+
+/// The task of `start`, aborted when it's dropped so it doesn't outlive `run`.
+struct Started(tokio::task::JoinHandle<Result<()>>);
+
+impl Drop for Started {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run a node with its hooks: `on_ready`, then `start` in its own task once every node of the
+/// dataflow is ready. The events received meanwhile are forwarded to the `LifecycleEvents` given
+/// to `on_ready`, and the node is dropped without being started if it's stopped before.
+pub async fn run_with_hooks<N: Node + 'static>(
+    mut node: Box<N>,
+    mut lifecycle: Lifecycle,
+) -> Result<()> {
+    let (events, receiver) = mpsc::unbounded_channel();
+
+    node.on_ready(LifecycleEvents { events: receiver }).await?;
+
+    if !lifecycle.ready().await {
+        return Ok(());
+    }
+
+    let mut task = Started(node.start());
+
+    loop {
+        tokio::select! {
+            result = &mut task.0 => return result?,
+            event = lifecycle.recv() => match event {
+                Some(event) => {
+                    let _ = events.send(event);
+                }
+                None => return (&mut task.0).await?,
+            },
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! This module defines the manifest of a node: the ports it declares, with their labels
+//! and message types, and its configuration schema. It's generated by `#[derive(Node)]`
+//! from the attributes of the struct, and exported next to `IRIDIS_NODE` by dynamically
+//! linked nodes, so it can be read without instantiating the node. The same attributes let
+//! `#[derive(Node)]` generate the constructor of the node, with the helpers below.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::prelude::{
+    thirdparty::serde::{self, Deserialize, Serialize},
+    *,
+};
+
+/// The kind of a port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde", rename_all = "lowercase")]
+pub enum PortKind {
+    Input,
+    Output,
+    Query,
+    Queryable,
+}
+
+impl std::fmt::Display for PortKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortKind::Input => write!(f, "input"),
+            PortKind::Output => write!(f, "output"),
+            PortKind::Query => write!(f, "query"),
+            PortKind::Queryable => write!(f, "queryable"),
+        }
+    }
+}
+
+/// The type of the messages of a port: the Rust type and its Arrow representation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde")]
+pub struct MessageManifest {
+    pub rust: String,
+    pub arrow: String,
+}
+
+impl MessageManifest {
+    pub fn of<T: ArrowMessage>() -> Self {
+        Self {
+            rust: std::any::type_name::<T>().to_string(),
+            arrow: T::field("").data_type().to_string(),
+        }
+    }
+}
+
+/// A port declared by a node. Raw ports have no message type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde")]
+pub struct PortManifest {
+    pub label: String,
+    pub kind: PortKind,
+
+    /// The messages received by an input, sent by an output, or the requests of a query/queryable
+    pub message: Option<MessageManifest>,
+    /// The responses of a query/queryable
+    pub response: Option<MessageManifest>,
+}
+
+/// Implemented by every primitive, so `#[derive(Node)]` can describe the fields of a node.
+pub trait Port {
+    const KIND: PortKind;
+
+    /// The message and response types of this port
+    fn messages() -> (Option<MessageManifest>, Option<MessageManifest>);
+}
+
+/// Implemented by the primitives that can be declared with `#[input]`
+pub trait InputPort: Port + Sized {
+    /// Claim the primitive from the inputs of the node
+    fn claim(inputs: &mut Inputs, label: String) -> impl Future<Output = Result<Self>> + Send;
+}
+
+/// Implemented by the primitives that can be declared with `#[output]`
+pub trait OutputPort: Port + Sized {
+    /// Claim the primitive from the outputs of the node
+    fn claim(outputs: &mut Outputs, label: String) -> impl Future<Output = Result<Self>> + Send;
+}
+
+/// Implemented by the primitives that can be declared with `#[query]`
+pub trait QueryPort: Port + Sized {
+    /// Claim the primitive from the queries of the node
+    fn claim(queries: &mut Queries, label: String) -> impl Future<Output = Result<Self>> + Send;
+}
+
+/// Implemented by the primitives that can be declared with `#[queryable]`
+pub trait QueryablePort: Port + Sized {
+    /// Claim the primitive from the queryables of the node
+    fn claim(
+        queryables: &mut Queryables,
+        label: String,
+    ) -> impl Future<Output = Result<Self>> + Send;
+}
+
+macro_rules! impl_port {
+    ($kind:ident, $marker:ident, $primitives:ty, $raw:ty, $typed:ident<$($t:ident),*>, $messages:expr) => {
+        impl Port for $raw {
+            const KIND: PortKind = PortKind::$kind;
+
+            fn messages() -> (Option<MessageManifest>, Option<MessageManifest>) {
+                (None, None)
+            }
+        }
+
+        impl $marker for $raw {
+            fn claim(
+                primitives: &mut $primitives,
+                label: String,
+            ) -> impl Future<Output = Result<Self>> + Send {
+                primitives.raw(label)
+            }
+        }
+
+        impl<$($t: ArrowMessage),*> Port for $typed<$($t),*> {
+            const KIND: PortKind = PortKind::$kind;
+
+            fn messages() -> (Option<MessageManifest>, Option<MessageManifest>) {
+                $messages
+            }
+        }
+
+        impl<$($t: ArrowMessage),*> $marker for $typed<$($t),*> {
+            fn claim(
+                primitives: &mut $primitives,
+                label: String,
+            ) -> impl Future<Output = Result<Self>> + Send {
+                primitives.with(label)
+            }
+        }
+    };
+}
+
+impl_port!(Input, InputPort, Inputs, RawInput, Input<T>, {
+    (Some(MessageManifest::of::<T>()), None)
+});
+impl_port!(Output, OutputPort, Outputs, RawOutput, Output<T>, {
+    (Some(MessageManifest::of::<T>()), None)
+});
+impl_port!(Query, QueryPort, Queries, RawQuery, Query<T, F>, {
+    (Some(MessageManifest::of::<T>()), Some(MessageManifest::of::<F>()))
+});
+impl_port!(Queryable, QueryablePort, Queryables, RawQueryable, Queryable<T, F>, {
+    (Some(MessageManifest::of::<T>()), Some(MessageManifest::of::<F>()))
+});
+
+/// Read a key of the configuration for a field declared with `#[config]`. Returns `None`
+/// if the key is absent, and an error naming the node and the key if it can't be deserialized.
+pub fn configuration_field<T: serde::de::DeserializeOwned>(
+    configuration: &serde_yml::Value,
+    key: &str,
+    node: &NodeID,
+) -> Result<Option<T>> {
+    configuration
+        .get(key)
+        .map(|value| serde_yml::from_value(value.clone()))
+        .transpose()
+        .wrap_err(format!(
+            "Invalid configuration key '{}' for node '{}' (uuid: {})",
+            key, node.label, node.uuid
+        ))
+}
+
+/// Read a key of the configuration for a field declared with `#[config]` without a default.
+/// A missing key is an error, unless `T` accepts `null` (e.g. an `Option`).
+pub fn required_configuration_field<T: serde::de::DeserializeOwned>(
+    configuration: &serde_yml::Value,
+    key: &str,
+    node: &NodeID,
+) -> Result<T> {
+    match configuration_field(configuration, key, node)? {
+        Some(value) => Ok(value),
+        None => serde_yml::from_value(serde_yml::Value::Null).map_err(|_| {
+            eyre::eyre!(
+                "Missing configuration key '{}' for node '{}' (uuid: {})",
+                key,
+                node.label,
+                node.uuid
+            )
+        }),
+    }
+}
+
+/// Check that every key of the configuration is claimed by a `#[config]` field or described
+/// with `#[configuration]`, so a misspelled key isn't silently replaced by a default.
+pub fn check_configuration_keys(
+    configuration: &serde_yml::Value,
+    keys: &[&str],
+    node: &NodeID,
+) -> Result<()> {
+    let unknown = match configuration {
+        serde_yml::Value::Null => Vec::new(),
+        serde_yml::Value::Mapping(mapping) => mapping
+            .keys()
+            .filter(|key| !key.as_str().is_some_and(|key| keys.contains(&key)))
+            .map(|key| format!("'{}'", serde_yml::to_string(key).unwrap_or_default().trim()))
+            .collect(),
+        _ => eyre::bail!(
+            "The configuration of node '{}' (uuid: {}) must be a mapping",
+            node.label,
+            node.uuid
+        ),
+    };
+
+    match unknown.is_empty() {
+        true => Ok(()),
+        false => Err(eyre::eyre!(
+            "Unknown configuration key(s) {} for node '{}' (uuid: {}), expected one of: {}",
+            unknown.join(", "),
+            node.label,
+            node.uuid,
+            keys.join(", ")
+        )),
+    }
+}
+
+impl PortManifest {
+    fn new<P: Port>(label: impl Into<String>) -> Self {
+        let (message, response) = P::messages();
+
+        Self {
+            label: label.into(),
+            kind: P::KIND,
+            message,
+            response,
+        }
+    }
+
+    pub fn input<P: InputPort>(label: impl Into<String>) -> Self {
+        Self::new::<P>(label)
+    }
+
+    pub fn output<P: OutputPort>(label: impl Into<String>) -> Self {
+        Self::new::<P>(label)
+    }
+
+    pub fn query<P: QueryPort>(label: impl Into<String>) -> Self {
+        Self::new::<P>(label)
+    }
+
+    pub fn queryable<P: QueryablePort>(label: impl Into<String>) -> Self {
+        Self::new::<P>(label)
+    }
+
+    /// The uuid of this port for a node
+    pub fn uuid(&self, node: &NodeID) -> Uuid {
+        match self.kind {
+            PortKind::Input => node.input(&self.label).uuid,
+            PortKind::Output => node.output(&self.label).uuid,
+            PortKind::Query => node.query(&self.label).uuid,
+            PortKind::Queryable => node.queryable(&self.label).uuid,
+        }
+    }
+}
+
+/// Everything a node declares about itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde")]
+pub struct NodeManifest {
+    /// The Rust type of the node
+    pub name: String,
+
+    /// The ports of the node, `None` if it doesn't declare them: they're not checked then
+    pub ports: Option<Vec<PortManifest>>,
+
+    /// The schema of the configuration, `None` if the node doesn't declare one
+    pub configuration: Option<serde_yml::Value>,
+}
+
+/// The type of the `IRIDIS_NODE_MANIFEST` symbol: it returns the manifest serialized in YAML,
+/// or `null` if the node doesn't declare its ports. The string lives as long as the library.
+pub type NodeManifestInstance = extern "C" fn() -> AbiStr;
+
+impl NodeManifest {
+    /// Get a port by its label and kind
+    pub fn port(&self, kind: PortKind, label: &str) -> Option<&PortManifest> {
+        self.ports
+            .iter()
+            .flatten()
+            .find(|port| port.kind == kind && port.label == label)
+    }
+
+    /// Add every port of the manifest to a `NodeLayout`, returns them by label.
+    ///
+    /// ```rust,ignore
+    /// let (sink, ports) = layout
+    ///     .node("sink", async |builder: &mut NodeLayout| manifest.layout(builder))
+    ///     .await;
+    /// ```
+    pub fn layout(&self, builder: &mut NodeLayout) -> HashMap<String, PrimitiveID> {
+        self.ports
+            .iter()
+            .flatten()
+            .map(|port| {
+                let primitive = match port.kind {
+                    PortKind::Input => builder.input(&port.label),
+                    PortKind::Output => builder.output(&port.label),
+                    PortKind::Query => builder.query(&port.label),
+                    PortKind::Queryable => builder.queryable(&port.label),
+                };
+
+                (port.label.clone(), primitive)
+            })
+            .collect()
+    }
+
+    /// Check the ports of a node in a layout against this manifest, before the node is instantiated.
+    /// Nothing is checked if the manifest doesn't declare the ports.
+    pub fn check(&self, layout: &DataflowLayout, node: &NodeID) -> Result<()> {
+        let Some(ports) = &self.ports else {
+            return Ok(());
+        };
+
+        let primitives = layout
+            .debug
+            .nodes
+            .get(&node.uuid)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut errors = Vec::new();
+        let mut declared = HashSet::new();
+
+        for port in ports {
+            let uuid = port.uuid(node);
+            declared.insert(uuid);
+
+            let kinds = [
+                (PortKind::Input, &layout.data.inputs),
+                (PortKind::Output, &layout.data.outputs),
+                (PortKind::Query, &layout.data.queries),
+                (PortKind::Queryable, &layout.data.queryables),
+            ];
+
+            let present = primitives.contains(&uuid)
+                && kinds
+                    .iter()
+                    .any(|(kind, uuids)| *kind == port.kind && uuids.contains(&uuid));
+
+            if !present {
+                errors.push(format!(
+                    "{} '{}' is declared by the node but missing from the layout",
+                    port.kind, port.label
+                ));
+            }
+        }
+
+        for uuid in primitives.difference(&declared) {
+            errors.push(format!(
+                "'{}' is in the layout but not declared by the node",
+                layout.label(uuid)
+            ));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(eyre::eyre!(
+                "The layout of node '{}' (uuid: {}) doesn't match the manifest of '{}': {}",
+                node.label,
+                node.uuid,
+                self.name,
+                errors.join(", ")
+            )),
+        }
+    }
+
+    /// Serialize an optional manifest, as exported by `IRIDIS_NODE_MANIFEST`
+    pub fn export(manifest: Option<&NodeManifest>) -> String {
+        serde_yml::to_string(&manifest).unwrap_or_else(|_| "null".to_string())
+    }
+
+    /// Deserialize an optional manifest, as exported by `IRIDIS_NODE_MANIFEST`
+    pub fn import(manifest: &str) -> Result<Option<NodeManifest>> {
+        serde_yml::from_str(manifest).wrap_err("Failed to parse the manifest of the node")
+    }
+}
@@ -1,12 +1,14 @@
 //! This module defines a message type for the dataflow communication
 
+use std::collections::BTreeMap;
+
 use arrow_data::ArrayData;
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::mpsc::{Receiver, Sender, WeakSender};
 use uuid::Uuid;
 
 use crate::prelude::*;
 
-use uhlc::Timestamp;
+use uhlc::{ID, NTP64, Timestamp};
 
 /// Header for a dataflow message
 #[derive(Debug, PartialEq, Clone)]
@@ -16,6 +18,337 @@ pub struct Header {
 
     /// Identifier of the message, representing the source node uuid and the IO it's coming from (output, query or queryable)
     pub source: (Uuid, Uuid),
+
+    /// The trace of the message and the span that sent it, see `TraceContext`
+    pub trace: Option<TraceContext>,
+
+    /// The number of messages sent by the output or the query before this one. A response
+    /// has the sequence number of its request.
+    pub sequence: u64,
+
+    /// The metadata set by the nodes, see `HeaderMetadata`
+    pub metadata: HeaderMetadata,
+}
+
+impl Header {
+    /// A header without trace, sequence number nor metadata, e.g. for a message sent by the host
+    pub fn new(timestamp: Timestamp, source: (Uuid, Uuid)) -> Self {
+        Self {
+            timestamp,
+            source,
+            trace: None,
+            sequence: 0,
+            metadata: HeaderMetadata::default(),
+        }
+    }
+}
+
+/// The largest key or value of the metadata of a message, in bytes
+pub const MAX_METADATA_SIZE: usize = 1024;
+
+/// The most values in the metadata of a message
+pub const MAX_METADATA_VALUES: usize = 256;
+
+/// The most hops kept in the `Lineage` of a message
+pub const MAX_LINEAGE_HOPS: usize = 64;
+
+/// Where the data of a message comes from: when it was first sent, and the (node, primitive)
+/// of every output or query it went through since, the first one being its source. Nodes
+/// start a lineage by sending a message with `Lineage::default()`, outputs forwarding the
+/// metadata of the messages they receive extend it, and operators deriving a message from
+/// others keep the lineage of their oldest parent, see `HeaderMetadata::derived`.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Lineage {
+    origin: Option<Timestamp>,
+    hops: Vec<(Uuid, Uuid)>,
+}
+
+impl Lineage {
+    /// When the data was first sent, `None` until the lineage goes through an output
+    pub fn origin(&self) -> Option<Timestamp> {
+        self.origin
+    }
+
+    /// The (node, primitive) the data went through, from its source. Only the source and the
+    /// last `MAX_LINEAGE_HOPS - 1` hops are kept.
+    pub fn hops(&self) -> &[(Uuid, Uuid)] {
+        &self.hops
+    }
+
+    /// The (node, primitive) that first sent the data
+    pub fn source(&self) -> Option<(Uuid, Uuid)> {
+        self.hops.first().cloned()
+    }
+
+    /// Replace the uuids of every hop, e.g. by the uuids of the same nodes in another runtime
+    pub fn translate(&mut self, mut uuid: impl FnMut(Uuid) -> Uuid) {
+        for (node, primitive) in &mut self.hops {
+            (*node, *primitive) = (uuid(*node), uuid(*primitive));
+        }
+    }
+
+    /// Record that the data is sent by `primitive` at `timestamp`
+    pub fn hop(&mut self, timestamp: Timestamp, primitive: (Uuid, Uuid)) {
+        self.origin.get_or_insert(timestamp);
+
+        if self.hops.len() >= MAX_LINEAGE_HOPS {
+            self.hops.remove(1);
+        }
+
+        self.hops.push(primitive);
+    }
+}
+
+/// A value of the metadata of a message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MetadataValue {
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl MetadataValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MetadataValue::String(value) => Some(value),
+            MetadataValue::Bytes(_) => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            MetadataValue::String(value) => value.as_bytes(),
+            MetadataValue::Bytes(value) => value,
+        }
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::String(value.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        MetadataValue::String(value)
+    }
+}
+
+impl From<&[u8]> for MetadataValue {
+    fn from(value: &[u8]) -> Self {
+        MetadataValue::Bytes(value.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for MetadataValue {
+    fn from(value: Vec<u8>) -> Self {
+        MetadataValue::Bytes(value)
+    }
+}
+
+/// The metadata of a message: a correlation id, its lineage and small values by key. Nodes
+/// read it from the messages they receive and set it on the messages they send, operators
+/// forwarding messages keep it.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct HeaderMetadata {
+    /// Identifies the messages related to the same request. A response has the correlation
+    /// id of its request.
+    pub correlation: Option<Uuid>,
+
+    /// Where the data comes from, only tracked when a node starts it, see `Lineage`
+    pub lineage: Option<Lineage>,
+
+    values: BTreeMap<String, MetadataValue>,
+}
+
+impl HeaderMetadata {
+    /// The metadata of a message derived from `parents`: it keeps the lineage of the parent
+    /// whose data is the oldest, so the latency of the derived message covers all of them.
+    pub fn derived<'a>(parents: impl IntoIterator<Item = &'a Header>) -> Self {
+        let lineage = parents
+            .into_iter()
+            .filter_map(|parent| parent.metadata.lineage.as_ref())
+            .filter(|lineage| lineage.origin.is_some())
+            .min_by_key(|lineage| lineage.origin)
+            .cloned();
+
+        Self {
+            lineage,
+            ..Default::default()
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.values.get(key)
+    }
+
+    /// Set the value of `key`, both must be at most `MAX_METADATA_SIZE` bytes long. A new key
+    /// is refused once the metadata holds `MAX_METADATA_VALUES` values.
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<MetadataValue>) -> Result<()> {
+        let (key, value) = (key.into(), value.into());
+
+        if key.len() > MAX_METADATA_SIZE || value.as_bytes().len() > MAX_METADATA_SIZE {
+            eyre::bail!(
+                "Metadata '{}' is larger than {} bytes",
+                key,
+                MAX_METADATA_SIZE
+            );
+        }
+
+        if self.values.len() >= MAX_METADATA_VALUES && !self.values.contains_key(&key) {
+            eyre::bail!(
+                "Metadata '{}' can't be set, there are already {} values",
+                key,
+                MAX_METADATA_VALUES
+            );
+        }
+
+        self.values.insert(key, value);
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<MetadataValue> {
+        self.values.remove(key)
+    }
+
+    /// Every value, by key
+    pub fn values(&self) -> impl Iterator<Item = (&String, &MetadataValue)> {
+        self.values.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.correlation.is_none() && self.lineage.is_none() && self.values.is_empty()
+    }
+
+    /// Encode the metadata to pass it across a process or a library, empty metadata is encoded
+    /// to nothing.
+    pub fn encode(&self) -> Vec<u8> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut bytes = Vec::new();
+
+        // Which of the correlation id, the lineage and its origin are present
+        let flags = self.correlation.is_some() as u8
+            | (self.lineage.is_some() as u8) << 1
+            | (self
+                .lineage
+                .as_ref()
+                .is_some_and(|lineage| lineage.origin.is_some()) as u8)
+                << 2;
+
+        bytes.push(flags);
+
+        if let Some(correlation) = self.correlation {
+            bytes.extend(correlation.into_bytes());
+        }
+
+        if let Some(lineage) = &self.lineage {
+            if let Some(origin) = lineage.origin {
+                bytes.extend(origin.get_time().as_u64().to_le_bytes());
+                bytes.extend(origin.get_id().to_le_bytes());
+            }
+
+            bytes.extend((lineage.hops.len() as u16).to_le_bytes());
+            for (node, primitive) in &lineage.hops {
+                bytes.extend(node.into_bytes());
+                bytes.extend(primitive.into_bytes());
+            }
+        }
+
+        bytes.extend((self.values.len() as u16).to_le_bytes());
+
+        for (key, value) in &self.values {
+            let (kind, value) = match value {
+                MetadataValue::String(value) => (0, value.as_bytes()),
+                MetadataValue::Bytes(value) => (1, value.as_slice()),
+            };
+
+            bytes.extend((key.len() as u16).to_le_bytes());
+            bytes.extend(key.as_bytes());
+            bytes.push(kind);
+            bytes.extend((value.len() as u16).to_le_bytes());
+            bytes.extend(value);
+        }
+
+        bytes
+    }
+
+    /// Decode the bytes written by `encode`
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+            if bytes.len() < len {
+                eyre::bail!("Truncated message metadata");
+            }
+
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+
+            Ok(head)
+        }
+
+        fn len(bytes: &mut &[u8]) -> Result<usize> {
+            Ok(u16::from_le_bytes(take(bytes, 2)?.try_into()?) as usize)
+        }
+
+        let mut metadata = Self::default();
+
+        if bytes.is_empty() {
+            return Ok(metadata);
+        }
+
+        let mut bytes = bytes;
+
+        fn uuid(bytes: &mut &[u8]) -> Result<Uuid> {
+            Ok(Uuid::from_bytes(take(bytes, 16)?.try_into()?))
+        }
+
+        let flags = take(&mut bytes, 1)?[0];
+
+        if flags & 1 != 0 {
+            metadata.correlation = Some(uuid(&mut bytes)?);
+        }
+
+        if flags & 2 != 0 {
+            let mut lineage = Lineage::default();
+
+            if flags & 4 != 0 {
+                let time = u64::from_le_bytes(take(&mut bytes, 8)?.try_into()?);
+                let id: [u8; 16] = take(&mut bytes, 16)?.try_into()?;
+
+                lineage.origin = Some(Timestamp::new(
+                    NTP64(time),
+                    ID::try_from(id).map_err(eyre::Report::msg)?,
+                ));
+            }
+
+            for _ in 0..len(&mut bytes)? {
+                lineage.hops.push((uuid(&mut bytes)?, uuid(&mut bytes)?));
+            }
+
+            metadata.lineage = Some(lineage);
+        }
+
+        for _ in 0..len(&mut bytes)? {
+            let key_len = len(&mut bytes)?;
+            let key = String::from_utf8(take(&mut bytes, key_len)?.to_vec())?;
+
+            let kind = take(&mut bytes, 1)?[0];
+            let value_len = len(&mut bytes)?;
+            let value = take(&mut bytes, value_len)?.to_vec();
+
+            let value = match kind {
+                0 => MetadataValue::String(String::from_utf8(value)?),
+                _ => MetadataValue::Bytes(value),
+            };
+
+            metadata.values.insert(key, value);
+        }
+
+        Ok(metadata)
+    }
 }
 
 /// Dataflow message. Cheap to clone
@@ -28,6 +361,9 @@ pub struct DataflowMessage {
 /// MPSC Message sender. Can be cloned, cheap to clone
 pub type MessageSender = Sender<DataflowMessage>;
 
+/// Weak MPSC Message sender. It does not keep the channel open, upgrade it to send messages
+pub type WeakMessageSender = WeakSender<DataflowMessage>;
+
 /// MPSC Message receiver. Cannot be cloned
 pub type MessageReceiver = Receiver<DataflowMessage>;
 
@@ -0,0 +1,158 @@
+//! This module defines the metadata exported by every dynamically linked library, next to the
+//! symbol of the node or plugin. The runtime checks it before calling anything else in the
+//! library, because the Rust ABI is only stable for a given compiler, target and `iridis` version,
+//! and for the exact types exchanged through the symbol.
+
+use std::{
+    any::TypeId,
+    hash::{Hash, Hasher},
+};
+
+use crate::prelude::*;
+
+/// The version of the `LibraryMetadata` layout itself.
+pub const IRIDIS_METADATA_VERSION: u32 = 2;
+
+/// Hash the interface `T` of a symbol, from its `TypeId`. The `TypeId` of a type depends on the
+/// compiler and on the version and features of every crate that defines a part of it, so two
+/// builds only get the same hash if they exchange the exact same types, `tokio` included.
+pub extern "C" fn interface_hash<T: ?Sized + 'static>() -> u64 {
+    /// FNV-1a, so the hash doesn't depend on the keys of the standard hasher
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 ^= *byte as u64;
+                self.0 = self.0.wrapping_mul(0x100000001b3);
+            }
+        }
+    }
+
+    let mut hasher = Fnv(0xcbf29ce484222325);
+    TypeId::of::<T>().hash(&mut hasher);
+
+    hasher.finish()
+}
+
+/// The metadata of a library, as exported in its symbol. Only static strings are stored.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LibraryMetadata {
+    /// Always `IRIDIS_METADATA_VERSION`, the other fields are only read if it matches
+    pub version: u32,
+
+    pub iridis: AbiStr,
+    pub rustc: AbiStr,
+    pub target: AbiStr,
+
+    /// The hash of the Rust interface used by the symbol of the node or plugin, computed by the
+    /// library itself, see `interface_hash`
+    pub interface: extern "C" fn() -> u64,
+}
+
+unsafe impl Send for LibraryMetadata {}
+unsafe impl Sync for LibraryMetadata {}
+
+impl LibraryMetadata {
+    /// The metadata of the current build, for an interface.
+    pub const fn new(interface: extern "C" fn() -> u64) -> Self {
+        Self {
+            version: IRIDIS_METADATA_VERSION,
+            iridis: AbiStr::new(env!("CARGO_PKG_VERSION")),
+            rustc: AbiStr::new(env!("IRIDIS_RUSTC_VERSION")),
+            target: AbiStr::new(env!("IRIDIS_TARGET")),
+            interface,
+        }
+    }
+
+    /// Copy the metadata out of the library.
+    ///
+    /// # Safety
+    ///
+    /// The strings must be valid, which is the case for a `LibraryMetadata` read from a library
+    /// that is still loaded and that exports the same `version`.
+    pub unsafe fn to_metadata(&self) -> Result<Metadata> {
+        if self.version != IRIDIS_METADATA_VERSION {
+            eyre::bail!(
+                "Unsupported metadata version {}, expected {}",
+                self.version,
+                IRIDIS_METADATA_VERSION
+            );
+        }
+
+        Ok(Metadata {
+            iridis: unsafe { self.iridis.as_str()? }.to_string(),
+            rustc: unsafe { self.rustc.as_str()? }.to_string(),
+            target: unsafe { self.target.as_str()? }.to_string(),
+            interface: (self.interface)(),
+        })
+    }
+}
+
+/// The metadata of a library, owned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    pub iridis: String,
+    pub rustc: String,
+    pub target: String,
+
+    pub interface: u64,
+}
+
+impl Metadata {
+    /// The metadata of the current build, for an interface.
+    pub fn current(interface: extern "C" fn() -> u64) -> Self {
+        Self {
+            iridis: env!("CARGO_PKG_VERSION").to_string(),
+            rustc: env!("IRIDIS_RUSTC_VERSION").to_string(),
+            target: env!("IRIDIS_TARGET").to_string(),
+            interface: interface(),
+        }
+    }
+
+    /// Check that a library with this metadata can be used by a runtime with the `expected` one.
+    pub fn check(&self, expected: &Metadata) -> Result<()> {
+        let mut mismatches = Vec::new();
+
+        if self.iridis != expected.iridis {
+            mismatches.push(format!(
+                "built with iridis {} instead of {}",
+                self.iridis, expected.iridis
+            ));
+        }
+
+        if self.rustc != expected.rustc {
+            mismatches.push(format!(
+                "built with '{}' instead of '{}'",
+                self.rustc, expected.rustc
+            ));
+        }
+
+        if self.target != expected.target {
+            mismatches.push(format!(
+                "built for target '{}' instead of '{}'",
+                self.target, expected.target
+            ));
+        }
+
+        if self.interface != expected.interface {
+            mismatches.push(format!(
+                "interface hash {:#018x} instead of {:#018x} (the exchanged types differ, check the versions of iridis and tokio)",
+                self.interface, expected.interface
+            ));
+        }
+
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(eyre::eyre!(
+                "Incompatible library: {}",
+                mismatches.join(", ")
+            )),
+        }
+    }
+}
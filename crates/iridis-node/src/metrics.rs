@@ -0,0 +1,238 @@
+//! This module defines the metrics collected by the primitives while a dataflow runs: the
+//! messages and bytes that go through every primitive and every connection, how long outputs
+//! are blocked by full channels, how long messages wait in the channels, the round trip of
+//! the queries, the errors of the queryables and the end-to-end latency of the messages
+//! whose `Lineage` is tracked. The runtime gives every node the `Metrics`
+//! of its dataflow, and reads them to build its snapshots.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::prelude::{thirdparty::uhlc::Timestamp, *};
+
+/// The number of buckets of a `Histogram`, the last one holds everything above 2^30 µs
+pub const HISTOGRAM_BUCKETS: usize = 32;
+
+/// A histogram of durations, whose buckets grow by powers of two from 1µs.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    buckets: [AtomicU64; HISTOGRAM_BUCKETS],
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Histogram {
+    pub fn record(&self, duration: Duration) {
+        let micros = duration.as_micros().max(1) as u64;
+        let bucket = (u64::BITS - (micros - 1).leading_zeros()) as usize;
+
+        self.buckets[bucket.min(HISTOGRAM_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+
+        let nanos = duration.as_nanos().min(u64::MAX as u128) as u64;
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(nanos, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            count: self.count.load(Ordering::Relaxed),
+            sum: Duration::from_nanos(self.sum.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max.load(Ordering::Relaxed)),
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+/// The content of a `Histogram` at some point.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub sum: Duration,
+    pub max: Duration,
+
+    /// The number of durations in each bucket, see `HistogramSnapshot::bound`
+    pub buckets: Vec<u64>,
+}
+
+impl HistogramSnapshot {
+    /// The upper bound of a bucket
+    pub fn bound(bucket: usize) -> Duration {
+        Duration::from_micros(1 << bucket.min(63))
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_nanos((self.sum.as_nanos() / count as u128) as u64),
+        }
+    }
+
+    /// An upper bound of the `quantile` (between 0 and 1) of the durations, the maximum for
+    /// the last bucket.
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let rank = (quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank.max(1) {
+                return match bucket + 1 < self.buckets.len() {
+                    true => Self::bound(bucket).min(self.max),
+                    false => self.max,
+                };
+            }
+        }
+
+        Duration::ZERO
+    }
+
+    /// Merge the durations of another histogram in this one
+    pub fn merge(&mut self, other: &HistogramSnapshot) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+
+        self.buckets
+            .resize(self.buckets.len().max(other.buckets.len()), 0);
+        for (bucket, count) in other.buckets.iter().enumerate() {
+            self.buckets[bucket] += count;
+        }
+    }
+}
+
+/// The counters of a primitive. `time` is how long outputs wait for room in the channels of
+/// their connections, how long messages waited before being received by an input, the round
+/// trip of a query, and the time a queryable takes to answer.
+#[derive(Debug, Default)]
+pub struct PrimitiveCounters {
+    pub messages: AtomicU64,
+    pub bytes: AtomicU64,
+    pub errors: AtomicU64,
+
+    pub time: Histogram,
+}
+
+impl PrimitiveCounters {
+    pub fn count(&self, bytes: usize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The counters of a connection between an output and an input.
+#[derive(Debug, Default)]
+pub struct ConnectionCounters {
+    pub sent: AtomicU64,
+    pub bytes: AtomicU64,
+    /// The messages that couldn't be sent, because the input was closed
+    pub errors: AtomicU64,
+    /// How long the output waited for room in the channel of the input
+    pub blocked: Histogram,
+
+    pub received: AtomicU64,
+    /// How long the messages waited between being stamped and being received
+    pub latency: Histogram,
+}
+
+/// The metrics of every primitive and connection of a dataflow, by uuid.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    primitives: Mutex<HashMap<Uuid, Arc<PrimitiveCounters>>>,
+    connections: Mutex<HashMap<(Uuid, Uuid), Arc<ConnectionCounters>>>,
+    paths: Mutex<HashMap<(Uuid, Uuid), Arc<Histogram>>>,
+}
+
+impl Metrics {
+    /// The counters of a primitive, created on first use
+    pub fn primitive(&self, primitive: Uuid) -> Arc<PrimitiveCounters> {
+        match self.primitives.lock() {
+            Ok(mut primitives) => primitives.entry(primitive).or_default().clone(),
+            Err(_) => Arc::default(),
+        }
+    }
+
+    /// The counters of the connection between an output and an input, created on first use
+    pub fn connection(&self, output: Uuid, input: Uuid) -> Arc<ConnectionCounters> {
+        match self.connections.lock() {
+            Ok(mut connections) => connections.entry((output, input)).or_default().clone(),
+            Err(_) => Arc::default(),
+        }
+    }
+
+    /// The end-to-end latency of the messages sent from a source output to an input, through
+    /// any number of nodes, created on first use
+    pub fn path(&self, source: Uuid, input: Uuid) -> Arc<Histogram> {
+        match self.paths.lock() {
+            Ok(mut paths) => paths.entry((source, input)).or_default().clone(),
+            Err(_) => Arc::default(),
+        }
+    }
+
+    /// Every primitive counted so far
+    pub fn primitives(&self) -> HashMap<Uuid, Arc<PrimitiveCounters>> {
+        self.primitives
+            .lock()
+            .map(|primitives| primitives.clone())
+            .unwrap_or_default()
+    }
+
+    /// Every connection counted so far
+    pub fn connections(&self) -> HashMap<(Uuid, Uuid), Arc<ConnectionCounters>> {
+        self.connections
+            .lock()
+            .map(|connections| connections.clone())
+            .unwrap_or_default()
+    }
+
+    /// Every path counted so far
+    pub fn paths(&self) -> HashMap<(Uuid, Uuid), Arc<Histogram>> {
+        self.paths
+            .lock()
+            .map(|paths| paths.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// The metrics of a primitive, and of the dataflow it belongs to. A primitive created outside
+/// of a runtime has metrics of its own.
+#[derive(Debug, Clone, Default)]
+pub struct PortMetrics {
+    pub dataflow: Arc<Metrics>,
+    pub counters: Arc<PrimitiveCounters>,
+}
+
+impl PortMetrics {
+    pub fn new(dataflow: &Arc<Metrics>, primitive: Uuid) -> Self {
+        Self {
+            dataflow: dataflow.clone(),
+            counters: dataflow.primitive(primitive),
+        }
+    }
+}
+
+/// The time elapsed since a message was stamped, zero if it comes from the future.
+pub fn elapsed_since(timestamp: &Timestamp) -> Duration {
+    let now = NodeClock::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    now.saturating_sub(timestamp.get_time().to_duration())
+}
@@ -19,13 +19,82 @@ pub trait Node: Send + Sync {
 
     /// The `start` function is used to start the node's execution
     fn start(self: Box<Self>) -> tokio::task::JoinHandle<Result<()>>;
+
+    /// The ports and configuration declared by the node, see `NodeManifest`. It's generated
+    /// by `#[derive(Node)]`, `None` means the node doesn't declare anything.
+    fn manifest() -> Option<NodeManifest>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Check the configuration before the node is created. It's generated by
+    /// `#[node(config = MyConfig)]`, the default accepts any configuration.
+    fn check_configuration(_configuration: &serde_yml::Value) -> Result<()>
+    where
+        Self: Sized,
+    {
+        Ok(())
+    }
+
+    /// Whether the node defines lifecycle hooks, in which case the runtime calls `run` instead
+    /// of `start`. It's generated by `#[node]`.
+    fn has_hooks(&self) -> bool {
+        false
+    }
+
+    /// Run the node with its lifecycle hooks, see `run_with_hooks`. It's generated by `#[node]`
+    /// when the node defines at least one hook.
+    fn run(self: Box<Self>, lifecycle: Lifecycle) -> tokio::task::JoinHandle<Result<()>> {
+        drop(lifecycle);
+
+        self.start()
+    }
+
+    /// Called once every node of the dataflow is constructed, before any of them starts. The
+    /// node keeps `events` to receive the stop, pause, resume and peer error events in `start`
+    fn on_ready(&mut self, events: LifecycleEvents) -> impl Future<Output = Result<()>> + Send
+    where
+        Self: Sized,
+    {
+        drop(events);
+
+        async { Ok(()) }
+    }
 }
 
-/// The `DynamicallyLinkedNodeInstance` type is used for the `C` symbolic function
+/// The constructor generated by `#[derive(Node)]` when at least one field of the node is
+/// declared as a port or read from the configuration. `#[node]` uses it when `new` is omitted.
+#[diagnostic::on_unimplemented(
+    message = "`#[node]` can't generate `new` for `{Self}`: none of its fields is declared as a port or read from the configuration",
+    label = "`new` is missing from this `impl`",
+    note = "declare the fields of `{Self}` with `#[input]`, `#[output]`, `#[query]`, `#[queryable]` or `#[config]`, or write `async fn new`"
+)]
+pub trait NodeFields: Sized {
+    /// Claim the ports by their label, read the configuration fields by their key, and
+    /// initialize the other fields with `Default::default()`
+    fn from_fields(
+        inputs: Inputs,
+        outputs: Outputs,
+        queries: Queries,
+        queryables: Queryables,
+        configuration: serde_yml::Value,
+    ) -> impl Future<Output = Result<Self>> + Send;
+}
+
+/// The `DynamicallyLinkedNodeInstance` type is used for the `C` symbolic function. The runtime
+/// passes its `tokio` context, so the node runs its tasks on the executor chosen by the host.
 pub type DynamicallyLinkedNodeInstance = fn(
+    HostRuntime,
     Inputs,
     Outputs,
     Queries,
     Queryables,
     serde_yml::Value,
 ) -> tokio::task::JoinHandle<Result<Box<dyn Node>>>;
+
+/// The interface of `IRIDIS_NODE`, exported in the metadata of the node. `HostRuntime` carries
+/// a `tokio::runtime::Handle`, so the hash covers the `tokio` build too.
+pub const NODE_INTERFACE: extern "C" fn() -> u64 =
+    interface_hash::<(DynamicallyLinkedNodeInstance, NodeManifestInstance)>;
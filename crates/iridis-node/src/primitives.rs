@@ -16,6 +16,9 @@ pub(crate) mod queryable;
 pub(crate) mod queryables;
 pub(crate) mod raw_queryable;
 
+pub(crate) mod resolver;
+pub(crate) mod returned;
+
 pub use input::*;
 pub use inputs::*;
 pub use raw_input::*;
@@ -31,3 +34,6 @@ pub use raw_query::*;
 pub use queryable::*;
 pub use queryables::*;
 pub use raw_queryable::*;
+
+pub use resolver::*;
+pub use returned::*;
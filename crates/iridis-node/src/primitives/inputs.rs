@@ -11,8 +11,12 @@ type Receivers = SharedMap<Uuid, MessageReceiver>;
 #[derive(Debug)]
 pub struct Inputs {
     receivers: Receivers,
+    resolver: Option<Resolver<MessageReceiver>>,
 
     source: NodeID,
+    metrics: Arc<Metrics>,
+    tracer: NodeTracer,
+    returned: Option<ReturnedReceivers>,
 }
 
 impl Inputs {
@@ -24,19 +28,59 @@ impl Inputs {
             source.uuid
         );
 
-        Self { receivers, source }
+        Self {
+            receivers,
+            resolver: None,
+            source,
+            metrics: Arc::default(),
+            tracer: NodeTracer::default(),
+            returned: None,
+        }
+    }
+
+    /// Opens the inputs that are not found in the receivers with this `Resolver`
+    pub fn with_resolver(mut self, resolver: Resolver<MessageReceiver>) -> Self {
+        self.resolver = Some(resolver);
+
+        self
+    }
+
+    /// Count the messages of the primitives in the `Metrics` of the dataflow
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+
+        self
+    }
+
+    /// Continue the traces of the node with this `NodeTracer`, shared by all its primitives
+    pub fn with_tracer(mut self, tracer: NodeTracer) -> Self {
+        self.tracer = tracer;
+
+        self
+    }
+
+    /// Give the receivers of the primitives back to `returned` when they're dropped
+    pub fn with_returned(mut self, returned: ReturnedReceivers) -> Self {
+        self.returned = Some(returned);
+
+        self
+    }
+
+    /// The node these inputs belong to
+    pub fn source(&self) -> &NodeID {
+        &self.source
     }
 
     async fn compute(&mut self, input: impl Into<String>) -> Result<(MessageReceiver, InputID)> {
         let label: String = input.into();
         let layout = self.source.input(&label);
 
-        let receiver = self
-            .receivers
-            .lock()
-            .await
-            .remove(&layout.uuid)
-            .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
+        let receiver = self.receivers.lock().await.remove(&layout.uuid);
+        let receiver = match (receiver, &self.resolver) {
+            (None, Some(resolver)) => resolver.resolve(&layout),
+            (receiver, _) => receiver,
+        }
+        .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
 
         Ok((receiver, layout))
     }
@@ -54,7 +98,12 @@ impl Inputs {
             self.source.uuid
         );
 
-        Ok(RawInput::new(receiver, self.source.clone(), layout))
+        let mut primitive = RawInput::new(receiver, self.source.clone(), layout);
+        primitive.metrics = PortMetrics::new(&self.metrics, primitive.layout.uuid);
+        primitive.tracer = self.tracer.clone();
+        primitive.returned = self.returned.clone();
+
+        Ok(primitive)
     }
 
     /// Creates a new Input, this input has type information so it can be directly transformed
@@ -69,6 +118,11 @@ impl Inputs {
             self.source.uuid
         );
 
-        Ok(Input::new(receiver, self.source.clone(), layout))
+        let mut primitive = Input::new(receiver, self.source.clone(), layout);
+        primitive.raw.metrics = PortMetrics::new(&self.metrics, primitive.raw.layout.uuid);
+        primitive.raw.tracer = self.tracer.clone();
+        primitive.raw.returned = self.returned.clone();
+
+        Ok(primitive)
     }
 }
@@ -12,7 +12,12 @@ pub struct Output<T: ArrowMessage> {
 
 impl<T: ArrowMessage> Output<T> {
     /// Create a new typed Output from a MessageSender, NodeID, and OutputID
-    pub fn new(tx: Vec<MessageSender>, clock: Arc<HLC>, source: NodeID, layout: OutputID) -> Self {
+    pub fn new(
+        tx: SharedOutputChannels,
+        clock: Arc<HLC>,
+        source: NodeID,
+        layout: OutputID,
+    ) -> Self {
         Self {
             raw: RawOutput::new(tx, clock, source, layout),
             _phantom: std::marker::PhantomData,
@@ -21,14 +26,20 @@ impl<T: ArrowMessage> Output<T> {
 
     /// Send a message to the output asynchronously.
     pub async fn send(&self, data: T) -> Result<()> {
+        self.send_with(data, HeaderMetadata::default()).await
+    }
+
+    /// Send a message with some metadata to the output asynchronously.
+    pub async fn send_with(&self, data: T, metadata: HeaderMetadata) -> Result<()> {
         self.raw
-            .send(
+            .send_with(
                 data.try_into_arrow()
                     .wrap_err(report_failed_conversion_to_arrow::<T>(
                         &self.raw.source,
                         &self.raw.layout,
                     ))?
                     .into_data(),
+                metadata,
             )
             .await
     }
@@ -5,14 +5,17 @@ use std::{collections::HashMap, sync::Arc};
 use crate::prelude::{thirdparty::tokio::sync::Mutex, *};
 
 type SharedMap<K, V> = Arc<Mutex<HashMap<K, V>>>;
-type Senders = SharedMap<Uuid, Vec<MessageSender>>;
+type Senders = SharedMap<Uuid, SharedOutputChannels>;
 
 /// Outputs let you manage output connections during a node *implementation*
 pub struct Outputs {
     senders: Senders,
+    resolver: Option<Resolver<SharedOutputChannels>>,
     clock: Arc<uhlc::HLC>,
 
     source: NodeID,
+    metrics: Arc<Metrics>,
+    tracer: NodeTracer,
 }
 
 impl Outputs {
@@ -20,24 +23,48 @@ impl Outputs {
     pub fn new(senders: Senders, clock: Arc<uhlc::HLC>, source: NodeID) -> Self {
         Self {
             senders,
+            resolver: None,
             clock,
             source,
+            metrics: Arc::default(),
+            tracer: NodeTracer::default(),
         }
     }
 
+    /// Opens the outputs that are not found in the senders with this `Resolver`
+    pub fn with_resolver(mut self, resolver: Resolver<SharedOutputChannels>) -> Self {
+        self.resolver = Some(resolver);
+
+        self
+    }
+
+    /// Count the messages of the primitives in the `Metrics` of the dataflow
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+
+        self
+    }
+
+    /// Continue the traces of the node with this `NodeTracer`, shared by all its primitives
+    pub fn with_tracer(mut self, tracer: NodeTracer) -> Self {
+        self.tracer = tracer;
+
+        self
+    }
+
     async fn compute(
         &mut self,
         output: impl Into<String>,
-    ) -> Result<(Vec<MessageSender>, OutputID)> {
+    ) -> Result<(SharedOutputChannels, OutputID)> {
         let label: String = output.into();
         let layout = self.source.output(&label);
 
-        let senders = self
-            .senders
-            .lock()
-            .await
-            .remove(&layout.uuid)
-            .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
+        let senders = self.senders.lock().await.remove(&layout.uuid);
+        let senders = match (senders, &self.resolver) {
+            (None, Some(resolver)) => resolver.resolve(&layout),
+            (senders, _) => senders,
+        }
+        .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
 
         Ok((senders, layout))
     }
@@ -55,12 +82,12 @@ impl Outputs {
             self.source.uuid
         );
 
-        Ok(RawOutput::new(
-            senders,
-            self.clock.clone(),
-            self.source.clone(),
-            layout,
-        ))
+        let mut primitive =
+            RawOutput::new(senders, self.clock.clone(), self.source.clone(), layout);
+        primitive.metrics = PortMetrics::new(&self.metrics, primitive.layout.uuid);
+        primitive.tracer = self.tracer.clone();
+
+        Ok(primitive)
     }
 
     /// Creates a new typed Output, this output has type information so you don't have
@@ -76,11 +103,10 @@ impl Outputs {
             self.source.uuid
         );
 
-        Ok(Output::new(
-            senders,
-            self.clock.clone(),
-            self.source.clone(),
-            layout,
-        ))
+        let mut primitive = Output::new(senders, self.clock.clone(), self.source.clone(), layout);
+        primitive.raw.metrics = PortMetrics::new(&self.metrics, primitive.raw.layout.uuid);
+        primitive.raw.tracer = self.tracer.clone();
+
+        Ok(primitive)
     }
 }
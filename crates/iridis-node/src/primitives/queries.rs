@@ -12,10 +12,14 @@ type Receivers = SharedMap<Uuid, MessageReceiver>;
 pub struct Queries {
     senders: Senders,
     receivers: Receivers,
+    resolver: Option<Resolver<(MessageSender, MessageReceiver)>>,
 
     clock: Arc<uhlc::HLC>,
 
     source: NodeID,
+    metrics: Arc<Metrics>,
+    tracer: NodeTracer,
+    returned: Option<ReturnedReceivers>,
 }
 
 impl Queries {
@@ -29,11 +33,43 @@ impl Queries {
         Self {
             senders,
             receivers,
+            resolver: None,
             clock,
             source,
+            metrics: Arc::default(),
+            tracer: NodeTracer::default(),
+            returned: None,
         }
     }
 
+    /// Opens the queries that are not found in the senders and receivers with this `Resolver`
+    pub fn with_resolver(mut self, resolver: Resolver<(MessageSender, MessageReceiver)>) -> Self {
+        self.resolver = Some(resolver);
+
+        self
+    }
+
+    /// Count the messages of the primitives in the `Metrics` of the dataflow
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+
+        self
+    }
+
+    /// Continue the traces of the node with this `NodeTracer`, shared by all its primitives
+    pub fn with_tracer(mut self, tracer: NodeTracer) -> Self {
+        self.tracer = tracer;
+
+        self
+    }
+
+    /// Give the receivers of the primitives back to `returned` when they're dropped
+    pub fn with_returned(mut self, returned: ReturnedReceivers) -> Self {
+        self.returned = Some(returned);
+
+        self
+    }
+
     async fn compute(
         &mut self,
         query: impl Into<String>,
@@ -41,19 +77,16 @@ impl Queries {
         let label: String = query.into();
         let layout = self.source.query(&label);
 
-        let sender = self
-            .senders
-            .lock()
-            .await
-            .remove(&layout.uuid)
-            .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
+        let sender = self.senders.lock().await.remove(&layout.uuid);
+        let receiver = self.receivers.lock().await.remove(&layout.uuid);
 
-        let receiver = self
-            .receivers
-            .lock()
-            .await
-            .remove(&layout.uuid)
-            .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
+        let (sender, receiver) = match (sender, receiver, &self.resolver) {
+            (Some(sender), Some(receiver), _) => (sender, receiver),
+            (None, None, Some(resolver)) => resolver
+                .resolve(&layout)
+                .ok_or_eyre(report_io_not_found(&self.source, &layout))?,
+            _ => return Err(report_io_not_found(&self.source, &layout)),
+        };
 
         Ok((sender, receiver, layout))
     }
@@ -71,13 +104,12 @@ impl Queries {
             self.source.uuid
         );
 
-        Ok(RawQuery::new(
-            tx,
-            rx,
-            self.clock.clone(),
-            self.source.clone(),
-            layout,
-        ))
+        let mut primitive = RawQuery::new(tx, rx, self.clock.clone(), self.source.clone(), layout);
+        primitive.metrics = PortMetrics::new(&self.metrics, primitive.layout.uuid);
+        primitive.tracer = self.tracer.clone();
+        primitive.returned = self.returned.clone();
+
+        Ok(primitive)
     }
 
     /// Creates a new query, this query has type information
@@ -95,12 +127,11 @@ impl Queries {
             self.source.uuid
         );
 
-        Ok(Query::new(
-            tx,
-            rx,
-            self.clock.clone(),
-            self.source.clone(),
-            layout,
-        ))
+        let mut primitive = Query::new(tx, rx, self.clock.clone(), self.source.clone(), layout);
+        primitive.raw.metrics = PortMetrics::new(&self.metrics, primitive.raw.layout.uuid);
+        primitive.raw.tracer = self.tracer.clone();
+        primitive.raw.returned = self.returned.clone();
+
+        Ok(primitive)
     }
 }
@@ -28,14 +28,24 @@ impl<T: ArrowMessage, F: ArrowMessage> Query<T, F> {
 
     /// Query a message from the channel and converting it from Arrow format, asynchronously
     pub async fn query(&mut self, data: T) -> Result<TypedDataflowMessage<F>> {
+        self.query_with(data, HeaderMetadata::default()).await
+    }
+
+    /// Query a message with some metadata, converting the response from Arrow format, asynchronously
+    pub async fn query_with(
+        &mut self,
+        data: T,
+        metadata: HeaderMetadata,
+    ) -> Result<TypedDataflowMessage<F>> {
         self.raw
-            .query(
+            .query_with(
                 data.try_into_arrow()
                     .wrap_err(report_failed_conversion_to_arrow::<T>(
                         &self.raw.source,
                         &self.raw.layout,
                     ))?
                     .into_data(),
+                metadata,
             )
             .await?
             .try_into()
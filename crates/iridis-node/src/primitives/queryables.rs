@@ -12,10 +12,14 @@ type Receivers = SharedMap<Uuid, MessageReceiver>;
 pub struct Queryables {
     senders: Senders,
     receivers: Receivers,
+    resolver: Option<Resolver<(HashMap<Uuid, MessageSender>, MessageReceiver)>>,
 
     clock: Arc<uhlc::HLC>,
 
     source: NodeID,
+    metrics: Arc<Metrics>,
+    tracer: NodeTracer,
+    returned: Option<ReturnedReceivers>,
 }
 
 impl Queryables {
@@ -30,10 +34,45 @@ impl Queryables {
             clock,
             senders,
             receivers,
+            resolver: None,
             source,
+            metrics: Arc::default(),
+            tracer: NodeTracer::default(),
+            returned: None,
         }
     }
 
+    /// Opens the queryables that are not found in the senders and receivers with this `Resolver`
+    pub fn with_resolver(
+        mut self,
+        resolver: Resolver<(HashMap<Uuid, MessageSender>, MessageReceiver)>,
+    ) -> Self {
+        self.resolver = Some(resolver);
+
+        self
+    }
+
+    /// Count the messages of the primitives in the `Metrics` of the dataflow
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+
+        self
+    }
+
+    /// Continue the traces of the node with this `NodeTracer`, shared by all its primitives
+    pub fn with_tracer(mut self, tracer: NodeTracer) -> Self {
+        self.tracer = tracer;
+
+        self
+    }
+
+    /// Give the receivers of the primitives back to `returned` when they're dropped
+    pub fn with_returned(mut self, returned: ReturnedReceivers) -> Self {
+        self.returned = Some(returned);
+
+        self
+    }
+
     async fn compute(
         &mut self,
         queryable: impl Into<String>,
@@ -41,19 +80,16 @@ impl Queryables {
         let label: String = queryable.into();
         let layout = self.source.queryable(&label);
 
-        let senders = self
-            .senders
-            .lock()
-            .await
-            .remove(&layout.uuid)
-            .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
+        let senders = self.senders.lock().await.remove(&layout.uuid);
+        let receiver = self.receivers.lock().await.remove(&layout.uuid);
 
-        let receiver = self
-            .receivers
-            .lock()
-            .await
-            .remove(&layout.uuid)
-            .ok_or_eyre(report_io_not_found(&self.source, &layout))?;
+        let (senders, receiver) = match (senders, receiver, &self.resolver) {
+            (Some(senders), Some(receiver), _) => (senders, receiver),
+            (None, None, Some(resolver)) => resolver
+                .resolve(&layout)
+                .ok_or_eyre(report_io_not_found(&self.source, &layout))?,
+            _ => return Err(report_io_not_found(&self.source, &layout)),
+        };
 
         Ok((senders, receiver, layout))
     }
@@ -71,13 +107,18 @@ impl Queryables {
             self.source.uuid
         );
 
-        Ok(RawQueryable::new(
+        let mut primitive = RawQueryable::new(
             senders,
             receivers,
             self.clock.clone(),
             self.source.clone(),
             layout,
-        ))
+        );
+        primitive.metrics = PortMetrics::new(&self.metrics, primitive.layout.uuid);
+        primitive.tracer = self.tracer.clone();
+        primitive.returned = self.returned.clone();
+
+        Ok(primitive)
     }
 
     /// Creates a new typed Queryable, this queryable has type information
@@ -95,12 +136,17 @@ impl Queryables {
             self.source.uuid
         );
 
-        Ok(Queryable::new(
+        let mut primitive = Queryable::new(
             senders,
             receivers,
             self.clock.clone(),
             self.source.clone(),
             layout,
-        ))
+        );
+        primitive.raw.metrics = PortMetrics::new(&self.metrics, primitive.raw.layout.uuid);
+        primitive.raw.tracer = self.tracer.clone();
+        primitive.raw.returned = self.returned.clone();
+
+        Ok(primitive)
     }
 }
@@ -1,5 +1,7 @@
 //! This module contains implementations for this primitive.
 
+use std::{sync::atomic::Ordering, time::UNIX_EPOCH};
+
 use crate::prelude::*;
 
 /// Not typed Input to receive data from the dataflow
@@ -12,12 +14,26 @@ pub struct RawInput {
     pub source: NodeID,
     /// The layout of the input, useful for debugging
     pub layout: InputID,
+
+    /// The metrics of the input, set by the runtime
+    pub metrics: PortMetrics,
+    /// The trace of the node, set by the runtime
+    pub tracer: NodeTracer,
+    /// Where the receiver is given back when it's dropped, set by the runtime
+    pub returned: Option<ReturnedReceivers>,
 }
 
 impl RawInput {
     /// Create a new RawInput instance
     pub fn new(rx: MessageReceiver, source: NodeID, layout: InputID) -> Self {
-        Self { rx, source, layout }
+        Self {
+            rx,
+            source,
+            layout,
+            metrics: PortMetrics::default(),
+            tracer: NodeTracer::default(),
+            returned: None,
+        }
     }
 
     /// Receive a message from the channel, asynchronously
@@ -28,6 +44,50 @@ impl RawInput {
             .await
             .ok_or_eyre(report_error_receiving(&self.source, &self.layout))?;
 
+        let latency = elapsed_since(&message.header.timestamp);
+
+        self.metrics
+            .counters
+            .count(message.data.get_array_memory_size());
+        self.metrics.counters.time.record(latency);
+
+        let connection = self
+            .metrics
+            .dataflow
+            .connection(message.header.source.1, self.layout.uuid);
+
+        connection.received.fetch_add(1, Ordering::Relaxed);
+        connection.latency.record(latency);
+
+        // The end-to-end latency from the source of the data to this input
+        if let Some(lineage) = &message.header.metadata.lineage {
+            if let (Some(origin), Some((_, source))) = (lineage.origin(), lineage.source()) {
+                self.metrics
+                    .dataflow
+                    .path(source, self.layout.uuid)
+                    .record(elapsed_since(&origin));
+            }
+        }
+
+        let span = NodeTracer::span(message.header.trace);
+        self.tracer.enter(span.0);
+        self.tracer.record(
+            span,
+            SpanKind::Receive,
+            &self.source,
+            &self.layout.label,
+            UNIX_EPOCH + message.header.timestamp.get_time().to_duration(),
+            false,
+        );
+
         Ok(message)
     }
 }
+
+impl Drop for RawInput {
+    fn drop(&mut self) {
+        if let Some(returned) = self.returned.take() {
+            returned.give_back(self.layout.uuid, &mut self.rx);
+        }
+    }
+}
@@ -1,13 +1,61 @@
 //! This module contains implementations for this primitive.
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Weak,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
-use crate::prelude::{thirdparty::arrow_data::ArrayData, *};
+use crate::prelude::{
+    thirdparty::{
+        arrow_data::ArrayData,
+        tokio::{
+            sync::{RwLock, mpsc::error::TrySendError},
+            time::Instant,
+        },
+    },
+    *,
+};
+
+/// What a tap does when its channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapOverflow {
+    /// Wait for the tap to have room, like a connected input would.
+    Backpressure,
+    /// Drop the message for this tap, so it never slows down the output.
+    Drop,
+}
+
+/// A receiver attached to an output from outside the dataflow.
+#[derive(Debug, Clone)]
+pub struct Tap {
+    pub tx: MessageSender,
+    pub overflow: TapOverflow,
+}
+
+/// The downstream channels of an output. They're shared with the runtime so it can attach
+/// new receivers to the output while the node is running.
+#[derive(Debug, Default)]
+pub struct OutputChannels {
+    /// The senders to the inputs connected to this output, by input uuid
+    pub connections: HashMap<Uuid, MessageSender>,
+    /// The taps attached from outside the dataflow, by tap uuid. Closed ones are discarded
+    pub taps: HashMap<Uuid, Tap>,
+}
+
+/// Shared `OutputChannels`, the runtime only keeps a weak reference so the channels are closed
+/// as soon as the output is dropped.
+pub type SharedOutputChannels = Arc<RwLock<OutputChannels>>;
+
+/// Weak reference to `OutputChannels`, kept by the runtime.
+pub type WeakOutputChannels = Weak<RwLock<OutputChannels>>;
 
 /// Not typed Output to receive data from the dataflow
 pub struct RawOutput {
     /// The senders parts of MPSC channels
-    pub tx: Vec<MessageSender>,
+    pub tx: SharedOutputChannels,
     /// The shared clock of the runtime
     pub clock: Arc<HLC>,
 
@@ -15,46 +63,144 @@ pub struct RawOutput {
     pub source: NodeID,
     /// The layout of the output, useful for debugging
     pub layout: OutputID,
+
+    /// The metrics of the output, set by the runtime
+    pub metrics: PortMetrics,
+    /// The trace of the node, set by the runtime
+    pub tracer: NodeTracer,
+
+    /// The sequence number of the next message
+    sequence: AtomicU64,
 }
 
 impl RawOutput {
     /// Create a new RawOutput instance
-    pub fn new(tx: Vec<MessageSender>, clock: Arc<HLC>, source: NodeID, layout: OutputID) -> Self {
+    pub fn new(
+        tx: SharedOutputChannels,
+        clock: Arc<HLC>,
+        source: NodeID,
+        layout: OutputID,
+    ) -> Self {
         Self {
             tx,
             clock,
             source,
             layout,
+            metrics: PortMetrics::default(),
+            tracer: NodeTracer::default(),
+            sequence: AtomicU64::new(0),
         }
     }
 
     /// Send a message asynchronously to all connected nodes.
     pub async fn send(&self, data: ArrayData) -> Result<()> {
-        let data = DataflowMessage {
-            header: Header {
-                timestamp: self.clock.new_timestamp(),
-                source: (self.source.uuid, self.layout.uuid),
-            },
-            data,
+        self.send_with(data, HeaderMetadata::default()).await
+    }
+
+    /// Send a message with some metadata asynchronously to all connected nodes.
+    pub async fn send_with(&self, data: ArrayData, mut metadata: HeaderMetadata) -> Result<()> {
+        let span = NodeTracer::span(self.tracer.current());
+        let start = NodeClock::now();
+
+        let timestamp = self.clock.new_timestamp();
+        if let Some(lineage) = &mut metadata.lineage {
+            lineage.hop(timestamp, (self.source.uuid, self.layout.uuid));
+        }
+
+        let result = self
+            .forward(DataflowMessage {
+                header: Header {
+                    timestamp,
+                    source: (self.source.uuid, self.layout.uuid),
+                    trace: Some(span.0),
+                    sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+                    metadata,
+                },
+                data,
+            })
+            .await;
+
+        self.tracer.record(
+            span,
+            SpanKind::Send,
+            &self.source,
+            &self.layout.label,
+            start,
+            result.is_err(),
+        );
+
+        result
+    }
+
+    /// Send a message with its `Header` as is, for a message stamped by this output outside
+    /// of this process.
+    pub async fn forward(&self, data: DataflowMessage) -> Result<()> {
+        let (connections, taps) = {
+            let tx = self.tx.read().await;
+
+            (
+                tx.connections
+                    .iter()
+                    .map(|(input, tx)| (*input, tx.clone()))
+                    .collect::<Vec<_>>(),
+                tx.taps.values().cloned().collect::<Vec<_>>(),
+            )
         };
 
+        let bytes = data.data.get_array_memory_size();
+        self.metrics.counters.count(bytes);
+
+        let start = Instant::now();
         let mut tasks = Vec::new();
 
-        for tx in &self.tx {
-            let tx = tx.clone();
+        for (input, tx) in connections {
             let data = data.clone();
 
             let source = self.source.clone();
             let layout = self.layout.clone();
 
+            let connection = self.metrics.dataflow.connection(layout.uuid, input);
+
             tasks.push(tokio::spawn(async move {
-                tx.send(data)
+                let start = Instant::now();
+
+                let result = tx
+                    .send(data)
                     .await
                     .map_err(eyre::Report::msg)
-                    .wrap_err(report_error_sending(&source, layout))
+                    .wrap_err(report_error_sending(&source, layout));
+
+                match &result {
+                    Ok(()) => {
+                        connection.sent.fetch_add(1, Ordering::Relaxed);
+                        connection.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+                    }
+                    Err(_) => {
+                        connection.errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                connection.blocked.record(start.elapsed());
+
+                result
             }));
         }
 
+        let mut closed_tap = false;
+        let mut taps_tasks = Vec::new();
+
+        for Tap { tx, overflow } in taps {
+            let data = data.clone();
+
+            match overflow {
+                TapOverflow::Backpressure => {
+                    taps_tasks.push(tokio::spawn(async move { tx.send(data).await.is_ok() }))
+                }
+                TapOverflow::Drop => {
+                    closed_tap |= matches!(tx.try_send(data), Err(TrySendError::Closed(_)))
+                }
+            }
+        }
+
         let mut results = Vec::new();
         for task in tasks {
             match task.await {
@@ -63,9 +209,25 @@ impl RawOutput {
             }
         }
 
+        self.metrics.counters.time.record(start.elapsed());
+
+        for task in taps_tasks {
+            closed_tap |= !matches!(task.await, Ok(true));
+        }
+
+        if closed_tap {
+            self.tx
+                .write()
+                .await
+                .taps
+                .retain(|_, tap| !tap.tx.is_closed());
+        }
+
         if results.iter().all(|r| r.is_ok()) {
             Ok(())
         } else {
+            self.metrics.counters.error();
+
             let combined_report: eyre::Report = results
                 .into_iter()
                 .filter(Result::is_err)
@@ -2,7 +2,10 @@
 
 use std::sync::Arc;
 
-use crate::prelude::{thirdparty::arrow_data::ArrayData, *};
+use crate::prelude::{
+    thirdparty::{arrow_data::ArrayData, tokio::time::Instant},
+    *,
+};
 
 /// Not typed Query to receive data from the dataflow
 pub struct RawQuery {
@@ -17,6 +20,16 @@ pub struct RawQuery {
     pub source: NodeID,
     /// The layout of the query, useful for debugging
     pub layout: QueryID,
+
+    /// The metrics of the query, set by the runtime
+    pub metrics: PortMetrics,
+    /// The trace of the node, set by the runtime
+    pub tracer: NodeTracer,
+    /// Where the receiver is given back when it's dropped, set by the runtime
+    pub returned: Option<ReturnedReceivers>,
+
+    /// The sequence number of the next request
+    sequence: u64,
 }
 
 impl RawQuery {
@@ -34,19 +47,71 @@ impl RawQuery {
             clock,
             source,
             layout,
+            metrics: PortMetrics::default(),
+            tracer: NodeTracer::default(),
+            returned: None,
+            sequence: 0,
         }
     }
 
     /// Query a message to a queryable
     pub async fn query(&mut self, data: ArrayData) -> Result<DataflowMessage> {
-        let data = DataflowMessage {
+        self.query_with(data, HeaderMetadata::default()).await
+    }
+
+    /// Query a message with some metadata to a queryable. The request gets a new correlation
+    /// id if it has none, the response carries it back.
+    pub async fn query_with(
+        &mut self,
+        data: ArrayData,
+        mut metadata: HeaderMetadata,
+    ) -> Result<DataflowMessage> {
+        metadata.correlation.get_or_insert_with(Uuid::new_v4);
+
+        let start = Instant::now();
+        let span = NodeTracer::span(self.tracer.current());
+        let time = NodeClock::now();
+
+        self.metrics.counters.count(data.get_array_memory_size());
+
+        let timestamp = self.clock.new_timestamp();
+        if let Some(lineage) = &mut metadata.lineage {
+            lineage.hop(timestamp, (self.source.uuid, self.layout.uuid));
+        }
+
+        let request = DataflowMessage {
             header: Header {
-                timestamp: self.clock.new_timestamp(),
+                timestamp,
                 source: (self.source.uuid, self.layout.uuid),
+                trace: Some(span.0),
+                sequence: self.sequence,
+                metadata,
             },
             data,
         };
 
+        self.sequence += 1;
+
+        let result = self.round_trip(request).await;
+
+        match &result {
+            Ok(_) => self.metrics.counters.time.record(start.elapsed()),
+            Err(_) => self.metrics.counters.error(),
+        }
+
+        self.tracer.record(
+            span,
+            SpanKind::Query,
+            &self.source,
+            &self.layout.label,
+            time,
+            result.is_err(),
+        );
+
+        result
+    }
+
+    async fn round_trip(&mut self, data: DataflowMessage) -> Result<DataflowMessage> {
         self.tx
             .send(data)
             .await
@@ -61,3 +126,11 @@ impl RawQuery {
         Ok(message)
     }
 }
+
+impl Drop for RawQuery {
+    fn drop(&mut self) {
+        if let Some(returned) = self.returned.take() {
+            returned.give_back(self.layout.uuid, &mut self.rx);
+        }
+    }
+}
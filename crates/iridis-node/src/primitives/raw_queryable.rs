@@ -2,7 +2,10 @@
 
 use std::{collections::HashMap, sync::Arc};
 
-use crate::prelude::{thirdparty::arrow_data::ArrayData, *};
+use crate::prelude::{
+    thirdparty::{arrow_data::ArrayData, tokio::time::Instant},
+    *,
+};
 
 /// Not typed Queryable to receive data from the dataflow
 pub struct RawQueryable {
@@ -17,6 +20,13 @@ pub struct RawQueryable {
     pub source: NodeID,
     /// The layout of the queryable, useful for debugging
     pub layout: QueryableID,
+
+    /// The metrics of the queryable, set by the runtime
+    pub metrics: PortMetrics,
+    /// The trace of the node, set by the runtime
+    pub tracer: NodeTracer,
+    /// Where the receiver is given back when it's dropped, set by the runtime
+    pub returned: Option<ReturnedReceivers>,
 }
 
 impl RawQueryable {
@@ -34,6 +44,9 @@ impl RawQueryable {
             clock,
             source,
             layout,
+            metrics: PortMetrics::default(),
+            tracer: NodeTracer::default(),
+            returned: None,
         }
     }
 
@@ -53,14 +66,43 @@ impl RawQueryable {
             .get(&message.header.source.1)
             .ok_or_eyre(report_io_not_found(&self.source, &self.layout))?;
 
+        self.metrics
+            .counters
+            .count(message.data.get_array_memory_size());
+
+        // The handler continues the trace of the request, and the response carries its
+        // sequence number and metadata
+        let span = NodeTracer::span(message.header.trace);
+        let (sequence, metadata) = (message.header.sequence, message.header.metadata.clone());
+        self.tracer.enter(span.0);
+
+        let start = Instant::now();
+        let time = NodeClock::now();
+        let response = response(message).await;
+
+        self.metrics.counters.time.record(start.elapsed());
+        if response.is_err() {
+            self.metrics.counters.error();
+        }
+
+        self.tracer.record(
+            span,
+            SpanKind::Answer,
+            &self.source,
+            &self.layout.label,
+            time,
+            response.is_err(),
+        );
+
         let data = DataflowMessage {
             header: Header {
                 timestamp: self.clock.new_timestamp(),
                 source: (self.source.uuid, self.layout.uuid),
+                trace: Some(span.0),
+                sequence,
+                metadata,
             },
-            data: response(message)
-                .await
-                .wrap_err(report_error_sending(&self.source, &self.layout))?,
+            data: response.wrap_err(report_error_sending(&self.source, &self.layout))?,
         };
 
         tx.send(data)
@@ -68,3 +110,11 @@ impl RawQueryable {
             .wrap_err(report_error_sending(&self.source, &self.layout))
     }
 }
+
+impl Drop for RawQueryable {
+    fn drop(&mut self) {
+        if let Some(returned) = self.returned.take() {
+            returned.give_back(self.layout.uuid, &mut self.rx);
+        }
+    }
+}
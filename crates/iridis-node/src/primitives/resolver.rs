@@ -0,0 +1,39 @@
+//! This module contains the `Resolver` used to open primitives on demand.
+
+use std::{fmt, sync::Arc};
+
+use crate::prelude::*;
+
+/// Opens a primitive on demand, when it's not part of the channels given to the node.
+/// It's used by the nodes loaded through the C ABI, whose channels are owned by the runtime.
+pub struct Resolver<T> {
+    resolve: Arc<dyn Fn(PrimitiveID) -> Option<T> + Send + Sync>,
+}
+
+impl<T> Resolver<T> {
+    /// Creates a new `Resolver` from a blocking function
+    pub fn new(resolve: impl Fn(PrimitiveID) -> Option<T> + Send + Sync + 'static) -> Self {
+        Self {
+            resolve: Arc::new(resolve),
+        }
+    }
+
+    /// Opens the primitive, returns `None` if it doesn't exist
+    pub fn resolve(&self, primitive: impl Into<PrimitiveID>) -> Option<T> {
+        (self.resolve)(primitive.into())
+    }
+}
+
+impl<T> Clone for Resolver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            resolve: self.resolve.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Resolver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Resolver").finish_non_exhaustive()
+    }
+}
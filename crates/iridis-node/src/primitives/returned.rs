@@ -0,0 +1,38 @@
+//! This module contains the `ReturnedReceivers` used to keep the messages of a replaced node.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use crate::prelude::{thirdparty::tokio::sync::mpsc, *};
+
+/// Collects the receivers of the primitives of a node when they're dropped, along with the
+/// messages still queued in them. It's used by reloadable nodes, so the next instance receives
+/// what the previous one didn't read.
+#[derive(Debug, Clone, Default)]
+pub struct ReturnedReceivers {
+    receivers: Arc<Mutex<HashMap<Uuid, MessageReceiver>>>,
+}
+
+impl ReturnedReceivers {
+    /// Give back the receiver of a primitive, it's replaced by a closed one
+    pub fn give_back(&self, uuid: Uuid, receiver: &mut MessageReceiver) {
+        let (_, closed) = mpsc::channel(1);
+
+        self.receivers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(uuid, std::mem::replace(receiver, closed));
+    }
+
+    /// Take the receivers given back so far
+    pub fn take(&self) -> HashMap<Uuid, MessageReceiver> {
+        std::mem::take(
+            &mut *self
+                .receivers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        )
+    }
+}
@@ -0,0 +1,664 @@
+//! This module defines the `SharedArena`, a shared memory region where nodes allocate Arrow
+//! buffers that other processes map read-only instead of copying them. The arena is split in
+//! pages, and every allocation has a reference count stored in the shared memory, only changed
+//! by the process that created the arena: the pages are reused once it reaches zero. A buffer
+//! sent to another process is kept by the sender in a `SharedLeases` table until the other
+//! process returns it, or is gone.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    ops::{Deref, DerefMut},
+    os::fd::{AsRawFd, FromRawFd},
+    path::{Path, PathBuf},
+    ptr::NonNull,
+    sync::{
+        Arc, LazyLock, Mutex, OnceLock, Weak,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+};
+
+use arrow_buffer::{BooleanBuffer, Buffer, NullBuffer, alloc::Allocation};
+use arrow_data::{ArrayData, ArrayDataBuilder};
+use arrow_schema::DataType;
+
+use crate::prelude::{
+    thirdparty::serde::{Deserialize, Serialize},
+    *,
+};
+
+/// The allocation unit of a `SharedArena`, and the alignment of every buffer
+pub const SHARED_PAGE_SIZE: usize = 64 * 1024;
+
+/// The capacity of the arena returned by `SharedArena::current`
+pub const DEFAULT_ARENA_CAPACITY: usize = 256 * 1024 * 1024;
+
+/// The state of a page, in the header of the segment.
+#[repr(C)]
+struct Slot {
+    /// References to the allocation, only meaningful on its first page
+    refs: AtomicU32,
+    /// The first page of the allocation this page belongs to
+    head: AtomicU32,
+    /// The number of pages of the allocation, only meaningful on its first page
+    pages: AtomicU32,
+    _reserved: u32,
+}
+
+fn header_len(pages: usize) -> usize {
+    (size_of::<u64>() + pages * size_of::<Slot>()).div_ceil(SHARED_PAGE_SIZE) * SHARED_PAGE_SIZE
+}
+
+/// A memory mapping, unmapped when dropped.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+// The mapped memory is only accessed through atomics, or through buffers owned by a single
+// allocation at a time.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, offset: usize, len: usize, writable: bool) -> Result<Self> {
+        let protection = match writable {
+            true => libc::PROT_READ | libc::PROT_WRITE,
+            false => libc::PROT_READ,
+        };
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                protection,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error()).wrap_err("Failed to map shared memory");
+        }
+
+        Ok(Self {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// A shared memory segment, either created by this process or mapped from another one.
+struct Segment {
+    /// The path other processes open the segment with
+    path: PathBuf,
+    pages: usize,
+
+    header: Mapping,
+    data: Mapping,
+
+    /// Only the process that created the segment allocates in it and counts its references
+    owned: bool,
+    allocator: Mutex<()>,
+
+    _file: File,
+}
+
+/// Every segment mapped by this process, to find the segment of a buffer
+static SEGMENTS: LazyLock<Mutex<Vec<Weak<Segment>>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+fn segments() -> Vec<Arc<Segment>> {
+    match SEGMENTS.lock() {
+        Ok(mut segments) => {
+            segments.retain(|segment| segment.strong_count() > 0);
+            segments.iter().filter_map(Weak::upgrade).collect()
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether `path` is a file descriptor of the process `pid`, as created by `Segment::create`
+fn created_by(path: &Path, pid: u32) -> bool {
+    path.parent() == Some(Path::new(&format!("/proc/{}/fd", pid)))
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
+impl Segment {
+    fn create(capacity: usize) -> Result<Arc<Self>> {
+        let pages = capacity.div_ceil(SHARED_PAGE_SIZE).max(1);
+        let header = header_len(pages);
+
+        let fd = unsafe { libc::memfd_create(c"iridis-arena".as_ptr(), libc::MFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error()).wrap_err("Failed to create shared memory");
+        }
+
+        let file = unsafe { File::from_raw_fd(fd) };
+        file.set_len((header + pages * SHARED_PAGE_SIZE) as u64)
+            .wrap_err("Failed to allocate shared memory")?;
+
+        let segment = Self::map(
+            file,
+            PathBuf::from(format!("/proc/{}/fd/{}", std::process::id(), fd)),
+            pages,
+            true,
+        )?;
+
+        unsafe { (segment.header.ptr as *mut u64).write(pages as u64) };
+
+        Ok(segment)
+    }
+
+    /// Map the segment created by the process `peer`, only a file descriptor of this process
+    /// is accepted
+    fn open(path: &Path, peer: u32) -> Result<Arc<Self>> {
+        if !created_by(path, peer) {
+            eyre::bail!(
+                "Shared memory {:?} doesn't belong to the process {}",
+                path,
+                peer
+            );
+        }
+
+        let mut file = File::options()
+            .read(true)
+            .open(path)
+            .wrap_err(format!("Failed to open shared memory {:?}", path))?;
+
+        let mut pages = [0; size_of::<u64>()];
+        file.read_exact(&mut pages)?;
+
+        Self::map(
+            file,
+            path.to_path_buf(),
+            u64::from_ne_bytes(pages) as usize,
+            false,
+        )
+    }
+
+    fn map(file: File, path: PathBuf, pages: usize, owned: bool) -> Result<Arc<Self>> {
+        let header = Mapping::new(&file, 0, header_len(pages), owned)?;
+        let data = Mapping::new(&file, header.len, pages * SHARED_PAGE_SIZE, owned)?;
+
+        let segment = Arc::new(Self {
+            path,
+            pages,
+            header,
+            data,
+            owned,
+            allocator: Mutex::new(()),
+            _file: file,
+        });
+
+        if let Ok(mut segments) = SEGMENTS.lock() {
+            segments.push(Arc::downgrade(&segment));
+        }
+
+        Ok(segment)
+    }
+
+    /// The segment mapped at `path`, mapping it from `peer` if needed
+    fn opened(path: &Path, peer: u32) -> Result<Arc<Self>> {
+        match segments().into_iter().find(|segment| segment.path == path) {
+            Some(segment) => Ok(segment),
+            None => Self::open(path, peer),
+        }
+    }
+
+    /// The segment `ptr` points into, if any
+    fn containing(ptr: *const u8) -> Option<Arc<Self>> {
+        segments()
+            .into_iter()
+            .find(|segment| segment.offset(ptr).is_some())
+    }
+
+    fn slots(&self) -> &[Slot] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self.header.ptr.add(size_of::<u64>()) as *const Slot,
+                self.pages,
+            )
+        }
+    }
+
+    fn offset(&self, ptr: *const u8) -> Option<usize> {
+        let offset = (ptr as usize).checked_sub(self.data.ptr as usize)?;
+
+        (offset < self.data.len).then_some(offset)
+    }
+
+    /// The first page of the allocation that contains `offset`
+    fn head(&self, offset: usize) -> Result<usize> {
+        let page = self
+            .slots()
+            .get(offset / SHARED_PAGE_SIZE)
+            .ok_or_eyre("Offset out of the shared memory")?;
+
+        Ok(page.head.load(Ordering::Acquire) as usize)
+    }
+
+    fn retain(&self, head: usize) {
+        self.slots()[head].refs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn release(&self, head: usize) {
+        self.slots()[head].refs.fetch_sub(1, Ordering::Release);
+    }
+
+    /// Find `pages` consecutive free pages, first fit.
+    fn allocate(&self, pages: usize) -> Option<usize> {
+        let _guard = self.allocator.lock().ok()?;
+        let slots = self.slots();
+
+        let mut start = 0;
+        let mut page = 0;
+
+        while page < self.pages && page - start < pages {
+            let slot = &slots[page];
+
+            match slot.refs.load(Ordering::Acquire) {
+                0 => page += 1,
+                _ => {
+                    page += (slot.pages.load(Ordering::Relaxed) as usize).max(1);
+                    start = page;
+                }
+            }
+        }
+
+        if page.saturating_sub(start) < pages {
+            return None;
+        }
+
+        for slot in &slots[start..start + pages] {
+            slot.head.store(start as u32, Ordering::Relaxed);
+        }
+
+        slots[start].pages.store(pages as u32, Ordering::Relaxed);
+        slots[start].refs.store(1, Ordering::Release);
+
+        Some(start)
+    }
+}
+
+/// One reference to an allocation, released when dropped.
+struct Lease {
+    segment: Arc<Segment>,
+    head: usize,
+}
+
+impl Lease {
+    /// Take another reference to an allocation of a segment created by this process
+    fn retain(segment: Arc<Segment>, head: usize) -> Self {
+        segment.retain(head);
+
+        Self { segment, head }
+    }
+}
+
+/// A buffer mapped from the segment of another process, kept alive by its `lender`.
+struct Borrowed {
+    _segment: Arc<Segment>,
+    _lender: Arc<dyn Allocation>,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.segment.release(self.head);
+    }
+}
+
+/// A shared memory arena, where outputs allocate the buffers of their messages. Messages
+/// built from these buffers are sent to other processes without copying their data.
+#[derive(Clone)]
+pub struct SharedArena {
+    segment: Arc<Segment>,
+}
+
+/// The arena of the host, for dynamically linked nodes
+static HOST_ARENA: OnceLock<fn() -> Result<SharedArena>> = OnceLock::new();
+
+/// The arena of this process
+static LOCAL_ARENA: Mutex<Option<SharedArena>> = Mutex::new(None);
+
+impl SharedArena {
+    /// Create a new arena of at least `capacity` bytes
+    pub fn new(capacity: usize) -> Result<Self> {
+        Ok(Self {
+            segment: Segment::create(capacity)?,
+        })
+    }
+
+    /// The arena shared by every node of the process, created on first use with
+    /// `DEFAULT_ARENA_CAPACITY`
+    pub fn current() -> Result<Self> {
+        match HOST_ARENA.get() {
+            Some(host) => host(),
+            None => Self::local(),
+        }
+    }
+
+    /// The arena of this copy of the crate, given to dynamically linked nodes by the host
+    pub(crate) fn local() -> Result<Self> {
+        let mut arena = LOCAL_ARENA
+            .lock()
+            .map_err(|_| eyre::eyre!("Shared arena lock poisoned"))?;
+
+        if arena.is_none() {
+            *arena = Some(Self::new(DEFAULT_ARENA_CAPACITY)?);
+        }
+
+        arena.clone().ok_or_eyre("Shared arena not created")
+    }
+
+    /// Use the arena of the host in a dynamically linked node
+    pub(crate) fn set_host(arena: fn() -> Result<SharedArena>) {
+        let _ = HOST_ARENA.set(arena);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.segment.data.len
+    }
+
+    /// The bytes of the pages that are still referenced, by this process or another one
+    pub fn used(&self) -> usize {
+        let slots = self.segment.slots();
+
+        let mut used = 0;
+        let mut page = 0;
+
+        while page < self.segment.pages {
+            let slot = &slots[page];
+
+            match slot.refs.load(Ordering::Acquire) {
+                0 => page += 1,
+                _ => {
+                    let pages = (slot.pages.load(Ordering::Relaxed) as usize).max(1);
+
+                    used += pages * SHARED_PAGE_SIZE;
+                    page += pages;
+                }
+            }
+        }
+
+        used
+    }
+
+    /// Allocate a zeroed buffer of `len` bytes, fails if the arena is full
+    pub fn alloc(&self, len: usize) -> Result<SharedBufferMut> {
+        let pages = len.div_ceil(SHARED_PAGE_SIZE).max(1);
+        let head = self.segment.allocate(pages).ok_or_eyre(format!(
+            "Shared arena is full, can't allocate {} bytes",
+            len
+        ))?;
+
+        let mut buffer = SharedBufferMut {
+            lease: Lease {
+                segment: self.segment.clone(),
+                head,
+            },
+            len,
+        };
+
+        buffer.fill(0);
+
+        Ok(buffer)
+    }
+}
+
+/// A buffer allocated in a `SharedArena`, that can be written until it's frozen into an
+/// Arrow `Buffer`.
+pub struct SharedBufferMut {
+    lease: Lease,
+    len: usize,
+}
+
+impl SharedBufferMut {
+    fn ptr(&self) -> *mut u8 {
+        unsafe {
+            self.lease
+                .segment
+                .data
+                .ptr
+                .add(self.lease.head * SHARED_PAGE_SIZE)
+        }
+    }
+
+    pub fn freeze(self) -> Buffer {
+        let ptr = NonNull::new(self.ptr()).expect("Shared memory is never null");
+
+        unsafe { Buffer::from_custom_allocation(ptr, self.len, Arc::new(self.lease)) }
+    }
+}
+
+impl Deref for SharedBufferMut {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr(), self.len) }
+    }
+}
+
+impl DerefMut for SharedBufferMut {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr(), self.len) }
+    }
+}
+
+/// A reference to a buffer in shared memory, as sent to another process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde")]
+pub struct SharedBuffer {
+    pub segment: PathBuf,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl SharedBuffer {
+    /// The shared memory behind `buffer`, if it has been allocated in a `SharedArena`
+    pub fn locate(buffer: &Buffer) -> Option<Self> {
+        let segment = Segment::containing(buffer.as_ptr())?;
+
+        Some(Self {
+            offset: segment.offset(buffer.as_ptr())?,
+            segment: segment.path.clone(),
+            len: buffer.len(),
+        })
+    }
+
+    /// Whether the buffer is in a segment created by one of `pids`
+    pub fn created_by(&self, pids: &[u32]) -> bool {
+        pids.iter().any(|pid| created_by(&self.segment, *pid))
+    }
+
+    /// Map the buffer read-only, without copying it. Only a segment of this process, or one
+    /// created by the process `peer`, is mapped. `lender` is kept alive as long as a buffer
+    /// mapped from the segment of another process.
+    pub fn into_buffer(self, peer: u32, lender: &Arc<dyn Allocation>) -> Result<Buffer> {
+        let segment = Segment::opened(&self.segment, peer)?;
+
+        let end = self.offset.checked_add(self.len);
+        if end.is_none_or(|end| end > segment.data.len) {
+            eyre::bail!("Shared buffer out of the shared memory {:?}", self.segment);
+        }
+
+        let head = segment.head(self.offset)?;
+        let ptr = NonNull::new(unsafe { segment.data.ptr.add(self.offset) })
+            .ok_or_eyre("Shared memory is never null")?;
+
+        let owner: Arc<dyn Allocation> = match segment.owned {
+            true => Arc::new(Lease::retain(segment, head)),
+            false => Arc::new(Borrowed {
+                _segment: segment,
+                _lender: lender.clone(),
+            }),
+        };
+
+        Ok(unsafe { Buffer::from_custom_allocation(ptr, self.len, owner) })
+    }
+}
+
+/// The validity bitmap of a `SharedArray`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde")]
+pub struct SharedNulls {
+    pub buffer: Option<SharedBuffer>,
+    pub offset: usize,
+    pub len: usize,
+}
+
+/// An `ArrayData` whose buffers are all in shared memory, as sent to another process. Empty
+/// buffers are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "crate::prelude::thirdparty::serde")]
+pub struct SharedArray {
+    pub data_type: DataType,
+    pub len: usize,
+    pub offset: usize,
+
+    pub nulls: Option<SharedNulls>,
+    pub buffers: Vec<Option<SharedBuffer>>,
+    pub children: Vec<SharedArray>,
+}
+
+fn locate(buffer: &Buffer) -> Option<Option<SharedBuffer>> {
+    match buffer.is_empty() {
+        true => Some(None),
+        false => SharedBuffer::locate(buffer).map(Some),
+    }
+}
+
+fn into_buffer(
+    buffer: Option<SharedBuffer>,
+    peer: u32,
+    lender: &Arc<dyn Allocation>,
+) -> Result<Buffer> {
+    match buffer {
+        Some(buffer) => buffer.into_buffer(peer, lender),
+        None => Ok(Buffer::from_vec(Vec::<u8>::new())),
+    }
+}
+
+impl SharedArray {
+    /// The shared memory behind every buffer of `data`, `None` if one of them has not been
+    /// allocated in a `SharedArena`
+    pub fn locate(data: &ArrayData) -> Option<Self> {
+        let nulls = match data.nulls() {
+            Some(nulls) => Some(SharedNulls {
+                buffer: locate(nulls.buffer())?,
+                offset: nulls.offset(),
+                len: nulls.len(),
+            }),
+            None => None,
+        };
+
+        Some(Self {
+            data_type: data.data_type().clone(),
+            len: data.len(),
+            offset: data.offset(),
+            nulls,
+            buffers: data.buffers().iter().map(locate).collect::<Option<_>>()?,
+            children: data
+                .child_data()
+                .iter()
+                .map(Self::locate)
+                .collect::<Option<_>>()?,
+        })
+    }
+
+    /// Whether every buffer is in a segment created by one of `pids`, so the other side of a
+    /// link maps them from the process it's connected to, or from its own segments
+    pub fn created_by(&self, pids: &[u32]) -> bool {
+        self.nulls
+            .as_ref()
+            .and_then(|nulls| nulls.buffer.as_ref())
+            .is_none_or(|buffer| buffer.created_by(pids))
+            && self
+                .buffers
+                .iter()
+                .flatten()
+                .all(|buffer| buffer.created_by(pids))
+            && self.children.iter().all(|child| child.created_by(pids))
+    }
+
+    /// Map every buffer read-only and rebuild the `ArrayData`, without copying it, see
+    /// `SharedBuffer::into_buffer`. `lender` is dropped once every buffer mapped from the
+    /// segment of another process is dropped.
+    pub fn into_data(self, peer: u32, lender: &Arc<dyn Allocation>) -> Result<ArrayData> {
+        let nulls = match self.nulls {
+            Some(nulls) => Some(NullBuffer::new(BooleanBuffer::new(
+                into_buffer(nulls.buffer, peer, lender)?,
+                nulls.offset,
+                nulls.len,
+            ))),
+            None => None,
+        };
+
+        ArrayDataBuilder::new(self.data_type)
+            .len(self.len)
+            .offset(self.offset)
+            .nulls(nulls)
+            .buffers(
+                self.buffers
+                    .into_iter()
+                    .map(|buffer| into_buffer(buffer, peer, lender))
+                    .collect::<Result<_>>()?,
+            )
+            .child_data(
+                self.children
+                    .into_iter()
+                    .map(|child| child.into_data(peer, lender))
+                    .collect::<Result<_>>()?,
+            )
+            .build()
+            .wrap_err("Invalid shared array")
+    }
+}
+
+/// The arrays sent by reference to another process, kept until it returns them. Dropping the
+/// table, once the other process is gone, releases every array it didn't return.
+#[derive(Default)]
+pub struct SharedLeases {
+    next: AtomicU64,
+    lent: Mutex<HashMap<u64, ArrayData>>,
+}
+
+impl SharedLeases {
+    /// Keep `data` until `release` is called with the returned id
+    pub fn lend(&self, data: ArrayData) -> u64 {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+
+        if let Ok(mut lent) = self.lent.lock() {
+            lent.insert(id, data);
+        }
+
+        id
+    }
+
+    pub fn release(&self, id: u64) {
+        if let Ok(mut lent) = self.lent.lock() {
+            lent.remove(&id);
+        }
+    }
+
+    /// The number of arrays not returned yet
+    pub fn len(&self) -> usize {
+        self.lent.lock().map(|lent| lent.len()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
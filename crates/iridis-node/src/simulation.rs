@@ -0,0 +1,106 @@
+//! This module defines the `NodeClock`, the time and randomness a node should use so it can
+//! run in a simulation. Outside of a simulation it's the system time and an unseeded
+//! generator. In a simulation, every node runs on the thread of the simulation: the time is
+//! virtual, it only advances when every node is waiting, and the generator is seeded.
+
+use std::{
+    cell::RefCell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{Duration, SystemTime},
+};
+
+use crate::prelude::thirdparty::tokio::time::{self, Instant};
+
+/// A small `SplitMix64` generator, enough to make the choices of a node reproducible.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        z ^ (z >> 31)
+    }
+}
+
+struct Simulated {
+    seed: u64,
+    epoch: SystemTime,
+    start: Instant,
+}
+
+thread_local! {
+    static SIMULATION: RefCell<Option<Simulated>> = const { RefCell::new(None) };
+    static GENERATOR: RefCell<SplitMix64> = RefCell::new(SplitMix64(
+        RandomState::new().build_hasher().finish(),
+    ));
+}
+
+/// The simulation running on the current thread, it ends when dropped.
+pub struct SimulationGuard {
+    previous: Option<(Option<Simulated>, SplitMix64)>,
+}
+
+impl SimulationGuard {
+    /// Run a simulation on the current thread, whose virtual time starts at `epoch`. It must
+    /// be called from a `tokio` runtime with a paused clock.
+    pub fn enter(seed: u64, epoch: SystemTime) -> Self {
+        let simulation = Simulated {
+            seed,
+            epoch,
+            start: Instant::now(),
+        };
+
+        let previous = SIMULATION.with_borrow_mut(|current| current.replace(simulation));
+        let generator =
+            GENERATOR.with_borrow_mut(|current| std::mem::replace(current, SplitMix64(seed)));
+
+        Self {
+            previous: Some((previous, generator)),
+        }
+    }
+}
+
+impl Drop for SimulationGuard {
+    fn drop(&mut self) {
+        if let Some((previous, generator)) = self.previous.take() {
+            SIMULATION.set(previous);
+            GENERATOR.set(generator);
+        }
+    }
+}
+
+/// The clock of the runtime, as seen by a node.
+pub struct NodeClock;
+
+impl NodeClock {
+    /// The current time: the system time, or the virtual time in a simulation.
+    pub fn now() -> SystemTime {
+        SIMULATION
+            .with_borrow(|simulation| {
+                simulation
+                    .as_ref()
+                    .map(|simulation| simulation.epoch + simulation.start.elapsed())
+            })
+            .unwrap_or_else(SystemTime::now)
+    }
+
+    /// Wait for `duration`, in virtual time in a simulation.
+    pub async fn sleep(duration: Duration) {
+        time::sleep(duration).await
+    }
+
+    /// A random number, from a generator seeded by the simulation in a simulation.
+    pub fn random() -> u64 {
+        GENERATOR.with_borrow_mut(|generator| generator.next())
+    }
+
+    /// The seed of the simulation running on this thread, if any.
+    pub fn simulation() -> Option<u64> {
+        SIMULATION.with_borrow(|simulation| simulation.as_ref().map(|simulation| simulation.seed))
+    }
+}
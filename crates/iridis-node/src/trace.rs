@@ -0,0 +1,229 @@
+//! This module defines the traces of a dataflow. Every message carries a `TraceContext` in
+//! its `Header`, so its path through the nodes can be stitched into one distributed trace:
+//! receiving a message makes its trace the current trace of the node, and the messages the
+//! node sends next continue it. The primitives record a span for every send, receive, query
+//! and answer, which the runtime can export.
+
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+
+use crate::prelude::{thirdparty::tokio::sync::mpsc, *};
+
+/// The trace a message belongs to, and the span that sent it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+}
+
+impl TraceContext {
+    /// A span starting a new trace
+    pub fn root() -> Self {
+        Self {
+            trace_id: ((NodeClock::random() as u128) << 64 | NodeClock::random() as u128).max(1),
+            span_id: NodeClock::random().max(1),
+        }
+    }
+
+    /// A new span in the same trace
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: NodeClock::random().max(1),
+        }
+    }
+
+    /// The trace and span ids in big endian, as in W3C Trace Context
+    pub fn to_bytes(&self) -> [u8; 24] {
+        let mut bytes = [0; 24];
+
+        bytes[..16].copy_from_slice(&self.trace_id.to_be_bytes());
+        bytes[16..].copy_from_slice(&self.span_id.to_be_bytes());
+
+        bytes
+    }
+
+    /// Read the bytes written by `to_bytes`, all zeros meaning no trace
+    pub fn from_bytes(bytes: [u8; 24]) -> Option<Self> {
+        let (trace_id, span_id) = bytes.split_at(16);
+
+        let context = Self {
+            trace_id: u128::from_be_bytes(trace_id.try_into().ok()?),
+            span_id: u64::from_be_bytes(span_id.try_into().ok()?),
+        };
+
+        match context.trace_id == 0 || context.span_id == 0 {
+            true => None,
+            false => Some(context),
+        }
+    }
+}
+
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}-{:016x}", self.trace_id, self.span_id)
+    }
+}
+
+/// What a span recorded by a primitive is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// An output sending a message
+    Send,
+    /// An input receiving a message, from the time it was sent
+    Receive,
+    /// A query waiting for its response
+    Query,
+    /// A queryable answering a request
+    Answer,
+}
+
+/// A span recorded by a primitive.
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    pub context: TraceContext,
+    pub parent: Option<u64>,
+
+    pub kind: SpanKind,
+    /// The node and the label of the primitive
+    pub node: NodeID,
+    pub port: String,
+
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub error: bool,
+}
+
+/// The most spans waiting for the exporter, the next ones are dropped
+pub const MAX_PENDING_SPANS: usize = 8192;
+
+/// The spans of a dataflow, they're only recorded while an exporter is attached.
+#[derive(Debug, Default)]
+pub struct Traces {
+    exporter: Mutex<Option<mpsc::Sender<SpanRecord>>>,
+    dropped: AtomicU64,
+}
+
+impl Traces {
+    /// Attach an exporter, which receives every span recorded from now on. It replaces the
+    /// previous exporter. Spans are dropped while `MAX_PENDING_SPANS` are waiting for it.
+    pub fn export(&self) -> mpsc::Receiver<SpanRecord> {
+        let (sender, receiver) = mpsc::channel(MAX_PENDING_SPANS);
+
+        if let Ok(mut exporter) = self.exporter.lock() {
+            exporter.replace(sender);
+        }
+
+        receiver
+    }
+
+    /// Detach the exporter, its receiver ends once it has received the spans already recorded.
+    pub fn detach(&self) {
+        if let Ok(mut exporter) = self.exporter.lock() {
+            exporter.take();
+        }
+    }
+
+    /// Whether spans are recorded
+    pub fn enabled(&self) -> bool {
+        self.exporter
+            .lock()
+            .map(|exporter| exporter.is_some())
+            .unwrap_or_default()
+    }
+
+    /// The spans dropped because the exporter couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, span: SpanRecord) {
+        if let Ok(exporter) = self.exporter.lock() {
+            if let Some(exporter) = exporter.as_ref() {
+                if let Err(mpsc::error::TrySendError::Full(_)) = exporter.try_send(span) {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// The current trace of a node, shared by its primitives. It's the trace of the last message
+/// received, or answered by a queryable.
+#[derive(Debug, Clone, Default)]
+pub struct NodeTracer {
+    pub traces: Arc<Traces>,
+
+    current: Arc<Mutex<Option<TraceContext>>>,
+}
+
+impl NodeTracer {
+    pub fn new(traces: &Arc<Traces>) -> Self {
+        Self {
+            traces: traces.clone(),
+            current: Arc::default(),
+        }
+    }
+
+    /// The current trace of the node
+    pub fn current(&self) -> Option<TraceContext> {
+        self.current.lock().ok().and_then(|current| *current)
+    }
+
+    /// Make `context` the current trace of the node
+    pub fn enter(&self, context: TraceContext) {
+        if let Ok(mut current) = self.current.lock() {
+            current.replace(context);
+        }
+    }
+
+    /// A new span, continuing the trace of `parent` or starting a new one. Returns the span
+    /// and the span id of its parent.
+    pub fn span(parent: Option<TraceContext>) -> (TraceContext, Option<u64>) {
+        match parent {
+            Some(parent) => (parent.child(), Some(parent.span_id)),
+            None => (TraceContext::root(), None),
+        }
+    }
+
+    /// Record a span if an exporter is attached, and emit a `tracing` event for it
+    pub fn record(
+        &self,
+        (context, parent): (TraceContext, Option<u64>),
+        kind: SpanKind,
+        node: &NodeID,
+        port: &str,
+        start: SystemTime,
+        error: bool,
+    ) {
+        tracing::trace!(
+            node = %node.label,
+            port = %port,
+            kind = ?kind,
+            trace = %context,
+            error,
+            "message"
+        );
+
+        if !self.traces.enabled() {
+            return;
+        }
+
+        self.traces.record(SpanRecord {
+            context,
+            parent,
+            kind,
+            node: node.clone(),
+            port: port.to_string(),
+            start,
+            end: NodeClock::now(),
+            error,
+        });
+    }
+}
@@ -0,0 +1,584 @@
+//! This module implements the runtime side of the C ABI. It exposes the primitives of a node
+//! as opaque handles, and adapts a node exported through `IRIDIS_NODE_ABI` to the `Node` trait.
+
+use std::{
+    ffi::c_void,
+    path::Path,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::serde_yml,
+    thirdparty::{
+        libloading,
+        tokio::{
+            self,
+            runtime::Handle,
+            sync::{Mutex, OwnedMutexGuard, watch},
+            task::JoinHandle,
+        },
+    },
+    *,
+};
+
+/// A primitive opened by the node.
+enum HostChannel {
+    Input(RawInput),
+    Output(RawOutput),
+    Query(RawQuery),
+    Queryable {
+        queryable: RawQueryable,
+        /// The header of the request waiting for a response, and the trace it's answered in
+        pending: Option<(Header, TraceContext)>,
+    },
+}
+
+/// The pointer to a `HostPrimitive` is the handle given to the node.
+struct HostPrimitive {
+    channel: Arc<Mutex<HostChannel>>,
+    closed: watch::Sender<bool>,
+
+    runtime: Handle,
+    stop: watch::Receiver<bool>,
+}
+
+/// Everything the node can reach through the `AbiHost`.
+struct HostContext {
+    runtime: Handle,
+
+    inputs: Arc<Mutex<Inputs>>,
+    outputs: Arc<Mutex<Outputs>>,
+    queries: Arc<Mutex<Queries>>,
+    queryables: Arc<Mutex<Queryables>>,
+
+    /// Keeps the handles valid until the node is dropped
+    primitives: StdMutex<Vec<Arc<HostPrimitive>>>,
+    /// Set when the node ends or is stopped, every pending call then returns `AbiStatus::CLOSED`
+    stop: watch::Sender<bool>,
+
+    reports: StdMutex<Vec<String>>,
+}
+
+/// Run a task on the runtime and wait for its output. This works from any thread, even
+/// from a thread of another `tokio` runtime.
+fn block_on<T: Send + 'static>(
+    runtime: &Handle,
+    task: impl Future<Output = T> + Send + 'static,
+) -> Option<T> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+
+    runtime.spawn(async move {
+        let _ = sender.send(task.await);
+    });
+
+    receiver.recv().ok()
+}
+
+/// Resolves once the signal is set, or once its sender is dropped.
+async fn signaled(mut signal: watch::Receiver<bool>) {
+    let _ = signal.wait_for(|value| *value).await;
+}
+
+impl HostPrimitive {
+    /// # Safety
+    ///
+    /// The handle must have been returned by `host_open`, and the node must not be dropped yet.
+    unsafe fn from_handle<'a>(handle: *mut c_void) -> &'a Self {
+        unsafe { &*(handle as *const HostPrimitive) }
+    }
+
+    /// Run an operation on the channel, unless the handle is closed or the node is stopped.
+    fn call<T: Send + 'static, F: Future<Output = Result<T, AbiStatus>> + Send + 'static>(
+        &self,
+        operation: impl FnOnce(OwnedMutexGuard<HostChannel>) -> F + Send + 'static,
+    ) -> Result<T, AbiStatus> {
+        let closed = self.closed.subscribe();
+        let stop = self.stop.clone();
+
+        if *closed.borrow() || *stop.borrow() {
+            return Err(AbiStatus::CLOSED);
+        }
+
+        let channel = self.channel.clone();
+
+        block_on(&self.runtime, async move {
+            tokio::select! {
+                result = async move { operation(channel.lock_owned().await).await } => result,
+                _ = signaled(closed) => Err(AbiStatus::CLOSED),
+                _ = signaled(stop) => Err(AbiStatus::CLOSED),
+            }
+        })
+        .unwrap_or(Err(AbiStatus::CLOSED))
+    }
+}
+
+fn status(result: Result<(), AbiStatus>) -> AbiStatus {
+    match result {
+        Ok(()) => AbiStatus::OK,
+        Err(status) => status,
+    }
+}
+
+/// Write a message to a destination given by the node.
+///
+/// # Safety
+///
+/// The destination must be valid.
+unsafe fn export(message: DataflowMessage, destination: *mut AbiMessage) -> Result<(), AbiStatus> {
+    let message = AbiMessage::try_from_message(message).map_err(|_| AbiStatus::ERROR)?;
+
+    unsafe { *destination = message };
+
+    Ok(())
+}
+
+unsafe extern "C" fn host_open(
+    context: *mut c_void,
+    kind: AbiPrimitive,
+    label: AbiStr,
+) -> *mut c_void {
+    let context = unsafe { &*(context as *const HostContext) };
+
+    let Ok(label) = (unsafe { label.as_str() }) else {
+        return std::ptr::null_mut();
+    };
+    let label = label.to_string();
+
+    let (inputs, outputs, queries, queryables) = (
+        context.inputs.clone(),
+        context.outputs.clone(),
+        context.queries.clone(),
+        context.queryables.clone(),
+    );
+
+    let channel =
+        block_on(&context.runtime, async move {
+            match kind {
+                AbiPrimitive::INPUT => inputs.lock().await.raw(label).await.map(HostChannel::Input),
+                AbiPrimitive::OUTPUT => outputs
+                    .lock()
+                    .await
+                    .raw(label)
+                    .await
+                    .map(HostChannel::Output),
+                AbiPrimitive::QUERY => queries
+                    .lock()
+                    .await
+                    .raw(label)
+                    .await
+                    .map(HostChannel::Query),
+                AbiPrimitive::QUERYABLE => {
+                    queryables.lock().await.raw(label).await.map(|queryable| {
+                        HostChannel::Queryable {
+                            queryable,
+                            pending: None,
+                        }
+                    })
+                }
+                AbiPrimitive(kind) => Err(eyre::eyre!("Invalid primitive kind {}", kind)),
+            }
+        });
+
+    let Some(Ok(channel)) = channel else {
+        return std::ptr::null_mut();
+    };
+
+    let primitive = Arc::new(HostPrimitive {
+        channel: Arc::new(Mutex::new(channel)),
+        closed: watch::channel(false).0,
+        runtime: context.runtime.clone(),
+        stop: context.stop.subscribe(),
+    });
+
+    let handle = Arc::as_ptr(&primitive) as *mut c_void;
+
+    context
+        .primitives
+        .lock()
+        .expect("Host primitives lock poisoned")
+        .push(primitive);
+
+    handle
+}
+
+unsafe extern "C" fn host_connected(handle: *mut c_void, index: usize, uuid: *mut AbiUuid) -> bool {
+    let primitive = unsafe { HostPrimitive::from_handle(handle) };
+
+    let connected = primitive.call(async move |channel| match &*channel {
+        HostChannel::Queryable { queryable, .. } => Ok(queryable.tx.keys().nth(index).cloned()),
+        _ => Err(AbiStatus::ERROR),
+    });
+
+    match connected {
+        Ok(Some(connected)) => {
+            unsafe { *uuid = connected.into() };
+
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn host_recv(handle: *mut c_void, message: *mut AbiMessage) -> AbiStatus {
+    let primitive = unsafe { HostPrimitive::from_handle(handle) };
+
+    let received = primitive.call(async move |mut channel| match &mut *channel {
+        HostChannel::Input(input) => input.recv().await.map_err(|_| AbiStatus::CLOSED),
+        HostChannel::Queryable { queryable, pending } => {
+            let request = queryable.rx.recv().await.ok_or(AbiStatus::CLOSED)?;
+
+            // The node answers in the trace of the request
+            let (trace, _) = NodeTracer::span(request.header.trace);
+            queryable.tracer.enter(trace);
+
+            *pending = Some((request.header.clone(), trace));
+
+            Ok(request)
+        }
+        _ => Err(AbiStatus::ERROR),
+    });
+
+    status(received.and_then(|received| unsafe { export(received, message) }))
+}
+
+unsafe extern "C" fn host_send(handle: *mut c_void, message: *mut AbiMessage) -> AbiStatus {
+    let primitive = unsafe { HostPrimitive::from_handle(handle) };
+
+    let Ok(message) = (unsafe { AbiMessage::take(message) }) else {
+        return AbiStatus::ERROR;
+    };
+
+    status(primitive.call(async move |mut channel| {
+        match &mut *channel {
+            HostChannel::Output(output) => output
+                .send_with(message.data, message.header.metadata)
+                .await
+                .map_err(|_| AbiStatus::CLOSED),
+            HostChannel::Queryable { queryable, pending } => {
+                let (request, trace) = pending.take().ok_or(AbiStatus::ERROR)?;
+                let sender = queryable
+                    .tx
+                    .get(&request.source.1)
+                    .ok_or(AbiStatus::ERROR)?;
+
+                let response = DataflowMessage {
+                    header: Header {
+                        timestamp: queryable.clock.new_timestamp(),
+                        source: (queryable.source.uuid, queryable.layout.uuid),
+                        trace: Some(trace),
+                        sequence: request.sequence,
+                        metadata: request.metadata,
+                    },
+                    data: message.data,
+                };
+
+                sender.send(response).await.map_err(|_| AbiStatus::CLOSED)
+            }
+            _ => Err(AbiStatus::ERROR),
+        }
+    }))
+}
+
+unsafe extern "C" fn host_query(
+    handle: *mut c_void,
+    request: *mut AbiMessage,
+    response: *mut AbiMessage,
+) -> AbiStatus {
+    let primitive = unsafe { HostPrimitive::from_handle(handle) };
+
+    let Ok(request) = (unsafe { AbiMessage::take(request) }) else {
+        return AbiStatus::ERROR;
+    };
+
+    let received = primitive.call(async move |mut channel| match &mut *channel {
+        HostChannel::Query(query) => query
+            .query_with(request.data, request.header.metadata)
+            .await
+            .map_err(|_| AbiStatus::CLOSED),
+        _ => Err(AbiStatus::ERROR),
+    });
+
+    status(received.and_then(|received| unsafe { export(received, response) }))
+}
+
+unsafe extern "C" fn host_close(handle: *mut c_void) {
+    let primitive = unsafe { HostPrimitive::from_handle(handle) };
+
+    primitive.closed.send_replace(true);
+}
+
+unsafe extern "C" fn host_report(context: *mut c_void, report: AbiStr) {
+    let context = unsafe { &*(context as *const HostContext) };
+
+    let report = unsafe { report.as_str() }
+        .unwrap_or("Invalid report")
+        .to_string();
+
+    context
+        .reports
+        .lock()
+        .expect("Host reports lock poisoned")
+        .push(report);
+}
+
+impl HostContext {
+    fn host(self: &Arc<Self>) -> AbiHost {
+        AbiHost {
+            context: Arc::as_ptr(self) as *mut c_void,
+            open: host_open,
+            connected: host_connected,
+            recv: host_recv,
+            send: host_send,
+            query: host_query,
+            close: host_close,
+            report: host_report,
+        }
+    }
+
+    /// Every error reported by the node, as one report
+    fn report(&self, message: String) -> eyre::Report {
+        let reports =
+            std::mem::take(&mut *self.reports.lock().expect("Host reports lock poisoned"));
+
+        eyre::Report::msg(
+            reports
+                .iter()
+                .fold(message, |acc, report| format!("{}\n\n{}", acc, report)),
+        )
+    }
+}
+
+/// The instance returned by `AbiNode::new`.
+#[derive(Clone, Copy)]
+struct Instance(*mut c_void);
+
+unsafe impl Send for Instance {}
+unsafe impl Sync for Instance {}
+
+/// Calls `stop` on the node if the task awaiting for it is aborted. `stop` and `drop` are
+/// never called at the same time, and `stop` is never called after `drop`.
+struct StopGuard {
+    abi: AbiNode,
+    instance: Instance,
+
+    dropped: Arc<StdMutex<bool>>,
+    stop: watch::Sender<bool>,
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        self.stop.send_replace(true);
+
+        let dropped = self.dropped.lock().expect("Node lock poisoned");
+        if !*dropped {
+            unsafe { (self.abi.stop)(self.instance.0) };
+        }
+    }
+}
+
+/// A node exported through the C ABI, adapted to the `Node` trait. Only `start` can be used,
+/// the node is created with `AbiLinkedNode::new`.
+pub struct AbiLinkedNode {
+    abi: AbiNode,
+    instance: Option<Instance>,
+    context: Arc<HostContext>,
+
+    /// A handle to the library of the node, so it's unloaded only once the node is dropped,
+    /// even if the task running it is aborted before.
+    #[cfg(not(target_family = "unix"))]
+    library: Option<libloading::Library>,
+    #[cfg(target_family = "unix")]
+    library: Option<libloading::os::unix::Library>,
+}
+
+impl AbiLinkedNode {
+    /// Create the node with the functions it exports. It must be called inside a `tokio` runtime,
+    /// the primitives of the node are served by this runtime.
+    pub async fn new(
+        abi: AbiNode,
+        inputs: Inputs,
+        outputs: Outputs,
+        queries: Queries,
+        queryables: Queryables,
+        configuration: serde_yml::Value,
+    ) -> Result<Self> {
+        if abi.version != IRIDIS_ABI_VERSION {
+            eyre::bail!(
+                "Unsupported C ABI version {} for this runtime, expected {}",
+                abi.version,
+                IRIDIS_ABI_VERSION
+            );
+        }
+
+        let source = inputs.source().clone();
+        let configuration = serde_yml::to_string(&configuration)
+            .wrap_err("Failed to serialize the configuration of the node")?;
+
+        let context = Arc::new(HostContext {
+            runtime: Handle::current(),
+            inputs: Arc::new(Mutex::new(inputs)),
+            outputs: Arc::new(Mutex::new(outputs)),
+            queries: Arc::new(Mutex::new(queries)),
+            queryables: Arc::new(Mutex::new(queryables)),
+            primitives: StdMutex::new(Vec::new()),
+            stop: watch::channel(false).0,
+            reports: StdMutex::new(Vec::new()),
+        });
+
+        let host = context.host();
+        let instance = tokio::task::spawn_blocking(move || {
+            let host = host;
+            let instance = unsafe {
+                (abi.new)(
+                    host,
+                    AbiStr::new(&source.label),
+                    source.uuid.into(),
+                    AbiStr::new(&configuration),
+                )
+            };
+
+            Instance(instance)
+        })
+        .await?;
+
+        if instance.0.is_null() {
+            return Err(context.report("Failed to create the node through the C ABI".to_string()));
+        }
+
+        Ok(Self {
+            abi,
+            instance: Some(instance),
+            context,
+            library: None,
+        })
+    }
+
+    /// Load the node from the `IRIDIS_NODE_ABI` symbol of a library already opened by the runtime.
+    pub async fn load(
+        path: &Path,
+        inputs: Inputs,
+        outputs: Outputs,
+        queries: Queries,
+        queryables: Queryables,
+        configuration: serde_yml::Value,
+    ) -> Result<Self> {
+        let path_buf = path.to_path_buf();
+        let (library, abi) = tokio::task::spawn_blocking(move || {
+            let library = unsafe {
+                #[cfg(target_family = "unix")]
+                let library = libloading::os::unix::Library::open(
+                    Some(path_buf.clone()),
+                    libloading::os::unix::RTLD_NOW,
+                )
+                .wrap_err(format!("Failed to load path {:?}", path_buf))?;
+
+                #[cfg(not(target_family = "unix"))]
+                let library = libloading::Library::new(path_buf.clone())
+                    .wrap_err(format!("Failed to load path {:?}", path_buf))?;
+
+                library
+            };
+
+            let abi = unsafe {
+                library
+                    .get::<*const AbiNode>(b"IRIDIS_NODE_ABI")
+                    .wrap_err(format!(
+                        "Failed to load symbol 'IRIDIS_NODE_ABI' from dylib {:?}",
+                        path_buf
+                    ))?
+                    .read()
+            };
+
+            Ok::<_, eyre::Report>((library, abi))
+        })
+        .await??;
+
+        let mut node = Self::new(abi, inputs, outputs, queries, queryables, configuration)
+            .await
+            .wrap_err(format!(
+                "Failed to create dynamically linked node from dylib {:?}",
+                path
+            ))?;
+
+        node.library = Some(library);
+
+        Ok(node)
+    }
+}
+
+impl Node for AbiLinkedNode {
+    fn new(
+        _: Inputs,
+        _: Outputs,
+        _: Queries,
+        _: Queryables,
+        _: serde_yml::Value,
+    ) -> JoinHandle<Result<Box<dyn Node>>> {
+        tokio::spawn(async {
+            Err(eyre::eyre!(
+                "A node exported through the C ABI must be created with `AbiLinkedNode::new`"
+            ))
+        })
+    }
+
+    fn start(mut self: Box<Self>) -> JoinHandle<Result<()>> {
+        let (abi, context, library) = (self.abi, self.context.clone(), self.library.take());
+
+        let Some(instance) = self.instance.take() else {
+            return tokio::spawn(async { Err(eyre::eyre!("The node has already been started")) });
+        };
+
+        tokio::spawn(async move {
+            let dropped = Arc::new(StdMutex::new(false));
+
+            let _guard = StopGuard {
+                abi,
+                instance,
+                dropped: dropped.clone(),
+                stop: context.stop.clone(),
+            };
+
+            let ended = context.clone();
+            let status = tokio::task::spawn_blocking(move || {
+                let instance = instance;
+                let status = unsafe { (abi.start)(instance.0) };
+
+                *dropped.lock().expect("Node lock poisoned") = true;
+                unsafe { (abi.drop)(instance.0) };
+
+                ended.stop.send_replace(true);
+                drop(library);
+
+                status
+            })
+            .await?;
+
+            match status.validate() {
+                Ok(AbiStatus::ERROR) => Err(context.report("The node failed".to_string())),
+                Ok(_) => Ok(()),
+                Err(report) => Err(context.report(report.to_string())),
+            }
+        })
+    }
+}
+
+impl Drop for AbiLinkedNode {
+    /// A node that has never been started is dropped on a blocking thread, because the node
+    /// may wait for calls in progress that need the runtime.
+    fn drop(&mut self) {
+        let Some(instance) = self.instance.take() else {
+            return;
+        };
+
+        let (abi, context, library) = (self.abi, self.context.clone(), self.library.take());
+
+        context.stop.send_replace(true);
+        context.runtime.clone().spawn_blocking(move || {
+            let instance = instance;
+            unsafe { (abi.drop)(instance.0) };
+
+            drop(context);
+            drop(library);
+        });
+    }
+}
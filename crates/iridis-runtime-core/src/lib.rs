@@ -1,9 +1,13 @@
 //! This module defines the `core` elements common to crates related to the `runtime`.
 
+pub(crate) mod abi;
+pub(crate) mod metadata;
 pub(crate) mod node;
 
 /// This prelude contains everything you need to use this crate.
 pub mod prelude {
+    pub use crate::abi::*;
+    pub use crate::metadata::*;
     pub use crate::node::*;
 
     pub use iridis_node::{self, prelude::*};
@@ -12,6 +16,7 @@ pub mod prelude {
 
     pub mod thirdparty {
         pub use libloading;
+        pub use tokio;
 
         pub use eyre::{self, Context, OptionExt, Result};
     }
@@ -0,0 +1,110 @@
+//! This module reads and checks the metadata exported by dynamically linked libraries,
+//! before any of their symbol is called.
+
+use std::path::{Path, PathBuf};
+
+use crate::prelude::{
+    thirdparty::{libloading, tokio},
+    *,
+};
+
+#[cfg(not(target_family = "unix"))]
+type Library = libloading::Library;
+#[cfg(target_family = "unix")]
+type Library = libloading::os::unix::Library;
+
+/// What a dynamically linked library exports, each kind has its own metadata symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryKind {
+    Node,
+    FileExtPlugin,
+    UrlSchemePlugin,
+}
+
+impl LibraryKind {
+    /// The name of the metadata symbol
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            LibraryKind::Node => "IRIDIS_NODE_METADATA",
+            LibraryKind::FileExtPlugin => "IRIDIS_FILE_EXT_PLUGIN_METADATA",
+            LibraryKind::UrlSchemePlugin => "IRIDIS_URL_SCHEME_PLUGIN_METADATA",
+        }
+    }
+}
+
+/// Read the metadata of an opened library.
+pub fn read_metadata(library: &Library, kind: LibraryKind) -> Result<Metadata> {
+    let symbol = kind.symbol();
+
+    let metadata = unsafe {
+        library
+            .get::<*const LibraryMetadata>(symbol.as_bytes())
+            .wrap_err(format!(
+                "Failed to load symbol '{}', the library may have been built with an older version of iridis",
+                symbol
+            ))?
+            .read()
+    };
+
+    unsafe { metadata.to_metadata() }
+}
+
+/// Check the metadata of an opened library against the one of the runtime, before using
+/// any other symbol of the library. Returns the checked metadata.
+pub fn check_metadata(
+    library: &Library,
+    kind: LibraryKind,
+    expected: &Metadata,
+    path: &Path,
+) -> Result<Metadata> {
+    read_metadata(library, kind)
+        .and_then(|metadata| metadata.check(expected).map(|_| metadata))
+        .wrap_err(format!("Refusing to load library {:?}", path))
+}
+
+/// Open a library without calling any of its symbols.
+fn open(path: &Path) -> Result<Library> {
+    unsafe {
+        #[cfg(target_family = "unix")]
+        let library =
+            libloading::os::unix::Library::open(Some(path), libloading::os::unix::RTLD_NOW)
+                .wrap_err(format!("Failed to load path {:?}", path))?;
+
+        #[cfg(not(target_family = "unix"))]
+        let library =
+            libloading::Library::new(path).wrap_err(format!("Failed to load path {:?}", path))?;
+
+        Ok(library)
+    }
+}
+
+/// Open a library only to read its metadata: nothing is instantiated.
+pub async fn library_metadata(path: PathBuf, kind: LibraryKind) -> Result<Metadata> {
+    tokio::task::spawn_blocking(move || {
+        read_metadata(&open(&path)?, kind)
+            .wrap_err(format!("Failed to read metadata of {:?}", path))
+    })
+    .await?
+}
+
+/// Open a library only to read the manifest of its node: nothing is instantiated. Returns
+/// `None` if the node doesn't declare its ports.
+pub async fn library_manifest(path: PathBuf) -> Result<Option<NodeManifest>> {
+    tokio::task::spawn_blocking(move || {
+        let library = open(&path)?;
+
+        let manifest = unsafe {
+            library
+                .get::<*const NodeManifestInstance>(b"IRIDIS_NODE_MANIFEST")
+                .wrap_err(format!(
+                    "Failed to load symbol 'IRIDIS_NODE_MANIFEST' from dylib {:?}",
+                    path
+                ))?
+                .read()
+        }();
+
+        NodeManifest::import(unsafe { manifest.as_str()? })
+            .wrap_err(format!("Failed to read manifest of {:?}", path))
+    })
+    .await?
+}
@@ -1,7 +1,15 @@
 //! This module defines the `RuntimeNode` enum, which can represent either a statically linked or dynamically linked node.
 //! It's separated from other modules because it's fundamentally a brick of the runtime.
 
-use crate::prelude::{thirdparty::libloading, *};
+use std::time::Duration;
+
+use crate::prelude::{
+    thirdparty::{libloading, tokio::sync::mpsc},
+    *,
+};
+
+/// How long a node with lifecycle hooks has to end after a stop request, before it's aborted.
+pub const STOP_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
 /// This struct represents a dynamically linked node.
 /// It loads the node from a shared library at runtime, storing the handle as a `Box<dyn Node>`.
@@ -30,9 +38,103 @@ impl RuntimeNode {
     /// This function will either start the statically linked node or the dynamically linked node,
     /// awaiting for the result.
     pub async fn run(self) -> Result<()> {
-        match self {
-            RuntimeNode::StaticallyLinked(node) => node.start().await?,
-            RuntimeNode::DynamicallyLinked(node) => node.handle.start().await?,
-        }
+        self.run_until(std::future::pending()).await
+    }
+
+    /// This function will start the node and `await` for its result, unless the `stop` future
+    /// completes first. In that case the node task is aborted and dropped *before* the library
+    /// of a dynamically linked node is released.
+    pub async fn run_until(self, stop: impl Future<Output = ()>) -> Result<()> {
+        let (handle, library) = match self {
+            RuntimeNode::StaticallyLinked(node) => (node, None),
+            RuntimeNode::DynamicallyLinked(node) => (node.handle, Some(node._library)),
+        };
+
+        let mut task = handle.start();
+
+        let result = tokio::select! {
+            result = &mut task => result,
+            _ = stop => {
+                task.abort();
+
+                match task.await {
+                    Err(error) if error.is_cancelled() => Ok(Ok(())),
+                    result => result,
+                }
+            }
+        };
+
+        drop(library);
+
+        result?
+    }
+
+    /// Run the node with its lifecycle: it's started once every node of the dataflow is ready.
+    /// When the `stop` future completes, a node with hooks receives `LifecycleEvent::Stop` and
+    /// is aborted after `STOP_GRACE_PERIOD`, any other node is aborted right away and doesn't
+    /// receive any other event.
+    pub async fn run_with(
+        self,
+        mut lifecycle: Lifecycle,
+        events: mpsc::UnboundedSender<LifecycleEvent>,
+        stop: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let (handle, library) = match self {
+            RuntimeNode::StaticallyLinked(node) => (node, None),
+            RuntimeNode::DynamicallyLinked(node) => (node.handle, Some(node._library)),
+        };
+
+        tokio::pin!(stop);
+
+        let hooked = handle.has_hooks();
+        let task = match hooked {
+            true => Some(handle.run(lifecycle)),
+            false => {
+                let started = tokio::select! {
+                    started = lifecycle.ready() => started,
+                    _ = &mut stop => false,
+                };
+
+                drop(lifecycle);
+
+                match started {
+                    true => Some(handle.start()),
+                    false => {
+                        drop(handle);
+
+                        None
+                    }
+                }
+            }
+        };
+
+        let result = match task {
+            Some(mut task) => tokio::select! {
+                result = &mut task => result,
+                _ = &mut stop => {
+                    let graceful = match hooked && events.send(LifecycleEvent::Stop).is_ok() {
+                        true => tokio::time::timeout(STOP_GRACE_PERIOD, &mut task).await.ok(),
+                        false => None,
+                    };
+
+                    match graceful {
+                        Some(result) => result,
+                        None => {
+                            task.abort();
+
+                            match task.await {
+                                Err(error) if error.is_cancelled() => Ok(Ok(())),
+                                result => result,
+                            }
+                        }
+                    }
+                }
+            },
+            None => Ok(Ok(())),
+        };
+
+        drop(library);
+
+        result?
     }
 }
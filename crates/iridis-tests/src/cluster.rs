@@ -0,0 +1,237 @@
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    time::Duration,
+};
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::{
+        tokio::{io::AsyncWriteExt, net::TcpStream},
+        *,
+    },
+    *,
+};
+
+fn free_address() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn runtime(id: &str) -> Runtime {
+    Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .with_id(id)
+}
+
+/// A `Transport` on each machine, the first one feeding the second one
+async fn layout(second: &str) -> (Arc<DataflowLayout>, NodeID, NodeID) {
+    let layout = DataflowLayout::empty();
+
+    let (perception, (_, output)) = layout
+        .node("perception", async |builder: &mut NodeLayout| {
+            builder.machine("perception");
+
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (planning, (input, _)) = layout
+        .node("planning", async |builder: &mut NodeLayout| {
+            builder.machine(second);
+
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    (layout, perception, planning)
+}
+
+#[tokio::test]
+async fn bridge_machines_over_tcp() {
+    // Each machine builds its own layout, with its own uuids
+    let (first_layout, perception, remote_planning) = layout("planning").await;
+    let (second_layout, remote_perception, planning) = layout("planning").await;
+
+    let (a, b) = (free_address(), free_address());
+
+    let (first, second) = tokio::join!(
+        runtime("perception").await.spawn_distributed(
+            first_layout,
+            Cluster::new("perception", a).peer("planning", b),
+            async |loader: &mut Loader| {
+                loader.load::<Transport>(perception.clone(), serde_yml::from_str("")?);
+                loader.load::<Transport>(remote_planning.clone(), serde_yml::from_str("")?);
+
+                Ok(())
+            }
+        ),
+        runtime("planning").await.spawn_distributed(
+            second_layout,
+            Cluster::new("planning", b).peer("perception", a),
+            async |loader: &mut Loader| {
+                loader.load::<Transport>(remote_perception.clone(), serde_yml::from_str("")?);
+                loader.load::<Transport>(planning.clone(), serde_yml::from_str("")?);
+
+                Ok(())
+            }
+        ),
+    );
+
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    assert_eq!(first.status(&perception).await, Some(NodeStatus::Running));
+    assert_eq!(first.status(&remote_planning).await, None);
+    assert_eq!(second.status(&planning).await, Some(NodeStatus::Running));
+
+    let sender = first.input_sender(perception.input("in")).await.unwrap();
+    let mut receiver = second.subscribe(planning.output("out")).await.unwrap();
+
+    let mut header = Header::new(first.clock().new_timestamp(), (Uuid::nil(), Uuid::nil()));
+    header.metadata.lineage = Some(Lineage::default());
+
+    sender
+        .send(DataflowMessage {
+            header,
+            data: "fixture".to_string().try_into_arrow().unwrap().into_data(),
+        })
+        .await
+        .unwrap();
+
+    let message: TypedDataflowMessage<String> = receiver.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message.data, "fixture");
+
+    // The uuids of the first machine are translated to the ones of the second machine
+    let lineage = message.header.metadata.lineage.clone().unwrap();
+    assert_eq!(
+        lineage.hops(),
+        [
+            (remote_perception.uuid, remote_perception.output("out").uuid),
+            (planning.uuid, planning.output("out").uuid)
+        ]
+    );
+
+    // The clock of the second machine caught up with the first one
+    assert_eq!(*first.clock().get_id(), clock_id("perception"));
+    assert!(second.clock().new_timestamp() > message.header.timestamp);
+    assert!(second.clock_drifts()["perception"].messages >= 1);
+
+    // Closing the input on the first machine ends both of them
+    drop(sender);
+
+    first.wait().await.unwrap();
+    second.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn reject_different_layouts() {
+    let (first_layout, perception, _) = layout("planning").await;
+    let (second_layout, _, planning) = layout("perception").await;
+
+    let (a, b) = (free_address(), free_address());
+
+    let (first, second) = tokio::join!(
+        runtime("perception").await.spawn_distributed(
+            first_layout,
+            Cluster::new("perception", a).peer("planning", b),
+            async |loader: &mut Loader| {
+                loader.load::<Transport>(perception.clone(), serde_yml::from_str("")?);
+
+                Ok(())
+            }
+        ),
+        runtime("planning").await.spawn_distributed(
+            second_layout,
+            Cluster::new("planning", b)
+                .peer("perception", a)
+                .timeout(Duration::from_secs(1)),
+            async |loader: &mut Loader| {
+                loader.load::<Transport>(planning.clone(), serde_yml::from_str("")?);
+
+                Ok(())
+            }
+        ),
+    );
+
+    let report = format!("{:?}", first.err().unwrap());
+    assert!(report.contains("differs"), "{}", report);
+
+    // The machine accepting the connection keeps waiting for a machine with the same layout
+    let report = format!("{:?}", second.err().unwrap());
+    assert!(report.contains("timed out"), "{}", report);
+    assert!(report.contains("differs"), "{}", report);
+}
+
+#[tokio::test]
+async fn ignore_stray_connections() {
+    let (first_layout, perception, remote_planning) = layout("planning").await;
+    let (second_layout, remote_perception, planning) = layout("planning").await;
+
+    let (a, b) = (free_address(), free_address());
+
+    // A connection that never says anything, and one announcing a 4 GiB frame
+    let strays = async {
+        let silent = loop {
+            match TcpStream::connect(a).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+
+        let mut oversized = TcpStream::connect(a).await.unwrap();
+        oversized.write_all(&u32::MAX.to_le_bytes()).await.unwrap();
+
+        (silent, oversized)
+    };
+
+    let (first, second, _strays) = tokio::join!(
+        runtime("perception").await.spawn_distributed(
+            first_layout,
+            Cluster::new("perception", a)
+                .peer("planning", b)
+                .timeout(Duration::from_secs(5)),
+            async |loader: &mut Loader| {
+                loader.load::<Transport>(perception.clone(), serde_yml::from_str("")?);
+                loader.load::<Transport>(remote_planning.clone(), serde_yml::from_str("")?);
+
+                Ok(())
+            }
+        ),
+        async {
+            // Let the strays connect first
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            runtime("planning")
+                .await
+                .spawn_distributed(
+                    second_layout,
+                    Cluster::new("planning", b).peer("perception", a),
+                    async |loader: &mut Loader| {
+                        loader
+                            .load::<Transport>(remote_perception.clone(), serde_yml::from_str("")?);
+                        loader.load::<Transport>(planning.clone(), serde_yml::from_str("")?);
+
+                        Ok(())
+                    },
+                )
+                .await
+        },
+        strays,
+    );
+
+    let (first, second) = (first.unwrap(), second.unwrap());
+
+    first.stop();
+    second.stop();
+
+    first.wait().await.unwrap();
+    second.wait().await.unwrap();
+}
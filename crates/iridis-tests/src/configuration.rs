@@ -0,0 +1,310 @@
+use iridis::prelude::{iridis_node::prelude::thirdparty::serde::Deserialize, thirdparty::*, *};
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "iridis::prelude::iridis_node::prelude::thirdparty::serde")]
+struct Nested {
+    name: String,
+    limits: Limits,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "iridis::prelude::iridis_node::prelude::thirdparty::serde")]
+pub struct Limits {
+    min: u32,
+    max: Option<u32>,
+}
+
+#[derive(Node)]
+pub struct Configured {
+    pub limits: Limits,
+}
+
+#[node(runtime = "default_runtime", config = Limits)]
+impl Node for Configured {
+    async fn new(_: Inputs, _: Outputs, _: Queries, _: Queryables, limits: Limits) -> Result<Self> {
+        Ok(Self { limits })
+    }
+
+    async fn start(self: Box<Self>) -> Result<()> {
+        match self.limits.max {
+            Some(max) if max < self.limits.min => Err(eyre::eyre!("Empty limits")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Receives the configuration checked against `Limits`, as it was written
+mod raw {
+    use iridis::prelude::{thirdparty::*, *};
+
+    use super::Limits;
+
+    #[derive(Node)]
+    pub struct RawConfigured {
+        pub configuration: serde_yml::Value,
+    }
+
+    #[node(runtime = "default_runtime", config = Limits)]
+    impl Node for RawConfigured {
+        async fn new(
+            _: Inputs,
+            _: Outputs,
+            _: Queries,
+            _: Queryables,
+            #[config(raw)] configuration: serde_yml::Value,
+        ) -> Result<Self> {
+            Ok(Self { configuration })
+        }
+
+        async fn start(self: Box<Self>) -> Result<()> {
+            match self.configuration["min"].as_u64() {
+                Some(4) => Err(eyre::eyre!("Raw minimum")),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+/// Its configuration type is named `Value` too, but it's deserialized like any other
+mod named {
+    use iridis::prelude::{iridis_node::prelude::thirdparty::serde::Deserialize, thirdparty::*, *};
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "iridis::prelude::iridis_node::prelude::thirdparty::serde")]
+    pub struct Value {
+        min: u32,
+    }
+
+    #[derive(Node)]
+    pub struct Named {}
+
+    #[node(runtime = "default_runtime", config = Value)]
+    impl Node for Named {
+        async fn new(
+            _: Inputs,
+            _: Outputs,
+            _: Queries,
+            _: Queryables,
+            value: Value,
+        ) -> Result<Self> {
+            match value.min {
+                2 => Err(eyre::eyre!("Typed minimum")),
+                _ => Ok(Self {}),
+            }
+        }
+
+        async fn start(self: Box<Self>) -> Result<()> {
+            Ok(())
+        }
+    }
+}
+
+/// Reads its configuration keys with the generated constructor
+mod keyed {
+    use iridis::prelude::{thirdparty::*, *};
+
+    #[derive(Node)]
+    pub struct Keyed {
+        #[config]
+        pub count: u32,
+        #[config("label")]
+        pub name: Option<String>,
+        #[config(default = 1)]
+        pub step: u32,
+    }
+
+    #[node(runtime = "default_runtime")]
+    impl Node for Keyed {
+        async fn start(self: Box<Self>) -> Result<()> {
+            match (self.count, self.name, self.step) {
+                (3, None, 1) => Err(eyre::eyre!("Keyed defaults")),
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+use keyed::Keyed;
+use named::Named;
+use raw::RawConfigured;
+
+#[test]
+fn deserialize_typed_configuration() {
+    let nested: Nested = deserialize_configuration(
+        serde_yml::from_str("{ name: a, limits: { min: 1, max: 2 } }").unwrap(),
+    )
+    .unwrap();
+    assert_eq!(nested.name, "a");
+    assert_eq!((nested.limits.min, nested.limits.max), (1, Some(2)));
+
+    let report = format!(
+        "{:?}",
+        deserialize_configuration::<Nested>(
+            serde_yml::from_str("{ name: a, limits: { min: -1 } }").unwrap()
+        )
+        .unwrap_err()
+    );
+    assert!(report.contains("Invalid configuration at 'limits.min'"));
+
+    let report = format!(
+        "{:?}",
+        deserialize_configuration::<Nested>(
+            serde_yml::from_str("{ name: a, limits: { min: 1, maxi: 2 } }").unwrap()
+        )
+        .unwrap_err()
+    );
+    assert!(report.contains("Unknown configuration field(s) 'limits.maxi'"));
+
+    let schema = configuration_schema::<Nested>().unwrap();
+    assert_eq!(schema["name"], serde_yml::Value::from("STR"));
+    assert_eq!(schema["limits"]["min"], serde_yml::Value::from("U32"));
+    assert_eq!(
+        schema["limits"]["max"]["OPTION"],
+        serde_yml::Value::from("U32")
+    );
+
+    assert!(configuration_schema::<serde_yml::Value>().is_none());
+}
+
+#[tokio::test]
+async fn reject_invalid_configuration() {
+    let configuration = Timer::manifest().unwrap().configuration.unwrap();
+    assert_eq!(configuration["frequency"], serde_yml::Value::from("F64"));
+
+    for (fixture, expected) in [
+        (
+            "frecuency: 10.0",
+            "Unknown configuration field(s) 'frecuency'",
+        ),
+        ("frequency: fast", "Invalid configuration at 'frequency'"),
+        ("frequency: 0", "expected a finite value > 0, got 0"),
+        ("frequency: -2.5", "expected a finite value > 0, got -2.5"),
+    ] {
+        let layout = DataflowLayout::empty();
+
+        let (timer, _) = layout
+            .node("timer", async |builder: &mut NodeLayout| {
+                builder.output("out")
+            })
+            .await;
+
+        let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+        let result = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+            .await
+            .unwrap()
+            .spawn(layout, async |loader: &mut Loader| {
+                loader.load::<Timer>(timer, serde_yml::from_str(fixture)?);
+
+                Ok(())
+            })
+            .await;
+
+        let report = format!("{:?}", result.err().unwrap());
+        assert!(report.contains("Invalid configuration for node 'timer'"));
+        assert!(report.contains(expected));
+    }
+}
+
+#[tokio::test]
+async fn pass_typed_configuration_to_new() {
+    let layout = DataflowLayout::empty();
+
+    let (configured, _) = layout
+        .node("configured", async |_: &mut NodeLayout| {})
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Configured>(configured, serde_yml::from_str("{ min: 2, max: 1 }")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let report = format!("{:?}", handle.wait().await.unwrap_err());
+    assert!(report.contains("Empty limits"));
+}
+
+/// Spawn a dataflow made of a single node without ports
+async fn spawn<T: Node + 'static>(configuration: &str) -> Result<DataflowHandle> {
+    let layout = DataflowLayout::empty();
+
+    let (node, _) = layout.node("node", async |_: &mut NodeLayout| {}).await;
+
+    let layout = layout.finish(async |_| Ok(())).await?;
+
+    let configuration: serde_yml::Value = serde_yml::from_str(configuration)?;
+
+    Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await?
+        .spawn(layout, async move |loader: &mut Loader| {
+            loader.load::<T>(node, configuration);
+
+            Ok(())
+        })
+        .await
+}
+
+#[tokio::test]
+async fn pass_raw_configuration_to_new() {
+    // The raw configuration is still checked against `Limits`
+    let report = format!(
+        "{:?}",
+        spawn::<RawConfigured>("{ min: 1, mini: 2 }")
+            .await
+            .err()
+            .unwrap()
+    );
+    assert!(report.contains("Unknown configuration field(s) 'mini'"));
+
+    let handle = spawn::<RawConfigured>("{ min: 4 }").await.unwrap();
+
+    let report = format!("{:?}", handle.wait().await.unwrap_err());
+    assert!(report.contains("Raw minimum"));
+
+    let report = format!("{:?}", spawn::<Named>("{ min: 2 }").await.err().unwrap());
+    assert!(report.contains("Typed minimum"));
+}
+
+#[test]
+fn manifest_of_typed_configuration() {
+    let manifest = Named::manifest().unwrap();
+
+    assert_eq!(manifest.ports, None);
+    assert_eq!(
+        manifest.configuration.unwrap()["min"],
+        serde_yml::Value::from("U32")
+    );
+}
+
+#[tokio::test]
+async fn check_configuration_keys() {
+    let report = format!("{:?}", spawn::<Keyed>("{ step: 2 }").await.err().unwrap());
+    assert!(report.contains("Missing configuration key 'count' for node 'node'"));
+
+    let report = format!(
+        "{:?}",
+        spawn::<Keyed>("{ count: 1, stpe: 2 }").await.err().unwrap()
+    );
+    assert!(report.contains("Unknown configuration key(s) 'stpe' for node 'node'"));
+    assert!(report.contains("expected one of: count, label, step"));
+
+    // A missing `Option` is `None`, and a missing key with a default uses it
+    let handle = spawn::<Keyed>("{ count: 3 }").await.unwrap();
+
+    let report = format!("{:?}", handle.wait().await.unwrap_err());
+    assert!(report.contains("Keyed defaults"));
+
+    spawn::<Keyed>("{ count: 3, label: a }")
+        .await
+        .unwrap()
+        .wait()
+        .await
+        .unwrap();
+}
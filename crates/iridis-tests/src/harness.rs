@@ -0,0 +1,291 @@
+//! This module defines the `NodeHarness`, to test a node alone. The harness builds a layout
+//! with the node and a mirror of it, whose primitives are connected to every port of the
+//! node, and instantiates the node with real `Inputs`, `Outputs`, `Queries` and `Queryables`.
+//! The test drives the node through typed handles on the mirror.
+
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+use iridis::prelude::{
+    thirdparty::{
+        tokio::{self, task::JoinHandle},
+        *,
+    },
+    *,
+};
+
+/// How long a handle waits for the node by default
+pub const DEFAULT_HARNESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn timeout<T>(timeout: Duration, what: String, future: impl Future<Output = T>) -> Result<T> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .wrap_err(format!(
+            "Timed out after {:?} waiting for {}",
+            timeout, what
+        ))
+}
+
+/// A node running alone, see the module documentation.
+pub struct NodeHarness<T: Node> {
+    pub node: NodeID,
+    pub clock: Arc<HLC>,
+
+    flows: Arc<RuntimeFlows>,
+
+    // The primitives of the mirror, by the kind of the port of the node they're connected to
+    inputs: Outputs,
+    outputs: Inputs,
+    queries: Queryables,
+    queryables: Queries,
+
+    task: JoinHandle<Result<()>>,
+    timeout: Duration,
+
+    _node: PhantomData<T>,
+}
+
+impl<T: Node + 'static> NodeHarness<T> {
+    /// Instantiate and start the node with its `configuration`, with the ports declared by its
+    /// manifest.
+    pub async fn new(configuration: serde_yml::Value) -> Result<Self> {
+        let ports = T::manifest()
+            .and_then(|manifest| manifest.ports)
+            .ok_or_eyre(format!(
+                "'{}' doesn't declare its ports, use `NodeHarness::with_ports`",
+                std::any::type_name::<T>()
+            ))?;
+
+        Self::with_ports(ports, configuration).await
+    }
+
+    /// Instantiate and start the node with its `configuration` and the given ports.
+    pub async fn with_ports(
+        ports: Vec<PortManifest>,
+        configuration: serde_yml::Value,
+    ) -> Result<Self> {
+        let layout = DataflowLayout::empty();
+
+        let (node, _) = layout
+            .node("node", async |builder: &mut NodeLayout| {
+                NodeManifest {
+                    name: std::any::type_name::<T>().to_string(),
+                    ports: Some(ports.clone()),
+                    configuration: None,
+                }
+                .layout(builder)
+            })
+            .await;
+
+        // The mirror has the opposite primitive for every port of the node
+        let (mirror, _) = layout
+            .node("harness", async |builder: &mut NodeLayout| {
+                for port in &ports {
+                    match port.kind {
+                        PortKind::Input => builder.output(&port.label),
+                        PortKind::Output => builder.input(&port.label),
+                        PortKind::Query => builder.queryable(&port.label),
+                        PortKind::Queryable => builder.query(&port.label),
+                    };
+                }
+            })
+            .await;
+
+        let layout =
+            layout
+                .finish(async |flows| {
+                    for port in &ports {
+                        match port.kind {
+                            PortKind::Input => flows
+                                .connect(mirror.output(&port.label), node.input(&port.label))?,
+                            PortKind::Output => flows
+                                .connect(node.output(&port.label), mirror.input(&port.label))?,
+                            PortKind::Query => flows
+                                .connect(node.query(&port.label), mirror.queryable(&port.label))?,
+                            PortKind::Queryable => flows
+                                .connect(mirror.query(&port.label), node.queryable(&port.label))?,
+                        }
+                    }
+
+                    Ok(())
+                })
+                .await?;
+
+        let clock = RuntimeClock::default().hlc();
+        let flows = Arc::new(RuntimeFlows::new(layout)?);
+
+        T::check_configuration(&configuration)?;
+
+        let (inputs, outputs, queries, queryables) =
+            flows.node_primitives(clock.clone(), node.clone());
+
+        let instance = T::new(inputs, outputs, queries, queryables, configuration)
+            .await?
+            .wrap_err(format!(
+                "Node '{}' failed to initialize",
+                std::any::type_name::<T>()
+            ))?;
+
+        let (mirror_inputs, mirror_outputs, mirror_queries, mirror_queryables) =
+            flows.node_primitives(clock.clone(), mirror);
+
+        Ok(Self {
+            node,
+            clock,
+            flows,
+            inputs: mirror_outputs,
+            outputs: mirror_inputs,
+            queries: mirror_queryables,
+            queryables: mirror_queries,
+            task: instance.start(),
+            timeout: DEFAULT_HARNESS_TIMEOUT,
+            _node: PhantomData,
+        })
+    }
+
+    /// Set how long the handles wait for the node.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// A handle to send messages to an input of the node.
+    pub async fn input<M: ArrowMessage>(&mut self, label: &str) -> Result<HarnessInput<M>> {
+        Ok(HarnessInput {
+            output: self.inputs.with(label).await?,
+        })
+    }
+
+    /// A handle to receive the messages sent by an output of the node.
+    pub async fn output<M: ArrowMessage>(&mut self, label: &str) -> Result<HarnessOutput<M>> {
+        Ok(HarnessOutput {
+            input: self.outputs.with(label).await?,
+            timeout: self.timeout,
+        })
+    }
+
+    /// A mock queryable, to answer a query of the node.
+    pub async fn query<Q: ArrowMessage, R: ArrowMessage>(
+        &mut self,
+        label: &str,
+    ) -> Result<HarnessQueryable<Q, R>> {
+        Ok(HarnessQueryable {
+            queryable: self.queries.with(label).await?,
+            timeout: self.timeout,
+        })
+    }
+
+    /// A handle to query a queryable of the node.
+    pub async fn queryable<Q: ArrowMessage, R: ArrowMessage>(
+        &mut self,
+        label: &str,
+    ) -> Result<HarnessQuery<Q, R>> {
+        Ok(HarnessQuery {
+            query: self.queryables.with(label).await?,
+            timeout: self.timeout,
+        })
+    }
+
+    /// Close every port of the mirror not claimed by a handle, and `await` for the node to end.
+    pub async fn finish(self) -> Result<()> {
+        self.flows.release_unclaimed().await;
+
+        let Self {
+            inputs,
+            outputs,
+            queries,
+            queryables,
+            task,
+            timeout: limit,
+            ..
+        } = self;
+
+        drop((inputs, outputs, queries, queryables));
+
+        timeout(limit, "the node to end".to_string(), task).await??
+    }
+}
+
+/// Sends messages to an input of the node, dropping it closes the input.
+pub struct HarnessInput<M: ArrowMessage> {
+    output: Output<M>,
+}
+
+impl<M: ArrowMessage> HarnessInput<M> {
+    pub async fn send(&self, data: M) -> Result<()> {
+        self.output.send(data).await
+    }
+}
+
+/// Receives the messages sent by an output of the node.
+pub struct HarnessOutput<M: ArrowMessage> {
+    input: Input<M>,
+    timeout: Duration,
+}
+
+impl<M: ArrowMessage> HarnessOutput<M> {
+    /// The next message, fails if none is sent in time or if the output is closed.
+    pub async fn recv(&mut self) -> Result<TypedDataflowMessage<M>> {
+        let what = format!("a message on output '{}'", self.input.raw.layout.label);
+
+        timeout(self.timeout, what, self.input.recv()).await?
+    }
+
+    /// Check that the output is closed, or sends nothing more for `duration`.
+    pub async fn expect_none(&mut self, duration: Duration) -> Result<()> {
+        match tokio::time::timeout(duration, self.input.raw.rx.recv()).await {
+            Ok(Some(_)) => Err(eyre::eyre!(
+                "Unexpected message on output '{}'",
+                self.input.raw.layout.label
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Answers a query of the node.
+pub struct HarnessQueryable<Q: ArrowMessage, R: ArrowMessage> {
+    queryable: Queryable<Q, R>,
+    timeout: Duration,
+}
+
+impl<Q: ArrowMessage + Send + 'static, R: ArrowMessage + Send + 'static> HarnessQueryable<Q, R> {
+    /// Answer the next request, fails if the node doesn't query in time.
+    pub async fn answer(
+        &mut self,
+        response: impl AsyncFnOnce(TypedDataflowMessage<Q>) -> Result<R>,
+    ) -> Result<()> {
+        let what = format!("a request on query '{}'", self.queryable.raw.layout.label);
+
+        timeout(self.timeout, what, self.queryable.on_query(response)).await?
+    }
+
+    /// Answer every request with `response` in the background, until the query is closed.
+    pub fn serve(
+        mut self,
+        response: impl Fn(TypedDataflowMessage<Q>) -> Result<R> + Send + Sync + 'static,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while self
+                .queryable
+                .on_query(async |message| response(message))
+                .await
+                .is_ok()
+            {}
+        })
+    }
+}
+
+/// Queries a queryable of the node.
+pub struct HarnessQuery<Q: ArrowMessage, R: ArrowMessage> {
+    query: Query<Q, R>,
+    timeout: Duration,
+}
+
+impl<Q: ArrowMessage, R: ArrowMessage> HarnessQuery<Q, R> {
+    /// Send a request and wait for the response of the node.
+    pub async fn query(&mut self, data: Q) -> Result<TypedDataflowMessage<R>> {
+        let what = format!("a response on queryable '{}'", self.query.raw.layout.label);
+
+        timeout(self.timeout, what, self.query.query(data)).await?
+    }
+}
@@ -1,2 +1,35 @@
+#[cfg(feature = "iridis")]
+pub mod harness;
+
+#[cfg(test)]
+mod cluster;
+#[cfg(test)]
+mod configuration;
 #[cfg(test)]
 mod layout;
+#[cfg(test)]
+mod lifecycle;
+#[cfg(test)]
+mod lineage;
+#[cfg(test)]
+mod manifest;
+#[cfg(test)]
+mod metadata;
+#[cfg(test)]
+mod metrics;
+#[cfg(test)]
+mod nodes;
+#[cfg(test)]
+mod placement;
+#[cfg(all(test, unix))]
+mod process;
+#[cfg(test)]
+mod record;
+#[cfg(test)]
+mod reload;
+#[cfg(test)]
+mod runtime;
+#[cfg(test)]
+mod simulation;
+#[cfg(test)]
+mod trace;
@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use iridis::prelude::{thirdparty::*, *};
+
+use crate::runtime::Failing;
+
+/// The hooks called on each `Hooked` node, by label
+static CALLS: LazyLock<Mutex<HashMap<String, Vec<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn record(label: &str, call: impl Into<String>) {
+    CALLS
+        .lock()
+        .unwrap()
+        .entry(label.to_string())
+        .or_default()
+        .push(call.into());
+}
+
+fn calls(label: &str) -> Vec<String> {
+    CALLS
+        .lock()
+        .unwrap()
+        .get(label)
+        .cloned()
+        .unwrap_or_default()
+}
+
+async fn wait_for(label: &str, count: usize) -> Vec<String> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let calls = calls(label);
+
+            if calls.len() >= count {
+                return calls;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap()
+}
+
+#[derive(Node)]
+pub struct Hooked {
+    label: String,
+    events: Option<LifecycleEvents>,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Hooked {
+    async fn new(
+        _: Inputs,
+        _: Outputs,
+        _: Queries,
+        _: Queryables,
+        configuration: serde_yml::Value,
+    ) -> Result<Self> {
+        Ok(Self {
+            label: serde_yml::from_value(configuration["label"].clone())?,
+            events: None,
+        })
+    }
+
+    async fn start(mut self: Box<Self>) -> Result<()> {
+        record(&self.label, "start");
+
+        let mut events = self.events.take().ok_or_eyre("Missing lifecycle events")?;
+
+        while let Some(event) = events.recv().await {
+            match event {
+                LifecycleEvent::Stop => break,
+                LifecycleEvent::Pause => record(&self.label, "pause"),
+                LifecycleEvent::Resume => record(&self.label, "resume"),
+                LifecycleEvent::PeerError(peer, _) => {
+                    record(&self.label, format!("error: {}", peer.label))
+                }
+            }
+        }
+
+        record(&self.label, "stop");
+
+        Ok(())
+    }
+
+    async fn on_ready(&mut self, events: LifecycleEvents) -> Result<()> {
+        record(&self.label, "ready");
+
+        self.events = Some(events);
+
+        Ok(())
+    }
+}
+
+mod echo {
+    use iridis::prelude::{thirdparty::*, *};
+
+    /// Sends back what it receives until it's asked to stop
+    #[derive(Node)]
+    pub struct Echo {
+        #[input("in")]
+        input: Input<String>,
+        #[output("out")]
+        output: Output<String>,
+
+        events: Option<LifecycleEvents>,
+    }
+
+    #[node(runtime = "default_runtime")]
+    impl Node for Echo {
+        async fn start(mut self: Box<Self>) -> Result<()> {
+            let mut events = self.events.take().ok_or_eyre("Missing lifecycle events")?;
+
+            loop {
+                tokio::select! {
+                    message = self.input.recv() => self.output.send(message?.data).await?,
+                    event = events.recv() => match event {
+                        Some(LifecycleEvent::Stop) | None => return Ok(()),
+                        Some(_) => {}
+                    },
+                }
+            }
+        }
+
+        async fn on_ready(&mut self, events: LifecycleEvents) -> Result<()> {
+            self.events = Some(events);
+
+            Ok(())
+        }
+    }
+}
+
+async fn runtime() -> Runtime {
+    Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn call_lifecycle_hooks() {
+    let layout = DataflowLayout::empty();
+
+    let (hooked, _) = layout.node("hooked", async |_| {}).await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Hooked>(hooked.clone(), serde_yml::from_str("label: hooks")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(wait_for("hooks", 2).await, ["ready", "start"]);
+
+    handle.pause(&hooked).await.unwrap();
+    assert_eq!(handle.status(&hooked).await, Some(NodeStatus::Paused));
+    assert!(handle.pause(&hooked).await.is_err());
+
+    handle.resume(&hooked).await.unwrap();
+    assert_eq!(handle.status(&hooked).await, Some(NodeStatus::Running));
+
+    assert_eq!(
+        wait_for("hooks", 4).await,
+        ["ready", "start", "pause", "resume"]
+    );
+
+    handle.stop();
+    handle.wait().await.unwrap();
+
+    // `start` is only called once, and ends on the stop request
+    assert_eq!(
+        calls("hooks"),
+        ["ready", "start", "pause", "resume", "stop"]
+    );
+}
+
+#[tokio::test]
+async fn notify_peer_error() {
+    let layout = DataflowLayout::empty();
+
+    let (hooked, _) = layout.node("hooked", async |_| {}).await;
+    let (failing, _) = layout.node("failing", async |_| {}).await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Hooked>(hooked.clone(), serde_yml::from_str("label: peers")?);
+            loader.load::<Failing>(failing.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        wait_for("peers", 3).await,
+        ["ready", "start", "error: failing"]
+    );
+
+    assert!(handle.pause(&failing).await.is_err());
+
+    handle.stop();
+    assert!(handle.wait().await.is_err());
+
+    assert_eq!(calls("peers"), ["ready", "start", "error: failing", "stop"]);
+}
+
+#[tokio::test]
+async fn receive_inputs_with_hooks() {
+    let layout = DataflowLayout::empty();
+
+    let (node, (input, output)) = layout
+        .node("echo", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<echo::Echo>(node.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(output).await.unwrap();
+    let sender = handle.input_sender(input).await.unwrap();
+
+    for data in ["a", "b"] {
+        sender
+            .send(crate::record::message(&handle, data))
+            .await
+            .unwrap();
+
+        let message = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            TypedDataflowMessage::<String>::try_from(message)
+                .unwrap()
+                .data,
+            data
+        );
+    }
+
+    handle.pause(&node).await.unwrap();
+    handle.resume(&node).await.unwrap();
+
+    // The node ends on the stop request, before `STOP_GRACE_PERIOD`
+    handle.stop();
+    tokio::time::timeout(STOP_GRACE_PERIOD / 2, handle.wait())
+        .await
+        .unwrap()
+        .unwrap();
+}
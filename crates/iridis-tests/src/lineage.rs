@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{HLC, Uuid, arrow_array::Array},
+    thirdparty::*,
+    *,
+};
+
+#[tokio::test]
+async fn track_latency_from_source_to_sink() {
+    let layout = DataflowLayout::empty();
+
+    let (source, (_, output)) = layout
+        .node("source", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (sink, (input, sink_output)) = layout
+        .node("sink", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(source.clone(), serde_yml::from_str("")?);
+            loader.load::<Transport>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(sink_output).await.unwrap();
+    let sender = handle.input_sender(source.input("in")).await.unwrap();
+
+    for (data, lineage) in [("tracked", Some(Lineage::default())), ("untracked", None)] {
+        let mut metadata = HeaderMetadata::default();
+        metadata.lineage = lineage;
+
+        sender
+            .send(DataflowMessage {
+                header: Header {
+                    metadata,
+                    ..Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil()))
+                },
+                data: data.to_string().try_into_arrow().unwrap().into_data(),
+            })
+            .await
+            .unwrap();
+    }
+
+    // The lineage starts when 'source' first sends the data, and goes through 'sink'
+    let message = receiver.recv().await.unwrap();
+    let lineage = message.header.metadata.lineage.unwrap();
+
+    assert_eq!(
+        lineage.hops(),
+        [
+            (source.uuid, source.output("out").uuid),
+            (sink.uuid, sink.output("out").uuid)
+        ]
+    );
+    assert!(lineage.origin().unwrap() < message.header.timestamp);
+
+    let message = receiver.recv().await.unwrap();
+    assert_eq!(message.header.metadata.lineage, None);
+
+    let metrics = handle.metrics().await;
+    assert_eq!(metrics.paths.len(), 1);
+
+    let path = &metrics.paths[0];
+    assert_eq!(path.label, "source/out -> sink/in");
+    assert_eq!(path.source, source.output("out").uuid);
+    assert_eq!(path.sink, sink.input("in").uuid);
+    assert_eq!(path.latency.count, 1);
+    assert!(path.within(Duration::from_secs(1)));
+
+    let text = handle.prometheus().await;
+    assert!(text.contains("# TYPE iridis_path_latency_seconds histogram"));
+    assert!(text.contains(
+        "iridis_path_latency_seconds_count{source_node=\"source\",source=\"out\",sink_node=\"sink\",sink=\"in\"} 1"
+    ));
+
+    drop(sender);
+    handle.wait().await.unwrap();
+}
+
+#[test]
+fn derive_lineage_from_parents() {
+    let clock = HLC::default();
+
+    let header = |lineage: Option<Lineage>| {
+        let mut header = Header::new(clock.new_timestamp(), (Uuid::nil(), Uuid::nil()));
+        header.metadata.lineage = lineage;
+
+        header
+    };
+
+    let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+    let mut oldest = Lineage::default();
+    oldest.hop(clock.new_timestamp(), (a, a));
+    oldest.hop(clock.new_timestamp(), (b, b));
+
+    let mut newest = Lineage::default();
+    newest.hop(clock.new_timestamp(), (c, c));
+
+    let parents = [
+        header(Some(newest)),
+        header(None),
+        header(Some(oldest.clone())),
+    ];
+
+    // The derived message keeps the lineage of the oldest data
+    let metadata = HeaderMetadata::derived(&parents);
+    assert_eq!(metadata.lineage, Some(oldest.clone()));
+    assert_eq!(metadata.lineage.unwrap().source(), Some((a, a)));
+
+    assert_eq!(HeaderMetadata::derived(&[header(None)]).lineage, None);
+
+    // The source and the most recent hops are kept
+    let mut long = oldest.clone();
+    for _ in 0..MAX_LINEAGE_HOPS {
+        long.hop(clock.new_timestamp(), (c, c));
+    }
+
+    assert_eq!(long.hops().len(), MAX_LINEAGE_HOPS);
+    assert_eq!(long.source(), Some((a, a)));
+    assert_eq!(long.origin(), oldest.origin());
+
+    let mut metadata = HeaderMetadata::default();
+    metadata.lineage = Some(long);
+
+    assert_eq!(
+        HeaderMetadata::decode(&metadata.encode()).unwrap(),
+        metadata
+    );
+
+    metadata.lineage = Some(Lineage::default());
+
+    assert_eq!(
+        HeaderMetadata::decode(&metadata.encode()).unwrap(),
+        metadata
+    );
+}
@@ -0,0 +1,230 @@
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::*,
+    *,
+};
+
+#[derive(Node)]
+#[configuration(verbose = "BOOL")]
+pub struct Declared {
+    #[input("in")]
+    pub input: Input<String>,
+    #[output]
+    pub out: RawOutput,
+    #[config(default = "> ".to_string())]
+    pub prefix: String,
+    pub received: usize,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Declared {
+    async fn start(mut self: Box<Self>) -> Result<()> {
+        while let Ok(message) = self.input.recv().await {
+            self.received += 1;
+
+            self.out
+                .send(
+                    format!("{}{}", self.prefix, message.data)
+                        .try_into_arrow()?
+                        .into_data(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn check_layout_against_manifest() {
+    let manifest = Declared::manifest().unwrap();
+
+    assert_eq!(manifest.ports.as_ref().unwrap().len(), 2);
+    assert_eq!(
+        manifest.port(PortKind::Input, "in").unwrap().message,
+        Some(MessageManifest {
+            rust: "alloc::string::String".to_string(),
+            arrow: "Utf8".to_string(),
+        })
+    );
+    assert_eq!(
+        manifest.port(PortKind::Output, "out").unwrap().message,
+        None
+    );
+    let configuration = manifest.configuration.clone().unwrap();
+    assert_eq!(
+        configuration.get("prefix"),
+        Some(&serde_yml::Value::from("STR"))
+    );
+    assert_eq!(
+        configuration.get("verbose"),
+        Some(&serde_yml::Value::from("BOOL"))
+    );
+
+    let layout = DataflowLayout::empty();
+
+    let (generated, ports) = layout
+        .node("generated", async |builder: &mut NodeLayout| {
+            manifest.layout(builder)
+        })
+        .await;
+
+    let (mismatched, _) = layout
+        .node("mismatched", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.input("other"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    assert_eq!(ports.len(), 2);
+    assert!(manifest.check(&layout, &generated).is_ok());
+
+    let report = format!("{:?}", manifest.check(&layout, &mismatched).unwrap_err());
+    assert!(report.contains("output 'out' is declared by the node but missing from the layout"));
+    assert!(report.contains("'other' is in the layout but not declared by the node"));
+
+    let result = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Declared>(mismatched, serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn generate_constructor_from_fields() {
+    let layout = DataflowLayout::empty();
+
+    let (declared, ports) = layout
+        .node("declared", async |builder: &mut NodeLayout| {
+            Declared::manifest().unwrap().layout(builder)
+        })
+        .await;
+
+    let (misconfigured, _) = layout
+        .node("misconfigured", async |builder: &mut NodeLayout| {
+            Declared::manifest().unwrap().layout(builder)
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let runtime = async || {
+        Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+            .await
+            .unwrap()
+    };
+
+    let handle = runtime()
+        .await
+        .spawn(layout.clone(), async |loader: &mut Loader| {
+            loader.load::<Declared>(declared, serde_yml::from_str("prefix: '$ '")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(ports["out"].uuid()).await.unwrap();
+    let sender = handle.input_sender(ports["in"].uuid()).await.unwrap();
+
+    sender
+        .send(DataflowMessage {
+            header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+            data: "ls".to_string().try_into_arrow().unwrap().into_data(),
+        })
+        .await
+        .unwrap();
+
+    let message: TypedDataflowMessage<String> = receiver.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message.data, "$ ls");
+
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    let result = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Declared>(misconfigured, serde_yml::from_str("prefix: [1, 2]")?);
+
+            Ok(())
+        })
+        .await;
+
+    let report = format!("{:?}", result.err().unwrap());
+    assert!(report.contains("Invalid configuration key 'prefix' for node 'misconfigured'"));
+}
+
+mod configured {
+    use iridis::prelude::{thirdparty::*, *};
+
+    /// Only declares its configuration, its ports are claimed by hand
+    #[derive(Node)]
+    pub struct Configured {
+        #[config(default = 1)]
+        pub count: u32,
+        pub input: Option<Input<String>>,
+    }
+
+    #[node(runtime = "default_runtime")]
+    impl Node for Configured {
+        async fn new(
+            mut inputs: Inputs,
+            _: Outputs,
+            _: Queries,
+            _: Queryables,
+            configuration: serde_yml::Value,
+        ) -> Result<Self> {
+            Ok(Self {
+                count: serde_yml::from_value(configuration["count"].clone())?,
+                input: Some(inputs.with("in").await?),
+            })
+        }
+
+        async fn start(self: Box<Self>) -> Result<()> {
+            eyre::ensure!(self.count == 2 && self.input.is_some(), "Not configured");
+
+            Ok(())
+        }
+    }
+}
+
+#[tokio::test]
+async fn skip_undeclared_ports() {
+    let manifest = configured::Configured::manifest().unwrap();
+
+    assert_eq!(manifest.ports, None);
+    assert_eq!(
+        manifest.configuration.unwrap().get("count"),
+        Some(&serde_yml::Value::from("U32"))
+    );
+
+    let layout = DataflowLayout::empty();
+
+    let (node, _) = layout
+        .node("configured", async |builder: &mut NodeLayout| {
+            builder.input("in")
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<configured::Configured>(node, serde_yml::from_str("count: 2")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    handle.wait().await.unwrap();
+}
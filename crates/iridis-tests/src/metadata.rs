@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::*,
+    *,
+};
+
+use crate::{harness::*, nodes::Lookup};
+
+#[tokio::test]
+async fn forward_metadata_through_transports() {
+    let layout = DataflowLayout::empty();
+
+    let (source, (_, output)) = layout
+        .node("source", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (sink, (input, sink_output)) = layout
+        .node("sink", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(source.clone(), serde_yml::from_str("")?);
+            loader.load::<Transport>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(sink_output).await.unwrap();
+    let sender = handle.input_sender(source.input("in")).await.unwrap();
+
+    let correlation = Uuid::new_v4();
+
+    for (i, data) in ["first", "second"].into_iter().enumerate() {
+        let mut metadata = HeaderMetadata::default();
+        metadata.correlation = Some(correlation);
+
+        metadata.set("index", i.to_string()).unwrap();
+        metadata.set("raw", vec![i as u8, 0xff]).unwrap();
+
+        sender
+            .send(DataflowMessage {
+                header: Header {
+                    metadata,
+                    ..Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil()))
+                },
+                data: data.to_string().try_into_arrow().unwrap().into_data(),
+            })
+            .await
+            .unwrap();
+    }
+
+    for i in 0..2 {
+        let message = receiver.recv().await.unwrap();
+        let metadata = &message.header.metadata;
+
+        // The sequence number is the one of the output of 'sink'
+        assert_eq!(message.header.sequence, i);
+        assert_eq!(message.header.source.0, sink.uuid);
+
+        assert_eq!(metadata.correlation, Some(correlation));
+        assert_eq!(
+            metadata.get("index").and_then(|value| value.as_str()),
+            Some(i.to_string().as_str())
+        );
+        assert_eq!(
+            metadata.get("raw").map(|value| value.as_bytes()),
+            Some([i as u8, 0xff].as_slice())
+        );
+    }
+
+    drop(sender);
+    handle.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn correlate_responses_with_requests() {
+    let mut harness = NodeHarness::<Lookup>::new(serde_yml::from_str("").unwrap())
+        .await
+        .unwrap()
+        .timeout(Duration::from_secs(1));
+
+    let _key = harness.input::<String>("key").await.unwrap();
+    let _value = harness.output::<String>("value").await.unwrap();
+    let _fetch = harness.query::<String, String>("fetch").await.unwrap();
+    let mut count = harness.queryable::<u8, u64>("count").await.unwrap();
+
+    let first = count.query(0).await.unwrap();
+    let second = count.query(0).await.unwrap();
+
+    // Every request gets its own correlation id, and the response its sequence number
+    assert_eq!(first.header.sequence, 0);
+    assert_eq!(second.header.sequence, 1);
+
+    let (first, second) = (
+        first.header.metadata.correlation.unwrap(),
+        second.header.metadata.correlation.unwrap(),
+    );
+    assert_ne!(first, second);
+}
+
+#[test]
+fn metadata_bytes() {
+    let mut metadata = HeaderMetadata::default();
+    metadata.correlation = Some(Uuid::new_v4());
+
+    metadata.set("key", "value").unwrap();
+    metadata.set("bytes", vec![0, 1, 2]).unwrap();
+
+    assert_eq!(
+        HeaderMetadata::decode(&metadata.encode()).unwrap(),
+        metadata
+    );
+
+    assert!(HeaderMetadata::default().encode().is_empty());
+    assert_eq!(
+        HeaderMetadata::decode(&[]).unwrap(),
+        HeaderMetadata::default()
+    );
+
+    assert!(
+        metadata
+            .set("large", vec![0; MAX_METADATA_SIZE + 1])
+            .is_err()
+    );
+    assert!(HeaderMetadata::decode(&metadata.encode()[..10]).is_err());
+
+    let mut full = HeaderMetadata::default();
+    for key in 0..MAX_METADATA_VALUES {
+        full.set(key.to_string(), "value").unwrap();
+    }
+
+    assert!(full.set("new", "value").is_err());
+    assert!(full.set("0", "replaced").is_ok());
+    assert_eq!(HeaderMetadata::decode(&full.encode()).unwrap(), full);
+
+    assert_eq!(
+        metadata
+            .remove("key")
+            .and_then(|value| value.as_str().map(String::from)),
+        Some("value".to_string())
+    );
+    assert_eq!(metadata.values().count(), 1);
+}
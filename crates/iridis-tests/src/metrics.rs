@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::{
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        },
+        *,
+    },
+    *,
+};
+
+/// Closes its input right away
+#[derive(Node)]
+pub struct Closed {
+    #[input("in")]
+    pub input: Input<String>,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Closed {
+    async fn start(self: Box<Self>) -> Result<()> {
+        drop(self.input);
+
+        Ok(())
+    }
+}
+
+async fn spawn_transports() -> (DataflowHandle, NodeID, NodeID) {
+    let layout = DataflowLayout::empty();
+
+    let (source, (_, output)) = layout
+        .node("source", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (sink, (input, _)) = layout
+        .node("sink", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(source.clone(), serde_yml::from_str("")?);
+            loader.load::<Transport>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    (handle, source, sink)
+}
+
+async fn send(handle: &DataflowHandle, input: InputID, messages: &[&str]) -> MessageSender {
+    let sender = handle.input_sender(input).await.unwrap();
+    for data in messages {
+        sender
+            .send(DataflowMessage {
+                header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+                data: data.to_string().try_into_arrow().unwrap().into_data(),
+            })
+            .await
+            .unwrap();
+    }
+
+    // Let the messages go through both nodes
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    sender
+}
+
+#[tokio::test]
+async fn collect_metrics() {
+    let (handle, source, sink) = spawn_transports().await;
+    let sender = send(&handle, source.input("in"), &["a", "b", "c", "d"]).await;
+
+    let metrics = handle.metrics().await;
+
+    let [connection] = metrics.connections.as_slice() else {
+        panic!("Expected one connection, got {:?}", metrics.connections);
+    };
+
+    assert_eq!(connection.label, "source/out -> sink/in");
+    assert_eq!(connection.sent, 4);
+    assert_eq!(connection.errors, 0);
+    assert_eq!(connection.received, 4);
+    assert!(connection.bytes > 0);
+    assert_eq!(connection.blocked.count, 4);
+    assert_eq!(connection.latency.count, 4);
+    assert_eq!(connection.queued, 0);
+    assert!(connection.capacity > 0);
+
+    let source = &metrics.nodes[&source];
+    assert_eq!((source.received, source.sent), (4, 4));
+    assert_eq!(source.sent_bytes, connection.bytes);
+    assert_eq!(source.errors, 0);
+
+    let sink = &metrics.nodes[&sink];
+    assert_eq!((sink.received, sink.sent), (4, 4));
+    assert_eq!(sink.latency.count, 4);
+    assert!(sink.latency.quantile(0.5) <= sink.latency.max.max(Duration::from_micros(1)));
+
+    drop(sender);
+    handle.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn serve_prometheus_metrics() {
+    let (handle, source, _) = spawn_transports().await;
+    let sender = send(&handle, source.input("in"), &["a", "b"]).await;
+
+    let server = handle
+        .serve_metrics("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+
+    let mut stream = TcpStream::connect(server.address).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("# TYPE iridis_port_messages_total counter\n"));
+    assert!(response.contains(
+        "iridis_port_messages_total{node=\"sink\",port=\"out\",direction=\"output\"} 2\n"
+    ));
+    assert!(response.contains(
+        "iridis_connection_sent_total{output_node=\"source\",output=\"out\",input_node=\"sink\",input=\"in\"} 2\n"
+    ));
+    assert!(response.contains("iridis_connection_latency_seconds_bucket{output_node=\"source\",output=\"out\",input_node=\"sink\",input=\"in\",le=\"+Inf\"} 2\n"));
+    assert_eq!(
+        response.split_once("\r\n\r\n").unwrap().1,
+        handle.prometheus().await
+    );
+
+    // Only '/metrics' is served
+    let mut stream = TcpStream::connect(server.address).await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    let address = server.address;
+    server.stop();
+    tokio::task::yield_now().await;
+    assert!(TcpStream::connect(address).await.is_err());
+
+    drop(sender);
+    handle.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn count_failed_sends() {
+    let layout = DataflowLayout::empty();
+
+    let (source, (_, output)) = layout
+        .node("source", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (sink, input) = layout
+        .node("sink", async |builder: &mut NodeLayout| builder.input("in"))
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(source.clone(), serde_yml::from_str("")?);
+            loader.load::<Closed>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    // Let the sink end before anything is sent to it
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let sender = send(&handle, source.input("in"), &["a"]).await;
+
+    let metrics = handle.metrics().await;
+
+    let [connection] = metrics.connections.as_slice() else {
+        panic!("Expected one connection, got {:?}", metrics.connections);
+    };
+
+    assert_eq!((connection.sent, connection.bytes), (0, 0));
+    assert_eq!(connection.errors, 1);
+    assert_eq!(metrics.nodes[&source].errors, 1);
+
+    drop(sender);
+    assert!(handle.wait().await.is_err());
+}
+
+#[test]
+fn histogram_quantiles() {
+    let histogram = Histogram::default();
+
+    for micros in [1, 3, 3, 100, 5000] {
+        histogram.record(Duration::from_micros(micros));
+    }
+
+    let snapshot = histogram.snapshot();
+
+    assert_eq!(snapshot.count, 5);
+    assert_eq!(snapshot.max, Duration::from_micros(5000));
+    assert_eq!(snapshot.mean(), Duration::from_nanos(1_021_400));
+
+    // Upper bounds of the buckets
+    assert_eq!(snapshot.quantile(0.2), Duration::from_micros(1));
+    assert_eq!(snapshot.quantile(0.6), Duration::from_micros(4));
+    assert_eq!(snapshot.quantile(0.8), Duration::from_micros(128));
+    assert_eq!(snapshot.quantile(1.0), Duration::from_micros(5000));
+
+    let mut merged = snapshot.clone();
+    merged.merge(&snapshot);
+    assert_eq!(merged.count, 10);
+    assert_eq!(merged.quantile(0.6), Duration::from_micros(4));
+
+    // Counts above u32::MAX
+    let large = HistogramSnapshot {
+        count: 1 << 32,
+        sum: Duration::from_secs(1 << 32),
+        ..Default::default()
+    };
+    assert_eq!(large.mean(), Duration::from_secs(1));
+}
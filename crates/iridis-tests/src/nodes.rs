@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use iridis::prelude::{thirdparty::*, *};
+
+use crate::harness::*;
+
+/// Fetches the value of every key it receives, and tells how many it fetched
+#[derive(Node)]
+pub struct Lookup {
+    #[input("key")]
+    pub key: Input<String>,
+    #[output("value")]
+    pub value: Output<String>,
+    #[query("fetch")]
+    pub fetch: Query<String, String>,
+    #[queryable("count")]
+    pub count: Queryable<u8, u64>,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Lookup {
+    async fn start(mut self: Box<Self>) -> Result<()> {
+        let mut fetched = 0;
+
+        loop {
+            let count = fetched;
+
+            tokio::select! {
+                key = self.key.recv() => {
+                    let Ok(key) = key else {
+                        return Ok(());
+                    };
+
+                    let value = self.fetch.query(key.data).await?;
+                    self.value.send(value.data).await?;
+
+                    fetched += 1;
+                }
+                answered = self.count.on_query(async |_| Ok(count)) => answered?,
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn drive_node_through_harness() {
+    let mut harness = NodeHarness::<Lookup>::new(serde_yml::from_str("").unwrap())
+        .await
+        .unwrap()
+        .timeout(Duration::from_secs(1));
+
+    let key = harness.input::<String>("key").await.unwrap();
+    let mut value = harness.output::<String>("value").await.unwrap();
+    let mut fetch = harness.query::<String, String>("fetch").await.unwrap();
+    let mut count = harness.queryable::<u8, u64>("count").await.unwrap();
+
+    key.send("a".to_string()).await.unwrap();
+    fetch
+        .answer(async |key| Ok(format!("value of {}", key.data)))
+        .await
+        .unwrap();
+
+    let message = value.recv().await.unwrap();
+    assert_eq!(message.data, "value of a");
+    assert_eq!(message.header.source.0, harness.node.uuid);
+
+    assert_eq!(count.query(0).await.unwrap().data, 1);
+
+    // The mock queryable answers in the background from now on
+    let _fetch = fetch.serve(|key| Ok(key.data.to_uppercase()));
+
+    key.send("b".to_string()).await.unwrap();
+    assert_eq!(value.recv().await.unwrap().data, "B");
+
+    value.expect_none(Duration::from_millis(50)).await.unwrap();
+
+    drop(key);
+    harness.finish().await.unwrap();
+
+    let report = format!("{:?}", value.recv().await.unwrap_err());
+    assert!(report.contains("value"), "{}", report);
+}
+
+#[tokio::test]
+async fn time_out_waiting_for_node() {
+    let mut harness = NodeHarness::<Timer>::new(serde_yml::from_str("frequency: 1.0").unwrap())
+        .await
+        .unwrap()
+        .timeout(Duration::from_millis(100));
+
+    let mut output = harness.output::<String>("out").await.unwrap();
+
+    assert_eq!(output.recv().await.unwrap().data, "tick");
+
+    let report = format!("{:?}", output.recv().await.unwrap_err());
+    assert!(report.contains("Timed out"), "{}", report);
+}
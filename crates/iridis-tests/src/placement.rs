@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use iridis::prelude::{thirdparty::*, *};
+
+/// The thread each `Threaded` node started on, by label
+static THREADS: LazyLock<Mutex<HashMap<String, Option<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Node)]
+pub struct Threaded {
+    label: String,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Threaded {
+    async fn new(
+        _: Inputs,
+        _: Outputs,
+        _: Queries,
+        _: Queryables,
+        configuration: serde_yml::Value,
+    ) -> Result<Self> {
+        Ok(Self {
+            label: serde_yml::from_value(configuration["label"].clone())?,
+        })
+    }
+
+    async fn start(self: Box<Self>) -> Result<()> {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        THREADS.lock().unwrap().insert(
+            self.label.clone(),
+            std::thread::current().name().map(String::from),
+        );
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn place_nodes_on_executors() {
+    let layout = DataflowLayout::empty();
+
+    let (dedicated, _) = layout
+        .node("dedicated", async |builder: &mut NodeLayout| {
+            builder.place(Placement::Dedicated { core: None })
+        })
+        .await;
+
+    let (pooled, _) = layout.node("pooled", async |_| {}).await;
+    let (blocking, _) = layout.node("blocking", async |_| {}).await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .with_pool("control", 1, [])
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.place(&pooled, Placement::Pool("control".to_string()));
+            loader.place(&blocking, Placement::Blocking);
+
+            loader.load::<Threaded>(dedicated, serde_yml::from_str("label: dedicated")?);
+            loader.load::<Threaded>(pooled, serde_yml::from_str("label: pooled")?);
+            loader.load::<Threaded>(blocking, serde_yml::from_str("label: blocking")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    handle.wait().await.unwrap();
+
+    let threads = THREADS.lock().unwrap().clone();
+
+    assert_eq!(threads["dedicated"].as_deref(), Some("iridis-dedicated"));
+    assert_eq!(threads["pooled"].as_deref(), Some("iridis-control"));
+    assert_ne!(
+        threads["blocking"],
+        std::thread::current().name().map(String::from)
+    );
+}
+
+#[tokio::test]
+async fn reject_undeclared_pool() {
+    let layout = DataflowLayout::empty();
+
+    let (node, _) = layout
+        .node("node", async |builder: &mut NodeLayout| {
+            builder.place(Placement::Pool("missing".to_string()))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let result = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Threaded>(node, serde_yml::from_str("label: missing")?);
+
+            Ok(())
+        })
+        .await;
+
+    let report = format!("{:?}", result.err().unwrap());
+    assert!(report.contains("Pool 'missing' is not declared on the runtime"));
+}
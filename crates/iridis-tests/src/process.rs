@@ -0,0 +1,287 @@
+use std::{path::PathBuf, time::Duration};
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::*,
+    *,
+};
+
+use crate::record::message;
+
+/// The marker of the node process that already crashed, shared by the processes of one test run
+fn crash_marker() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "iridis-crash-{}",
+        std::os::unix::process::parent_id()
+    ))
+}
+
+/// Entry point of the node processes launched by the tests, it does nothing in a regular run.
+#[tokio::test]
+async fn node_process() {
+    serve_node_process().await.unwrap();
+}
+
+/// Entry point of a node process that aborts shortly after it's ready, the first time only.
+#[tokio::test]
+async fn crashing_node_process() {
+    if std::env::var(NODE_PROCESS_ENV).is_ok() && std::fs::File::create_new(crash_marker()).is_ok()
+    {
+        std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(200));
+            std::process::abort();
+        });
+    }
+
+    serve_node_process().await.unwrap();
+}
+
+/// The file where a node process writes its pid, by the pid of the test run
+fn pid_marker(parent: u32) -> PathBuf {
+    std::env::temp_dir().join(format!("iridis-pid-{}", parent))
+}
+
+/// Entry point of a node process that tells its pid, so the test can signal it.
+#[tokio::test]
+async fn signaled_node_process() {
+    if std::env::var(NODE_PROCESS_ENV).is_ok() {
+        std::fs::write(
+            pid_marker(std::os::unix::process::parent_id()),
+            std::process::id().to_string(),
+        )
+        .unwrap();
+    }
+
+    serve_node_process().await.unwrap();
+}
+
+fn signal(pid: &str, signal: &str) {
+    assert!(
+        std::process::Command::new("kill")
+            .args([signal, pid])
+            .status()
+            .unwrap()
+            .success()
+    );
+}
+
+fn process(entry: &str) -> NodeProcess {
+    NodeProcess::current_exe().unwrap().args([
+        format!("process::{}", entry),
+        "--exact".into(),
+        "--quiet".into(),
+    ])
+}
+
+async fn spawn_transport(process: NodeProcess) -> (DataflowHandle, NodeID, InputID, OutputID) {
+    let layout = DataflowLayout::empty();
+
+    let (transport, _) = layout
+        .node("transport", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let node = transport.clone();
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async move |loader: &mut Loader| {
+            loader.load_url_process(
+                Url::parse("builtin:///transport")?,
+                node,
+                serde_yml::from_str("")?,
+                process,
+            );
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let (input, output) = (transport.input("in"), transport.output("out"));
+
+    (handle, transport, input, output)
+}
+
+#[tokio::test]
+async fn run_node_in_process() {
+    let (handle, transport, input, output) = spawn_transport(process("node_process")).await;
+
+    let mut receiver = handle.subscribe(output).await.unwrap();
+    let sender = handle.input_sender(input).await.unwrap();
+
+    sender.send(message(&handle, "fixture")).await.unwrap();
+
+    let message: TypedDataflowMessage<String> = receiver.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message.data, "fixture");
+    assert_eq!(message.header.source.0, transport.uuid);
+
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    assert!(receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn restart_crashed_process() {
+    let marker = std::env::temp_dir().join(format!("iridis-crash-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+
+    let (handle, transport, input, output) =
+        spawn_transport(process("crashing_node_process").restarts(1)).await;
+
+    let mut receiver = handle.subscribe(output).await.unwrap();
+    let sender = handle.input_sender(input).await.unwrap();
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    sender.send(message(&handle, "restarted")).await.unwrap();
+
+    let message: TypedDataflowMessage<String> = receiver.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message.data, "restarted");
+    assert_eq!(handle.status(&transport).await, Some(NodeStatus::Running));
+
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    let _ = std::fs::remove_file(marker);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn share_arena_buffers() {
+    use iridis::prelude::iridis_node::prelude::thirdparty::arrow_array::UInt8Array;
+
+    let arena = SharedArena::new(4 * SHARED_PAGE_SIZE).unwrap();
+
+    let mut buffer = arena.alloc(1024).unwrap();
+    buffer.fill(7);
+
+    let array = UInt8Array::new(buffer.freeze().into(), None);
+    let ptr = array.values().as_ptr();
+
+    let (handle, _, input, output) = spawn_transport(process("node_process")).await;
+
+    let mut receiver = handle.subscribe(output).await.unwrap();
+    let sender = handle.input_sender(input).await.unwrap();
+
+    sender
+        .send(DataflowMessage {
+            header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+            data: array.into_data(),
+        })
+        .await
+        .unwrap();
+
+    // The data went to the process and back without being copied
+    let message = receiver.recv().await.unwrap();
+    assert_eq!(message.data.buffers()[0].as_ptr(), ptr);
+    assert_eq!(message.data.buffers()[0].as_slice(), [7; 1024]);
+
+    drop(message);
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while arena.used() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        arena.alloc(4 * SHARED_PAGE_SIZE).unwrap().len(),
+        4 * SHARED_PAGE_SIZE
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn refuse_foreign_shared_memory() {
+    use iridis::prelude::iridis_node::prelude::thirdparty::arrow_buffer::alloc::Allocation;
+
+    let arena = SharedArena::new(SHARED_PAGE_SIZE).unwrap();
+    let buffer = arena.alloc(16).unwrap().freeze();
+
+    let shared = SharedBuffer::locate(&buffer).unwrap();
+    let lender: std::sync::Arc<dyn Allocation> = std::sync::Arc::new(());
+    let peer = std::os::unix::process::parent_id();
+
+    assert!(shared.clone().into_buffer(peer, &lender).is_ok());
+
+    // Only the file descriptors of the peer are opened
+    for path in [
+        PathBuf::from("/etc/hostname"),
+        PathBuf::from(format!("/proc/{}/environ", peer)),
+        PathBuf::from(format!("/proc/{}/fd/../environ", peer)),
+    ] {
+        let foreign = SharedBuffer {
+            segment: path,
+            offset: 0,
+            len: 1,
+        };
+
+        assert!(foreign.into_buffer(peer, &lender).is_err());
+    }
+
+    let overflowing = SharedBuffer {
+        offset: usize::MAX,
+        len: 2,
+        ..shared
+    };
+
+    assert!(overflowing.into_buffer(peer, &lender).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn release_buffers_of_killed_process() {
+    use iridis::prelude::iridis_node::prelude::thirdparty::arrow_array::UInt8Array;
+
+    let marker = pid_marker(std::process::id());
+    let _ = std::fs::remove_file(&marker);
+
+    let arena = SharedArena::new(4 * SHARED_PAGE_SIZE).unwrap();
+
+    let mut buffer = arena.alloc(1024).unwrap();
+    buffer.fill(7);
+
+    let array = UInt8Array::new(buffer.freeze().into(), None);
+
+    let (handle, _, input, _) = spawn_transport(process("signaled_node_process")).await;
+    let sender = handle.input_sender(input).await.unwrap();
+
+    // The process holds the buffer without ever returning it
+    let pid = std::fs::read_to_string(&marker).unwrap();
+    signal(&pid, "-STOP");
+
+    sender
+        .send(DataflowMessage {
+            header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+            data: array.into_data(),
+        })
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(arena.used(), SHARED_PAGE_SIZE);
+
+    signal(&pid, "-KILL");
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while arena.used() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    drop(sender);
+    assert!(handle.wait().await.is_err());
+
+    let _ = std::fs::remove_file(marker);
+}
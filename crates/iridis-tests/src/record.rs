@@ -0,0 +1,144 @@
+use std::{path::PathBuf, time::Duration};
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::*,
+    *,
+};
+
+fn recording(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("iridis-{}-{}.arrows", name, std::process::id()))
+}
+
+async fn runtime() -> Runtime {
+    Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+}
+
+/// A message sent by the host
+pub(crate) fn message(handle: &DataflowHandle, data: &str) -> DataflowMessage {
+    DataflowMessage {
+        header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+        data: data.to_string().try_into_arrow().unwrap().into_data(),
+    }
+}
+
+#[tokio::test]
+async fn record_and_replay() {
+    let path = recording("replay");
+
+    // 'source' feeds 'sink', the connection between them is recorded
+    let layout = DataflowLayout::empty();
+
+    let (source, (_, output)) = layout
+        .node("source", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (sink, (input, _)) = layout
+        .node("sink", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(source.clone(), serde_yml::from_str("")?);
+            loader.load::<Transport>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let recording = handle
+        .record(&path, [(source.output("out"), sink.input("in"))])
+        .await
+        .unwrap();
+
+    let sender = handle.input_sender(source.input("in")).await.unwrap();
+    for data in ["a", "b", "c"] {
+        sender.send(message(&handle, data)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    assert_eq!(recording.wait().await.unwrap(), 3);
+
+    // The recording is a plain Arrow IPC stream, with the layout in its metadata
+    let reader =
+        arrow_ipc::reader::StreamReader::try_new(std::fs::File::open(&path).unwrap(), None)
+            .unwrap();
+
+    let schema = reader.schema();
+    assert!(schema.metadata()["iridis.layout"].contains("sink"));
+    assert!(schema.field_with_name("data").is_ok());
+
+    let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+    assert_eq!(rows, 3);
+
+    // Replay into 'sink' alone, built again with other uuids
+    let layout = DataflowLayout::empty();
+
+    let (sink, output) = layout
+        .node("sink", async |builder: &mut NodeLayout| {
+            builder.input("in");
+            builder.output("out")
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(output).await.unwrap();
+
+    let replay = Replay::open(&path).await.unwrap().speed(2.0);
+    assert!(replay.snapshot.inputs.values().eq(["sink/in"]));
+
+    let start = std::time::Instant::now();
+    assert_eq!(replay.run(&handle).await.unwrap(), 3);
+
+    // 100ms between the first and the last message, replayed twice as fast
+    assert!(start.elapsed() >= Duration::from_millis(40));
+
+    for data in ["a", "b", "c"] {
+        let message: TypedDataflowMessage<String> =
+            receiver.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(message.data, data);
+    }
+
+    handle.wait().await.unwrap();
+    assert!(receiver.recv().await.is_none());
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn reject_invalid_recording() {
+    let path = recording("invalid");
+    std::fs::write(&path, b"").unwrap();
+
+    let report = format!("{:?}", Replay::open(&path).await.err().unwrap());
+    assert!(report.contains("is not a recording"), "{}", report);
+
+    let _ = std::fs::remove_file(path);
+}
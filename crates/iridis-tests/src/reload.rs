@@ -0,0 +1,203 @@
+use std::{path::PathBuf, time::Duration};
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::{tokio::task::JoinHandle, *},
+    *,
+};
+
+/// Prefixes every string it receives until it's asked to stop, the prefix is its configuration.
+/// It takes its time, so messages are still queued when it's reloaded
+#[derive(Node)]
+pub struct Prefix {
+    #[input("in")]
+    pub input: Input<String>,
+    #[output("out")]
+    pub output: Output<String>,
+    #[config(flatten)]
+    pub prefix: String,
+
+    events: Option<LifecycleEvents>,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Prefix {
+    async fn start(mut self: Box<Self>) -> Result<()> {
+        let mut events = self.events.take().ok_or_eyre("Missing lifecycle events")?;
+
+        loop {
+            tokio::select! {
+                message = self.input.recv() => {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+
+                    self.output
+                        .send(format!("{}{}", self.prefix, message?.data))
+                        .await?
+                }
+                event = events.recv() => match event {
+                    Some(LifecycleEvent::Stop) | None => return Ok(()),
+                    Some(_) => {}
+                },
+            }
+        }
+    }
+
+    async fn on_ready(&mut self, events: LifecycleEvents) -> Result<()> {
+        self.events = Some(events);
+
+        Ok(())
+    }
+}
+
+/// Loads a `Prefix` node from a `.prefix` file that contains its prefix, fails if it's empty
+struct PrefixFileExt {}
+
+impl FileExtPlugin for PrefixFileExt {
+    fn new() -> JoinHandle<Result<Box<dyn FileExtPlugin>>> {
+        tokio::spawn(async { Ok(Box::new(PrefixFileExt {}) as Box<dyn FileExtPlugin>) })
+    }
+
+    fn target(&self) -> Vec<String> {
+        vec!["prefix".to_string()]
+    }
+
+    fn load(
+        &self,
+        path: PathBuf,
+        inputs: Inputs,
+        outputs: Outputs,
+        queries: Queries,
+        queryables: Queryables,
+        _: serde_yml::Value,
+    ) -> JoinHandle<Result<RuntimeNode>> {
+        tokio::spawn(async move {
+            let prefix = tokio::fs::read_to_string(&path).await?;
+
+            if prefix.is_empty() {
+                eyre::bail!("Empty prefix in {:?}", path);
+            }
+
+            let node = Prefix::new(inputs, outputs, queries, queryables, prefix.into()).await??;
+
+            Ok(RuntimeNode::StaticallyLinked(node))
+        })
+    }
+}
+
+/// Write the file, with a modification time different from the previous one
+async fn rewrite(path: &PathBuf, content: &str) {
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    tokio::fs::write(path, content).await.unwrap();
+}
+
+/// Receive the next string, `None` if nothing is received for a while
+async fn recv(receiver: &mut MessageReceiver) -> Option<String> {
+    let message = tokio::time::timeout(Duration::from_millis(200), receiver.recv())
+        .await
+        .ok()??;
+
+    TypedDataflowMessage::<String>::try_from(message)
+        .ok()
+        .map(|message| message.data)
+}
+
+#[tokio::test]
+async fn reload_node_with_the_same_channels() {
+    let path = std::env::temp_dir().join(format!("iridis-{}.prefix", Uuid::new_v4()));
+    rewrite(&path, "a:").await;
+
+    let layout = DataflowLayout::empty();
+
+    let (prefix, (input, output)) = layout
+        .node("prefix", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let (url, node) = (Url::from_file_path(&path).unwrap(), prefix.clone());
+    let handle = Runtime::new(
+        async |file_ext: &mut FileExtLoader, _: &mut UrlSchemeLoader| {
+            file_ext.load_statically_linked_plugin::<PrefixFileExt>();
+
+            Ok(())
+        },
+    )
+    .await
+    .unwrap()
+    .spawn(layout, async move |loader: &mut Loader| {
+        loader.load_url_reloadable(url, node, serde_yml::from_str("")?);
+
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    let mut receiver = handle.subscribe(output).await.unwrap();
+    let sender = handle.input_sender(input).await.unwrap();
+
+    let message = |data: &str| DataflowMessage {
+        header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+        data: data.to_string().try_into_arrow().unwrap().into_data(),
+    };
+
+    sender.send(message("x")).await.unwrap();
+    assert_eq!(recv(&mut receiver).await.as_deref(), Some("a:x"));
+
+    // The new library can't be loaded, the current instance keeps running
+    rewrite(&path, "").await;
+    tokio::time::sleep(RELOAD_POLL_INTERVAL * 3).await;
+
+    sender.send(message("y")).await.unwrap();
+    assert_eq!(recv(&mut receiver).await.as_deref(), Some("a:y"));
+
+    rewrite(&path, "b:").await;
+
+    // Every message sent while the instances are switched is received once, in order
+    let mut received = Vec::new();
+    tokio::time::timeout(Duration::from_secs(10), async {
+        let mut sent = 0;
+
+        while !received
+            .last()
+            .is_some_and(|data: &String| data.starts_with("b:"))
+        {
+            sender.send(message(&sent.to_string())).await.unwrap();
+            sent += 1;
+
+            tokio::time::sleep(Duration::from_millis(1)).await;
+
+            while let Ok(message) = receiver.try_recv() {
+                received.push(
+                    TypedDataflowMessage::<String>::try_from(message)
+                        .unwrap()
+                        .data,
+                );
+            }
+        }
+
+        while received.len() < sent {
+            received.push(recv(&mut receiver).await.unwrap());
+        }
+    })
+    .await
+    .unwrap();
+
+    let numbers = received
+        .iter()
+        .map(|data| data[2..].parse::<usize>().unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(numbers, (0..received.len()).collect::<Vec<_>>());
+
+    sender.send(message("w")).await.unwrap();
+    assert_eq!(recv(&mut receiver).await.as_deref(), Some("b:w"));
+    assert_eq!(handle.status(&prefix).await, Some(NodeStatus::Running));
+
+    handle.stop();
+    handle.wait().await.unwrap();
+
+    let _ = std::fs::remove_file(path);
+}
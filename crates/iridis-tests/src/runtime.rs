@@ -0,0 +1,680 @@
+use std::time::Duration;
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::*,
+    *,
+};
+
+#[derive(Node)]
+pub struct Failing {}
+
+#[node(runtime = "default_runtime")]
+impl Node for Failing {
+    async fn new(
+        _: Inputs,
+        _: Outputs,
+        _: Queries,
+        _: Queryables,
+        _: serde_yml::Value,
+    ) -> Result<Self> {
+        Ok(Self {})
+    }
+
+    async fn start(self: Box<Self>) -> Result<()> {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        Err(eyre::eyre!("Failing node"))
+    }
+}
+
+mod finishing {
+    use iridis::prelude::{thirdparty::*, *};
+
+    #[derive(Node)]
+    pub struct Finishing {}
+
+    #[node(runtime = "default_runtime")]
+    impl Node for Finishing {
+        async fn new(
+            _: Inputs,
+            _: Outputs,
+            _: Queries,
+            _: Queryables,
+            _: serde_yml::Value,
+        ) -> Result<Self> {
+            Ok(Self {})
+        }
+
+        async fn start(self: Box<Self>) -> Result<()> {
+            Ok(())
+        }
+    }
+}
+
+use finishing::Finishing;
+
+async fn runtime() -> Runtime {
+    Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stop_running_dataflow() {
+    let layout = DataflowLayout::empty();
+
+    let (timer, output) = layout
+        .node("timer", async |builder: &mut NodeLayout| builder.output("out"))
+        .await;
+
+    let (printer, input) = layout
+        .node("printer", async |builder: &mut NodeLayout| builder.input("in"))
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Timer>(timer.clone(), serde_yml::from_str("frequency: 100.0")?);
+            loader.load::<Printer>(printer.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(handle.status(&timer).await, Some(NodeStatus::Running));
+    assert_eq!(handle.status(&printer).await, Some(NodeStatus::Running));
+
+    let mut events = handle.events();
+    handle.stop();
+
+    let mut stopped = Vec::new();
+    while stopped.len() < 2 {
+        if let RuntimeEvent::NodeStopped(node) = events.recv().await.unwrap() {
+            stopped.push(node);
+        }
+    }
+
+    assert!(stopped.contains(&timer) && stopped.contains(&printer));
+    assert_eq!(handle.status(&timer).await, Some(NodeStatus::Stopped));
+
+    handle.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn report_failing_node() {
+    let layout = DataflowLayout::empty();
+
+    let (failing, _) = layout.node("failing", async |_| {}).await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Failing>(failing.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut events = handle.events();
+
+    loop {
+        if let RuntimeEvent::NodeFailed(node, _) = events.recv().await.unwrap() {
+            assert_eq!(node, failing);
+            break;
+        }
+    }
+
+    assert!(matches!(
+        handle.status(&failing).await,
+        Some(NodeStatus::Failed(_))
+    ));
+
+    assert!(handle.wait().await.is_err());
+}
+
+#[tokio::test]
+async fn stop_while_waiting_and_report_every_failure() {
+    let layout = DataflowLayout::empty();
+
+    let (first, _) = layout.node("first", async |_| {}).await;
+    let (second, _) = layout.node("second", async |_| {}).await;
+    let (timer, _) = layout
+        .node("timer", async |builder: &mut NodeLayout| {
+            builder.output("out")
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Failing>(first.clone(), serde_yml::from_str("")?);
+            loader.load::<Failing>(second.clone(), serde_yml::from_str("")?);
+            loader.load::<Timer>(timer.clone(), serde_yml::from_str("frequency: 100.0")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let stopper = handle.stopper();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        stopper.stop();
+    });
+
+    let report = tokio::time::timeout(Duration::from_secs(5), handle.wait())
+        .await
+        .unwrap()
+        .unwrap_err();
+
+    let report = format!("{:?}", report);
+    assert!(report.contains("Node 'first'"), "{}", report);
+    assert!(report.contains("Node 'second'"), "{}", report);
+}
+
+#[tokio::test]
+async fn report_finished_node() {
+    let layout = DataflowLayout::empty();
+
+    let (finishing, _) = layout.node("finishing", async |_| {}).await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Finishing>(finishing.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    // The first subscriber receives the events emitted while the dataflow was spawned
+    let mut events = handle.events();
+
+    assert_eq!(
+        events.recv().await.unwrap(),
+        RuntimeEvent::NodeStarted(finishing.clone())
+    );
+    assert_eq!(
+        events.recv().await.unwrap(),
+        RuntimeEvent::NodeFinished(finishing.clone())
+    );
+
+    // Stopping the dataflow afterwards doesn't change the status of the node
+    handle.stop();
+
+    assert_eq!(handle.status(&finishing).await, Some(NodeStatus::Finished));
+
+    handle.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn inject_and_observe_from_host() {
+    let layout = DataflowLayout::empty();
+
+    let (operator, (op_in, op_out)) = layout
+        .node("operator", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(operator, serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(op_out).await.unwrap();
+    let sender = handle.input_sender(op_in).await.unwrap();
+
+    sender
+        .send(DataflowMessage {
+            header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+            data: "fixture".to_string().try_into_arrow().unwrap().into_data(),
+        })
+        .await
+        .unwrap();
+
+    let message: TypedDataflowMessage<String> = receiver.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message.data, "fixture");
+
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    assert!(receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn tap_connection_without_backpressure() {
+    let layout = DataflowLayout::empty();
+
+    let (timer, output) = layout
+        .node("timer", async |builder: &mut NodeLayout| builder.output("out"))
+        .await;
+
+    let (operator, op_in) = layout
+        .node("operator", async |builder: &mut NodeLayout| {
+            builder.output("out");
+            builder.input("in")
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output.clone(), op_in.clone()))
+        .await
+        .unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Timer>(timer, serde_yml::from_str("frequency: 1000.0")?);
+            loader.load::<Transport>(operator, serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    assert!(
+        handle
+            .tap(output.clone(), op_in.clone(), TapOverflow::Drop, 0)
+            .await
+            .is_err()
+    );
+
+    let mut tap = handle
+        .tap(output.clone(), op_in, TapOverflow::Drop, 1)
+        .await
+        .unwrap();
+
+    // The tap is never read, but the connection must keep flowing
+    let mut receiver = handle.subscribe(output).await.unwrap();
+    for _ in 0..10 {
+        receiver.recv().await.unwrap();
+    }
+
+    assert!(tap.rx.recv().await.is_some());
+
+    handle.untap(&tap).await;
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while tap.rx.recv().await.is_some() {}
+    })
+    .await
+    .unwrap();
+
+    handle.stop();
+    handle.wait().await.unwrap();
+}
+
+#[tokio::test]
+async fn replace_node_at_runtime() {
+    let layout = DataflowLayout::empty();
+
+    let (timer, output) = layout
+        .node("timer", async |builder: &mut NodeLayout| {
+            builder.output("out")
+        })
+        .await;
+
+    let (old, old_in) = layout
+        .node("old", async |builder: &mut NodeLayout| {
+            builder.output("out");
+            builder.input("in")
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output.clone(), old_in))
+        .await
+        .unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Timer>(timer.clone(), serde_yml::from_str("frequency: 100.0")?);
+            loader.load::<Transport>(old.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let nodes = DataflowLayout::empty();
+
+    let (new, (new_in, new_out)) = nodes
+        .node("new", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    handle
+        .reconfigure(
+            nodes,
+            async |flows: &mut FlowChanges| {
+                flows.remove_node(old.clone());
+                flows.connect(output, new_in)
+            },
+            async |loader: &mut Loader| {
+                loader.load::<Transport>(new.clone(), serde_yml::from_str("")?);
+
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(handle.status(&old).await, None);
+    assert_eq!(handle.status(&new).await, Some(NodeStatus::Running));
+    assert!(!handle.layout().await.debug.nodes.contains_key(&old.uuid));
+
+    let mut receiver = handle.subscribe(new_out).await.unwrap();
+    let message: TypedDataflowMessage<String> = receiver.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message.data, "tick");
+
+    // The timer must still be running: its connection to the removed node is gone
+    assert_eq!(handle.status(&timer).await, Some(NodeStatus::Running));
+
+    handle.stop();
+    handle.wait().await.unwrap();
+}
+
+/// Loads `Transport` through the C ABI, like a node exported by a dylib
+pub struct AbiTransport {}
+
+impl Node for AbiTransport {
+    fn new(
+        inputs: Inputs,
+        outputs: Outputs,
+        queries: Queries,
+        queryables: Queryables,
+        configuration: serde_yml::Value,
+    ) -> tokio::task::JoinHandle<Result<Box<dyn Node>>> {
+        tokio::spawn(async move {
+            let node = AbiLinkedNode::new(
+                AbiNode::new::<Transport>(),
+                inputs,
+                outputs,
+                queries,
+                queryables,
+                configuration,
+            )
+            .await?;
+
+            Ok(Box::new(node) as Box<dyn Node>)
+        })
+    }
+
+    fn start(self: Box<Self>) -> tokio::task::JoinHandle<Result<()>> {
+        tokio::spawn(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn run_node_through_c_abi() {
+    let layout = DataflowLayout::empty();
+
+    let (operator, (op_in, op_out)) = layout
+        .node("operator", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<AbiTransport>(operator, serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let mut receiver = handle.subscribe(op_out).await.unwrap();
+    let sender = handle.input_sender(op_in).await.unwrap();
+
+    for fixture in ["first", "second"] {
+        sender
+            .send(DataflowMessage {
+                header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+                data: fixture.to_string().try_into_arrow().unwrap().into_data(),
+            })
+            .await
+            .unwrap();
+
+        let message: TypedDataflowMessage<String> =
+            receiver.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(message.data, fixture);
+    }
+
+    drop(sender);
+    handle.wait().await.unwrap();
+
+    assert!(receiver.recv().await.is_none());
+}
+
+/// Loads `Transport` through the C ABI, but returns an unknown status from `start`
+pub struct InvalidAbiStatus {}
+
+unsafe extern "C" fn invalid_start(_: *mut std::ffi::c_void) -> AbiStatus {
+    AbiStatus(7)
+}
+
+impl Node for InvalidAbiStatus {
+    fn new(
+        inputs: Inputs,
+        outputs: Outputs,
+        queries: Queries,
+        queryables: Queryables,
+        configuration: serde_yml::Value,
+    ) -> tokio::task::JoinHandle<Result<Box<dyn Node>>> {
+        tokio::spawn(async move {
+            let abi = AbiNode {
+                start: invalid_start,
+                ..AbiNode::new::<Transport>()
+            };
+
+            let node = AbiLinkedNode::new(abi, inputs, outputs, queries, queryables, configuration)
+                .await?;
+
+            Ok(Box::new(node) as Box<dyn Node>)
+        })
+    }
+
+    fn start(self: Box<Self>) -> tokio::task::JoinHandle<Result<()>> {
+        tokio::spawn(async { Ok(()) })
+    }
+}
+
+#[tokio::test]
+async fn refuse_invalid_abi_status() {
+    let layout = DataflowLayout::empty();
+
+    let (operator, _) = layout
+        .node("operator", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let handle = runtime()
+        .await
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<InvalidAbiStatus>(operator, serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let report = format!("{:?}", handle.wait().await.unwrap_err());
+    assert!(report.contains("Invalid status 7"), "{}", report);
+}
+
+/// Build a library that only exports the metadata of a node, as if it had been built with
+/// another compiler
+fn incompatible_library(name: &str) -> std::path::PathBuf {
+    let current = Metadata::current(NODE_INTERFACE);
+
+    let source = format!(
+        r#"
+#[repr(C)]
+pub struct AbiStr {{
+    ptr: *const u8,
+    len: usize,
+}}
+
+const fn abi_str(value: &str) -> AbiStr {{
+    AbiStr {{
+        ptr: value.as_ptr(),
+        len: value.len(),
+    }}
+}}
+
+#[repr(C)]
+pub struct LibraryMetadata {{
+    version: u32,
+    iridis: AbiStr,
+    rustc: AbiStr,
+    target: AbiStr,
+    interface: extern "C" fn() -> u64,
+}}
+
+unsafe impl Sync for LibraryMetadata {{}}
+
+extern "C" fn interface() -> u64 {{
+    0
+}}
+
+#[unsafe(no_mangle)]
+pub static IRIDIS_NODE_METADATA: LibraryMetadata = LibraryMetadata {{
+    version: {},
+    iridis: abi_str("{}"),
+    rustc: abi_str("rustc 1.0.0"),
+    target: abi_str("{}"),
+    interface,
+}};
+"#,
+        IRIDIS_METADATA_VERSION, current.iridis, current.target
+    );
+
+    let directory = std::env::temp_dir().join(format!("iridis-{}-{}", name, Uuid::new_v4()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let path = directory.join("library.rs");
+    std::fs::write(&path, source).unwrap();
+
+    let library = directory.join(format!(
+        "{}{}.{}",
+        std::env::consts::DLL_PREFIX,
+        name,
+        std::env::consts::DLL_EXTENSION
+    ));
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+
+    let status = std::process::Command::new(rustc)
+        .args(["--edition", "2024", "--crate-type", "cdylib"])
+        .args(["--crate-name", name, "-o"])
+        .arg(&library)
+        .arg(&path)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    library
+}
+
+#[tokio::test]
+async fn refuse_incompatible_metadata() {
+    let library = incompatible_library("incompatible");
+
+    let layout = DataflowLayout::empty();
+
+    let (node, _) = layout
+        .node("node", async |builder: &mut NodeLayout| builder.input("in"))
+        .await;
+
+    let layout = layout.finish(async |_| Ok(())).await.unwrap();
+
+    let url = Url::from_file_path(&library).unwrap();
+    let spawned = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async move |loader: &mut Loader| {
+            loader.load_url(url, node, serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await;
+
+    let report = match spawned {
+        Ok(_) => panic!("The library has been loaded"),
+        Err(report) => format!("{:?}", report),
+    };
+
+    assert!(report.contains("Refusing to load library"), "{}", report);
+    assert!(report.contains("built with 'rustc 1.0.0'"), "{}", report);
+    assert!(
+        report.contains("interface hash 0x0000000000000000"),
+        "{}",
+        report
+    );
+    assert!(!report.contains("target"), "{}", report);
+
+    let _ = std::fs::remove_dir_all(library.parent().unwrap());
+}
+
+#[test]
+fn spawn_on_host_runtime() {
+    static EXECUTOR: NodeExecutor = NodeExecutor::new();
+
+    let host = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("host")
+        .enable_all()
+        .build()
+        .unwrap();
+
+    EXECUTOR.set_host(
+        HostRuntime::new(host.handle().clone(), &Metadata::current(NODE_INTERFACE)).unwrap(),
+    );
+
+    let task = std::thread::spawn(|| {
+        EXECUTOR.spawn(async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+
+            tokio::spawn(async { std::thread::current().name().map(String::from) })
+                .await
+                .unwrap()
+        })
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(host.block_on(task).unwrap().as_deref(), Some("host"));
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use iridis::prelude::{thirdparty::*, *};
+
+/// Answers every tick with a random number and the virtual time
+#[derive(Node)]
+pub struct Noise {
+    #[input("in")]
+    pub input: Input<String>,
+    #[output("out")]
+    pub output: Output<String>,
+}
+
+#[node(runtime = "default_runtime")]
+impl Node for Noise {
+    async fn start(mut self: Box<Self>) -> Result<()> {
+        while self.input.recv().await.is_ok() {
+            let time = NodeClock::now().duration_since(UNIX_EPOCH)?;
+
+            self.output
+                .send(format!("{} at {:?}", NodeClock::random(), time))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Run a timer feeding `Noise` for 10 virtual seconds, and collect what it sent
+fn simulate(seed: u64) -> Vec<(String, String)> {
+    Simulation::new(seed)
+        .run(async {
+            let layout = DataflowLayout::empty();
+
+            let (timer, output) = layout
+                .node("timer", async |builder: &mut NodeLayout| {
+                    builder.place(Placement::Dedicated { core: None });
+                    builder.output("out")
+                })
+                .await;
+
+            let (noise, (input, _)) = layout
+                .node("noise", async |builder: &mut NodeLayout| {
+                    (builder.input("in"), builder.output("out"))
+                })
+                .await;
+
+            let layout = layout
+                .finish(async |flows| flows.connect(output, input))
+                .await?;
+
+            let handle =
+                Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+                    .await?
+                    .spawn(layout, async |loader: &mut Loader| {
+                        loader
+                            .load::<Timer>(timer.clone(), serde_yml::from_str("frequency: 10.0")?);
+                        loader.load::<Noise>(noise.clone(), serde_yml::from_str("")?);
+
+                        Ok(())
+                    })
+                    .await?;
+
+            let mut receiver = handle.subscribe(noise.output("out")).await?;
+
+            tokio::time::sleep(Duration::from_secs(10)).await;
+
+            handle.stop();
+            handle.wait().await?;
+
+            let mut messages = Vec::new();
+            while let Some(message) = receiver.recv().await {
+                let message: TypedDataflowMessage<String> = message.try_into()?;
+
+                messages.push((format!("{:?}", message.header.timestamp), message.data));
+            }
+
+            Ok(messages)
+        })
+        .unwrap()
+}
+
+#[test]
+fn simulate_deterministically() {
+    let first = simulate(7);
+
+    assert!(first.len() >= 90, "{}", first.len());
+    assert!(first[0].1.ends_with("at 0ns"), "{}", first[0].1);
+
+    assert_eq!(first, simulate(7));
+    assert_ne!(first, simulate(8));
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use iridis::prelude::{
+    iridis_node::prelude::thirdparty::{Uuid, arrow_array::Array},
+    thirdparty::{
+        tokio::{
+            self,
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+            sync::mpsc,
+        },
+        *,
+    },
+    *,
+};
+
+/// A collector accepting every OTLP request, it sends their bodies to the returned receiver
+async fn collector() -> (std::net::SocketAddr, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+
+            let body = loop {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+
+                let text = String::from_utf8_lossy(&request).to_string();
+                let Some((head, body)) = text.split_once("\r\n\r\n") else {
+                    continue;
+                };
+
+                let length = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap();
+
+                if body.len() >= length {
+                    assert!(head.starts_with("POST /v1/traces HTTP/1.1\r\n"));
+
+                    break body.to_string();
+                }
+            };
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            stream.shutdown().await.unwrap();
+
+            let _ = sender.send(body);
+        }
+    });
+
+    (address, receiver)
+}
+
+#[tokio::test]
+async fn propagate_and_export_traces() {
+    let layout = DataflowLayout::empty();
+
+    let (source, (_, output)) = layout
+        .node("source", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let (sink, (input, sink_output)) = layout
+        .node("sink", async |builder: &mut NodeLayout| {
+            (builder.input("in"), builder.output("out"))
+        })
+        .await;
+
+    let layout = layout
+        .finish(async |flows| flows.connect(output, input))
+        .await
+        .unwrap();
+
+    let handle = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await
+        .unwrap()
+        .spawn(layout, async |loader: &mut Loader| {
+            loader.load::<Transport>(source.clone(), serde_yml::from_str("")?);
+            loader.load::<Transport>(sink.clone(), serde_yml::from_str("")?);
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+    let (address, mut requests) = collector().await;
+    let export = handle.export_traces(address);
+
+    let mut receiver = handle.subscribe(sink_output).await.unwrap();
+    let sender = handle.input_sender(source.input("in")).await.unwrap();
+
+    sender
+        .send(DataflowMessage {
+            header: Header::new(handle.clock().new_timestamp(), (Uuid::nil(), Uuid::nil())),
+            data: "traced".to_string().try_into_arrow().unwrap().into_data(),
+        })
+        .await
+        .unwrap();
+
+    // The message continues the trace started by 'source' through 'sink'
+    let message = receiver.recv().await.unwrap();
+    let trace = message.header.trace.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // source/in, source/out, sink/in and sink/out
+    assert_eq!(export.stop().await.unwrap(), 4);
+
+    let mut body = String::new();
+    while let Ok(request) = requests.try_recv() {
+        body.push_str(&request);
+    }
+
+    let trace_id = format!("\"traceId\":\"{:032x}\"", trace.trace_id);
+    assert_eq!(body.matches(&trace_id).count(), 4);
+    assert!(body.contains(&format!("\"spanId\":\"{:016x}\"", trace.span_id)));
+
+    for span in ["receive in", "send out"] {
+        assert_eq!(body.matches(&format!("\"name\":\"{}\"", span)).count(), 2);
+    }
+
+    for node in ["source", "sink"] {
+        assert!(body.contains(&format!(
+            "{{\"key\":\"service.name\",\"value\":{{\"stringValue\":\"{}\"}}}}",
+            node
+        )));
+    }
+
+    drop(sender);
+    handle.wait().await.unwrap();
+}
+
+#[test]
+fn trace_context_bytes() {
+    let trace = TraceContext::root();
+
+    assert_eq!(TraceContext::from_bytes(trace.to_bytes()), Some(trace));
+    assert_eq!(TraceContext::from_bytes([0; 24]), None);
+
+    let child = trace.child();
+    assert_eq!(child.trace_id, trace.trace_id);
+    assert_ne!(child.span_id, trace.span_id);
+}
+
+#[test]
+fn drop_spans_exporter_cannot_keep_up_with() {
+    let traces = Traces::default();
+    let mut spans = traces.export();
+
+    let span = SpanRecord {
+        context: TraceContext::root(),
+        parent: None,
+        kind: SpanKind::Send,
+        node: NodeID::new("node"),
+        port: "out".to_string(),
+        start: NodeClock::now(),
+        end: NodeClock::now(),
+        error: false,
+    };
+
+    for _ in 0..MAX_PENDING_SPANS + 10 {
+        traces.record(span.clone());
+    }
+
+    assert_eq!(traces.dropped(), 10);
+
+    traces.detach();
+
+    let mut received = 0;
+    while spans.try_recv().is_ok() {
+        received += 1;
+    }
+
+    assert_eq!(received, MAX_PENDING_SPANS);
+}
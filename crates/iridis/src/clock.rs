@@ -0,0 +1,124 @@
+//! This module defines the `RuntimeClock`, the hybrid logical clock of a runtime. Every
+//! message received from another process or machine updates it, so the timestamps of the
+//! messages keep their causality across runtimes, and the offset of the clock of each peer
+//! is kept to inspect the drift between them.
+
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::UNIX_EPOCH,
+};
+
+use uhlc::{HLCBuilder, ID, NTP64, Timestamp};
+
+use crate::prelude::{iridis_node::prelude::thirdparty::Uuid, *};
+
+/// The offset between the clock of a peer and the local clock, observed on the messages
+/// received from it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockDrift {
+    /// The time of the last timestamp minus the local time when it was received, in
+    /// nanoseconds. It's positive when the peer is ahead, and includes the transport latency.
+    pub last: i64,
+    /// The largest offset observed, in absolute value
+    pub max: i64,
+    /// The number of timestamps observed
+    pub messages: u64,
+}
+
+/// The HLC ID of a runtime, derived from its ID.
+pub fn clock_id(id: &str) -> ID {
+    let uuid = Uuid::new_v3(&Uuid::NAMESPACE_OID, id.as_bytes());
+
+    // A v3 UUID always has its version bits set, so it's never zero
+    ID::try_from(uuid.into_bytes()).expect("A v3 UUID is never zero")
+}
+
+/// The physical clock of the runtimes, `uhlc` only provides one with its `std` feature. It's
+/// virtual in a simulation.
+fn now() -> NTP64 {
+    NTP64::from(
+        NodeClock::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default(),
+    )
+}
+
+fn nanos(time: NTP64) -> i128 {
+    time.to_duration().as_nanos() as i128
+}
+
+/// The clock of a runtime. It derefs to its `HLC`.
+pub struct RuntimeClock {
+    hlc: Arc<HLC>,
+    drifts: Mutex<HashMap<String, ClockDrift>>,
+}
+
+impl Default for RuntimeClock {
+    fn default() -> Self {
+        // A random ID would make the timestamps of a simulation differ between two runs
+        match NodeClock::simulation() {
+            Some(seed) => Self::new(&format!("simulation-{}", seed)),
+            None => Self::from_hlc(HLCBuilder::new().with_clock(now).build()),
+        }
+    }
+}
+
+impl RuntimeClock {
+    /// A clock whose HLC ID is derived from `id`
+    pub fn new(id: &str) -> Self {
+        Self::from_hlc(
+            HLCBuilder::new()
+                .with_id(clock_id(id))
+                .with_clock(now)
+                .build(),
+        )
+    }
+
+    fn from_hlc(hlc: HLC) -> Self {
+        Self {
+            hlc: Arc::new(hlc),
+            drifts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The HLC shared with the nodes
+    pub fn hlc(&self) -> Arc<HLC> {
+        self.hlc.clone()
+    }
+
+    /// Update the clock with a timestamp received from `peer`, and record its offset.
+    pub fn observe(&self, peer: &str, timestamp: &Timestamp) {
+        let offset = (nanos(*timestamp.get_time()) - nanos(now()))
+            .clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+
+        if let Ok(mut drifts) = self.drifts.lock() {
+            let drift = drifts.entry(peer.to_string()).or_default();
+
+            drift.last = offset;
+            drift.max = drift.max.max(offset.saturating_abs());
+            drift.messages += 1;
+        }
+
+        if let Err(error) = self.hlc.update_with_timestamp(timestamp) {
+            tracing::warn!("Timestamp from '{}' rejected: {}", peer, error);
+        }
+    }
+
+    /// The drift of every peer a timestamp has been received from
+    pub fn drifts(&self) -> HashMap<String, ClockDrift> {
+        self.drifts
+            .lock()
+            .map(|drifts| drifts.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Deref for RuntimeClock {
+    type Target = HLC;
+
+    fn deref(&self) -> &HLC {
+        &self.hlc
+    }
+}
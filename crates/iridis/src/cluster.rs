@@ -0,0 +1,599 @@
+//! This module distributes a dataflow across several runtimes, one per machine. Every runtime
+//! loads the same layout but only the nodes of its machine, and the connections between two
+//! machines are bridged over TCP. At startup the runtimes check that they share the same
+//! layout, and wait for each other to be loaded before starting their nodes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::transport::{self, read_frame, spawn_reader, write_frame};
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::{
+        Uuid,
+        serde::{Deserialize, Serialize},
+    },
+    thirdparty::tokio::{
+        self,
+        net::{TcpListener, TcpStream, tcp::OwnedWriteHalf},
+        sync::mpsc,
+        task::JoinSet,
+    },
+    *,
+};
+
+/// How long a runtime waits for its peers to connect by default
+pub const DEFAULT_CLUSTER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The frames that are not messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "iridis_node::prelude::thirdparty::serde")]
+enum Control {
+    /// The first frame on a connection, with the description of the layout
+    Hello { machine: String, layout: String },
+    /// Every node of the machine is loaded, with the uuid of every node and primitive of its
+    /// layout by name, to translate the headers of the messages it sends
+    Ready { uuids: HashMap<String, String> },
+    /// A connection to an input on the other machine has been closed
+    Close(String),
+}
+
+type Frame = transport::Frame<Control>;
+
+/// The machines of a distributed dataflow, as seen by the runtime of one of them.
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    /// The machine of this runtime
+    pub machine: String,
+    /// The address this runtime listens on for the other machines
+    pub listen: SocketAddr,
+    /// The address of every other machine
+    pub peers: HashMap<String, SocketAddr>,
+
+    /// How long to wait for the other machines at startup
+    pub timeout: Duration,
+}
+
+impl Cluster {
+    pub fn new(machine: impl Into<String>, listen: SocketAddr) -> Self {
+        Self {
+            machine: machine.into(),
+            listen,
+            peers: HashMap::new(),
+            timeout: DEFAULT_CLUSTER_TIMEOUT,
+        }
+    }
+
+    pub fn peer(mut self, machine: impl Into<String>, address: SocketAddr) -> Self {
+        self.peers.insert(machine.into(), address);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check that every node of the layout has a unique label, and is on a machine of the cluster.
+    fn check(&self, layout: &DataflowLayout) -> Result<()> {
+        let mut labels = HashSet::new();
+
+        for node in layout.debug.nodes.keys() {
+            let label = layout.label(node);
+
+            if !labels.insert(label.clone()) {
+                eyre::bail!(
+                    "Node label '{}' is used twice, nodes of a distributed dataflow are matched by label",
+                    label
+                );
+            }
+
+            match layout.data.machines.get(node) {
+                Some(machine) if *machine == self.machine || self.peers.contains_key(machine) => {}
+                Some(machine) => eyre::bail!(
+                    "Node '{}' (uuid: {}) runs on machine '{}', which is not part of the cluster",
+                    label,
+                    node,
+                    machine
+                ),
+                None => eyre::bail!(
+                    "Node '{}' (uuid: {}) has no machine, every node of a distributed dataflow needs one",
+                    label,
+                    node
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Connect to every other machine: each pair of machines shares one TCP connection,
+    /// opened by the machine whose name comes first.
+    pub(crate) async fn connect(&self, layout: &DataflowLayout) -> Result<Peers> {
+        self.check(layout)?;
+
+        let description = describe(layout);
+        let listener = TcpListener::bind(self.listen)
+            .await
+            .wrap_err(format!("Failed to listen on {}", self.listen))?;
+
+        let mut outgoing = JoinSet::new();
+        for (machine, address) in &self.peers {
+            if *machine > self.machine {
+                let (cluster, machine, address) = (self.clone(), machine.clone(), *address);
+                let description = description.clone();
+
+                outgoing.spawn(async move { cluster.dial(&machine, address, &description).await });
+            }
+        }
+
+        let incoming = self
+            .peers
+            .keys()
+            .filter(|machine| **machine < self.machine)
+            .count();
+
+        // The last connection refused while accepting the other machines
+        let mut refused = None;
+
+        let streams = tokio::time::timeout(self.timeout, async {
+            let mut streams = self
+                .accept(&listener, incoming, &description, &mut refused)
+                .await;
+
+            while let Some(stream) = outgoing.join_next().await {
+                let (machine, stream) = stream??;
+                streams.insert(machine, stream);
+            }
+
+            Ok::<_, eyre::Report>(streams)
+        })
+        .await
+        .map_err(|_| {
+            let report = eyre::eyre!(
+                "Machine '{}' timed out waiting for the other machines",
+                self.machine
+            );
+
+            match refused {
+                Some(refused) => refused.wrap_err(report),
+                None => report,
+            }
+        })??;
+
+        Ok(Peers {
+            machine: self.machine.clone(),
+            streams,
+        })
+    }
+
+    async fn dial(
+        &self,
+        machine: &str,
+        address: SocketAddr,
+        description: &str,
+    ) -> Result<(String, TcpStream)> {
+        // The other machine may not be listening yet
+        let mut stream = loop {
+            match TcpStream::connect(address).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        };
+
+        stream.set_nodelay(true)?;
+
+        self.hello(&mut stream, description).await?;
+        let peer = self.greeted(&mut stream, description).await?;
+
+        if peer != machine {
+            eyre::bail!(
+                "Expected machine '{}' at {}, found '{}'",
+                machine,
+                address,
+                peer
+            );
+        }
+
+        Ok((peer, stream))
+    }
+
+    /// Accept the connections of the machines whose name comes first. Any other connection is
+    /// dropped with a warning, the last one in `refused`, so a stray connection doesn't stop
+    /// the cluster from starting.
+    async fn accept(
+        &self,
+        listener: &TcpListener,
+        count: usize,
+        description: &str,
+        refused: &mut Option<eyre::Report>,
+    ) -> HashMap<String, TcpStream> {
+        let mut streams = HashMap::new();
+        let mut handshakes = JoinSet::new();
+
+        while streams.len() < count {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, address)) => {
+                        let (cluster, description) = (self.clone(), description.to_string());
+
+                        handshakes.spawn(async move {
+                            (address, cluster.handshake(stream, &description).await)
+                        });
+                    }
+                    Err(e) => tracing::warn!("Failed to accept a connection: {}", e),
+                },
+                Some(Ok((address, handshake))) = handshakes.join_next() => {
+                    let report = match handshake {
+                        Ok((peer, stream)) if peer < self.machine && !streams.contains_key(&peer) => {
+                            streams.insert(peer, stream);
+
+                            continue;
+                        }
+                        Ok((peer, _)) => eyre::eyre!("Unexpected connection from machine '{}'", peer),
+                        Err(report) => report,
+                    };
+
+                    tracing::warn!("Dropped the connection from {}: {:?}", address, report);
+                    refused.replace(report);
+                }
+            }
+        }
+
+        streams
+    }
+
+    /// Exchange the `Hello` of both machines on an accepted connection
+    async fn handshake(
+        &self,
+        mut stream: TcpStream,
+        description: &str,
+    ) -> Result<(String, TcpStream)> {
+        stream.set_nodelay(true)?;
+
+        self.hello(&mut stream, description).await?;
+        let peer = self.greeted(&mut stream, description).await?;
+
+        Ok((peer, stream))
+    }
+
+    async fn hello(&self, stream: &mut TcpStream, description: &str) -> Result<()> {
+        write_frame(
+            stream,
+            Frame::Control(Control::Hello {
+                machine: self.machine.clone(),
+                layout: description.to_string(),
+            }),
+            None,
+        )
+        .await
+    }
+
+    /// Read the `Hello` of the other machine, and check it has the same layout
+    async fn greeted(&self, stream: &mut TcpStream, description: &str) -> Result<String> {
+        match read_frame(stream, None).await? {
+            Some(Frame::Control(Control::Hello { machine, layout })) => {
+                if !self.peers.contains_key(&machine) {
+                    eyre::bail!("Machine '{}' is not part of the cluster", machine);
+                }
+
+                if layout != description {
+                    eyre::bail!(
+                        "The layout of machine '{}' differs from the layout of machine '{}'",
+                        machine,
+                        self.machine
+                    );
+                }
+
+                Ok(machine)
+            }
+            _ => eyre::bail!("Expected a machine of the cluster"),
+        }
+    }
+}
+
+/// The name of a primitive shared by every machine: the uuids of the nodes are random, so
+/// each machine that builds the layout has its own.
+pub(crate) fn qualified(
+    layout: &DataflowLayout,
+    nodes: &HashMap<Uuid, Uuid>,
+    primitive: &Uuid,
+) -> String {
+    let node = nodes.get(primitive).map(|node| layout.label(node));
+
+    format!("{}/{}", node.unwrap_or_default(), layout.label(primitive))
+}
+
+/// The node of every primitive
+pub(crate) fn primitive_nodes(layout: &DataflowLayout) -> HashMap<Uuid, Uuid> {
+    layout
+        .debug
+        .nodes
+        .iter()
+        .flat_map(|(node, primitives)| primitives.iter().map(|primitive| (*primitive, *node)))
+        .collect()
+}
+
+/// The uuid of every node, by label, and of every primitive, by qualified name.
+pub(crate) fn named_uuids(layout: &DataflowLayout) -> HashMap<String, Uuid> {
+    let nodes = primitive_nodes(layout);
+
+    layout
+        .debug
+        .nodes
+        .keys()
+        .map(|node| (layout.label(node), *node))
+        .chain(
+            nodes
+                .keys()
+                .map(|primitive| (qualified(layout, &nodes, primitive), *primitive)),
+        )
+        .collect()
+}
+
+/// The uuids of another machine mirrored to the uuids of the same nodes and primitives here.
+fn mirror(local: &HashMap<String, Uuid>, remote: HashMap<String, String>) -> HashMap<Uuid, Uuid> {
+    remote
+        .into_iter()
+        .filter_map(|(name, remote)| Some((Uuid::parse_str(&remote).ok()?, *local.get(&name)?)))
+        .collect()
+}
+
+/// Translate the uuids of the header of a message sent by another machine, the ones that
+/// aren't part of the layout are kept.
+fn translate(header: &mut Header, mirrored: &HashMap<Uuid, Uuid>) {
+    let uuid = |uuid: Uuid| mirrored.get(&uuid).copied().unwrap_or(uuid);
+
+    header.source = (uuid(header.source.0), uuid(header.source.1));
+
+    if let Some(lineage) = &mut header.metadata.lineage {
+        lineage.translate(uuid);
+    }
+}
+
+/// A description of the layout with labels only, so two runtimes can check they run the
+/// same dataflow.
+pub(crate) fn describe(layout: &DataflowLayout) -> String {
+    let nodes = primitive_nodes(layout);
+    let mut lines = Vec::new();
+
+    for node in layout.debug.nodes.keys() {
+        lines.push(format!(
+            "node '{}' on '{}'",
+            layout.label(node),
+            layout.data.machines.get(node).cloned().unwrap_or_default()
+        ));
+    }
+
+    for primitive in nodes.keys() {
+        let kind = match primitive {
+            p if layout.data.inputs.contains(p) => "input",
+            p if layout.data.outputs.contains(p) => "output",
+            p if layout.data.queries.contains(p) => "query",
+            _ => "queryable",
+        };
+
+        lines.push(format!(
+            "{} '{}'",
+            kind,
+            qualified(layout, &nodes, primitive)
+        ));
+    }
+
+    for (a, b) in &layout.flows.connections {
+        lines.push(format!(
+            "connection '{}' -> '{}'",
+            qualified(layout, &nodes, a),
+            qualified(layout, &nodes, b)
+        ));
+    }
+
+    lines.sort();
+    lines.join("\n")
+}
+
+/// The connections to the other machines, once they have all been checked.
+pub(crate) struct Peers {
+    machine: String,
+    streams: HashMap<String, TcpStream>,
+}
+
+impl Peers {
+    /// Claim the channels of every connection between this machine and another one, wait for
+    /// every machine to be loaded, then forward the messages in the background.
+    pub(crate) async fn bridge(
+        self,
+        flows: &RuntimeFlows,
+        layout: &DataflowLayout,
+        clock: Arc<RuntimeClock>,
+    ) -> Result<()> {
+        let nodes = primitive_nodes(layout);
+
+        let machine = |primitive: &Uuid| {
+            nodes
+                .get(primitive)
+                .and_then(|node| layout.data.machines.get(node))
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        // Inputs on another machine, fed by a local output
+        let mut remote_inputs = HashMap::<String, Vec<(String, MessageReceiver)>>::new();
+        // Local inputs, fed by an output on another machine
+        let mut local_inputs = HashMap::<String, HashMap<String, MessageSender>>::new();
+
+        let mut seen = HashSet::new();
+        for (a, b) in &layout.flows.connections {
+            let (from, to) = (machine(a), machine(b));
+
+            if from == to || !seen.insert(*b) {
+                continue;
+            }
+
+            if !layout.data.outputs.contains(a) {
+                eyre::bail!(
+                    "'{}' and '{}' are on different machines, queries and queryables can't be connected across machines",
+                    layout.label(a),
+                    layout.label(b)
+                );
+            }
+
+            if from == self.machine {
+                let receiver =
+                    flows
+                        .inputs_receivers
+                        .lock()
+                        .await
+                        .remove(b)
+                        .ok_or_eyre(format!(
+                            "Input '{}' has already been claimed",
+                            layout.label(b)
+                        ))?;
+
+                remote_inputs
+                    .entry(to)
+                    .or_default()
+                    .push((qualified(layout, &nodes, b), receiver));
+            } else if to == self.machine {
+                let sender = flows.input_sender(layout, *b).await?;
+
+                local_inputs
+                    .entry(from)
+                    .or_default()
+                    .insert(qualified(layout, &nodes, b), sender);
+            }
+        }
+
+        let local = named_uuids(layout);
+        let uuids = local
+            .iter()
+            .map(|(name, uuid)| (name.clone(), uuid.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let mut links = Vec::new();
+
+        for (peer, stream) in self.streams {
+            let (reader, mut writer) = stream.into_split();
+
+            let ready = Control::Ready {
+                uuids: uuids.clone(),
+            };
+            write_frame(&mut writer, Frame::Control(ready), None).await?;
+
+            links.push((peer, spawn_reader::<Control>(reader, None), writer));
+        }
+
+        // Every machine must be loaded before any node starts
+        let mut mirrored = HashMap::new();
+        for (peer, frames, _) in &mut links {
+            match frames.recv().await {
+                Some(Ok(Frame::Control(Control::Ready { uuids }))) => {
+                    mirrored.insert(peer.clone(), mirror(&local, uuids));
+                }
+                Some(Err(report)) => return Err(report),
+                _ => eyre::bail!("Machine '{}' failed to load", peer),
+            }
+        }
+
+        for (peer, frames, writer) in links {
+            let inputs = remote_inputs.remove(&peer).unwrap_or_default();
+            let senders = local_inputs.remove(&peer).unwrap_or_default();
+            let mirrored = mirrored.remove(&peer).unwrap_or_default();
+
+            tokio::spawn(forward(peer.clone(), inputs, writer));
+            tokio::spawn(dispatch(peer, frames, senders, mirrored, clock.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Send the messages of the remote inputs to their machine.
+async fn forward(peer: String, inputs: Vec<(String, MessageReceiver)>, mut writer: OwnedWriteHalf) {
+    let (sender, mut frames) = mpsc::channel::<Frame>(128);
+
+    for (input, mut receiver) in inputs {
+        let sender = sender.clone();
+
+        tokio::spawn(async move {
+            // Keep draining the channel if the machine is gone, so the output doesn't fail
+            let mut open = true;
+
+            while let Some(message) = receiver.recv().await {
+                if open {
+                    open = sender
+                        .send(Frame::Message(input.clone(), message))
+                        .await
+                        .is_ok();
+                }
+            }
+
+            let _ = sender.send(Frame::Control(Control::Close(input))).await;
+        });
+    }
+
+    drop(sender);
+
+    while let Some(frame) = frames.recv().await {
+        if let Err(report) = write_frame(&mut writer, frame, None).await {
+            tracing::warn!("Connection to machine '{}' lost: {:?}", peer, report);
+
+            // Dropping the frames closes the forwarders, which keep draining their inputs
+            return;
+        }
+    }
+}
+
+/// Feed the local inputs with the messages sent by a machine. Every input is fed by a task of
+/// its own, so an input that is full doesn't hold back the messages of the others.
+async fn dispatch(
+    peer: String,
+    mut frames: mpsc::Receiver<Result<Frame>>,
+    senders: HashMap<String, MessageSender>,
+    mirrored: HashMap<Uuid, Uuid>,
+    clock: Arc<RuntimeClock>,
+) {
+    let mut inputs = senders
+        .into_iter()
+        .map(|(input, sender)| {
+            let (relay, mut messages) = mpsc::channel::<DataflowMessage>(128);
+
+            tokio::spawn(async move {
+                while let Some(message) = messages.recv().await {
+                    if sender.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            (input, relay)
+        })
+        .collect::<HashMap<_, _>>();
+
+    while let Some(frame) = frames.recv().await {
+        match frame {
+            Ok(Frame::Message(input, mut message)) => {
+                clock.observe(&peer, &message.header.timestamp);
+                translate(&mut message.header, &mirrored);
+
+                if let Some(relay) = inputs.get(&input) {
+                    let _ = relay.send(message).await;
+                }
+            }
+            Ok(Frame::Control(Control::Close(input))) => {
+                // The input is closed once the messages sent before are received
+                inputs.remove(&input);
+            }
+            Ok(Frame::Control(_) | Frame::Release(_)) => {}
+            Err(report) => {
+                tracing::warn!("Connection to machine '{}' lost: {:?}", peer, report);
+
+                return;
+            }
+        }
+    }
+}
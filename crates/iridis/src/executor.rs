@@ -0,0 +1,167 @@
+//! This module defines the executors of the runtime: the named pools declared on the
+//! `Runtime`, and the executor of each node built from its `Placement`.
+
+use std::collections::HashMap;
+
+use crate::prelude::{
+    thirdparty::tokio::{
+        self,
+        runtime::{Builder, Handle},
+        sync::oneshot,
+    },
+    *,
+};
+
+/// Pin the current thread to a CPU core.
+fn pin(core: usize) -> Result<()> {
+    match core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+        true => Ok(()),
+        false => Err(eyre::eyre!("Failed to pin thread to CPU core {}", core)),
+    }
+}
+
+/// A `tokio` runtime owned by the `Runtime`. It's shut down in the background when dropped,
+/// so it can be dropped from an asynchronous context.
+struct Pool(Option<tokio::runtime::Runtime>);
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.0.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// The executor of a node. A dedicated executor runs until it's dropped, along with the
+/// remaining tasks of the node.
+pub struct Executor {
+    handle: Handle,
+    _shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Executor {
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// A current-thread `tokio` runtime driven by `drive` until the executor is dropped.
+    fn current_thread(drive: impl FnOnce(Box<dyn FnOnce() + Send>) -> Result<()>) -> Result<Self> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .wrap_err("Failed to create Tokio runtime")?;
+
+        let handle = runtime.handle().clone();
+        let (shutdown, signal) = oneshot::channel::<()>();
+
+        drive(Box::new(move || {
+            let _ = runtime.block_on(signal);
+        }))?;
+
+        Ok(Self {
+            handle,
+            _shutdown: Some(shutdown),
+        })
+    }
+}
+
+/// The executors of the runtime, shared by every dataflow it spawns.
+#[derive(Default)]
+pub struct Executors {
+    pools: std::sync::Mutex<HashMap<String, Pool>>,
+    nodes: std::sync::Mutex<HashMap<NodeID, Executor>>,
+}
+
+impl Executors {
+    /// Declare a named pool of `workers` threads. Threads are pinned round-robin to `cores`,
+    /// if any.
+    pub fn pool(&self, name: impl Into<String>, workers: usize, cores: Vec<usize>) -> Result<()> {
+        let name = name.into();
+        let next = std::sync::atomic::AtomicUsize::new(0);
+
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(workers)
+            .thread_name(format!("iridis-{}", name))
+            .on_thread_start(move || {
+                if cores.is_empty() {
+                    return;
+                }
+
+                let core =
+                    cores[next.fetch_add(1, std::sync::atomic::Ordering::Relaxed) % cores.len()];
+
+                if let Err(report) = pin(core) {
+                    tracing::warn!("{:?}", report);
+                }
+            })
+            .enable_all()
+            .build()
+            .wrap_err(format!("Failed to create pool '{}'", name))?;
+
+        self.pools
+            .lock()
+            .map_err(|_| eyre::eyre!("Executors lock poisoned"))?
+            .insert(name, Pool(Some(runtime)));
+
+        Ok(())
+    }
+
+    /// Build the executor of a node from its placement, `None` for `Placement::Shared`. In a
+    /// simulation every node runs on the thread of the simulation, placements are ignored.
+    pub fn executor(&self, node: &NodeID, placement: &Placement) -> Result<Option<Executor>> {
+        if NodeClock::simulation().is_some() {
+            return Ok(None);
+        }
+
+        let executor = match placement {
+            Placement::Shared => return Ok(None),
+            Placement::Dedicated { core } => {
+                let core = *core;
+
+                Executor::current_thread(|drive| {
+                    std::thread::Builder::new()
+                        .name(format!("iridis-{}", node.label))
+                        .spawn(move || {
+                            if let Err(report) = core.map(pin).transpose() {
+                                tracing::warn!("{:?}", report);
+                            }
+
+                            drive()
+                        })
+                        .map(|_| ())
+                        .wrap_err("Failed to spawn dedicated thread")
+                })?
+            }
+            Placement::Pool(name) => Executor {
+                handle: self
+                    .pools
+                    .lock()
+                    .map_err(|_| eyre::eyre!("Executors lock poisoned"))?
+                    .get(name)
+                    .and_then(|pool| pool.0.as_ref())
+                    .map(|runtime| runtime.handle().clone())
+                    .ok_or_eyre(format!("Pool '{}' is not declared on the runtime", name))?,
+                _shutdown: None,
+            },
+            Placement::Blocking => Executor::current_thread(|drive| {
+                tokio::task::spawn_blocking(drive);
+
+                Ok(())
+            })?,
+        };
+
+        Ok(Some(executor))
+    }
+
+    /// Keep the executor of a node until the node is supervised.
+    pub(crate) fn insert(&self, node: NodeID, executor: Executor) {
+        if let Ok(mut nodes) = self.nodes.lock() {
+            nodes.insert(node, executor);
+        }
+    }
+
+    /// Take the executor of a node, `None` if it runs on the shared executor.
+    pub(crate) fn take(&self, node: &NodeID) -> Option<Executor> {
+        self.nodes.lock().ok()?.remove(node)
+    }
+}
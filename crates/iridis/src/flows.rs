@@ -8,30 +8,58 @@ use std::{
 
 use crate::prelude::{
     iridis_node::prelude::thirdparty::Uuid,
-    thirdparty::tokio::{self, sync::Mutex},
+    thirdparty::tokio::{
+        self,
+        sync::{Mutex, RwLock},
+    },
     *,
 };
 
 type SharedMap<K, V> = Arc<Mutex<HashMap<K, V>>>;
 
+/// A tap attached to a connection of a running dataflow. It receives a copy of every
+/// message sent through the connection until it's detached.
+#[derive(Debug)]
+pub struct ConnectionTap {
+    pub uuid: Uuid,
+
+    pub output: Uuid,
+    pub input: Uuid,
+
+    pub rx: MessageReceiver,
+}
+
 /// This struct contains the channels used to communicate between
 /// the different nodes in the dataflow.
 pub struct RuntimeFlows {
     pub inputs_receivers: SharedMap<Uuid, MessageReceiver>,
-    pub outputs_senders: SharedMap<Uuid, Vec<MessageSender>>,
+    pub outputs_senders: SharedMap<Uuid, SharedOutputChannels>,
 
     pub queries_senders: SharedMap<Uuid, MessageSender>, // other side is in 'queryables_receivers'
     pub queries_receivers: SharedMap<Uuid, MessageReceiver>, // other side is in 'queryables_senders'
 
     pub queryables_senders: SharedMap<Uuid, HashMap<Uuid, MessageSender>>, // receiver part in 'queries_receivers'
     pub queryables_receivers: SharedMap<Uuid, MessageReceiver>, // sender part in 'queries_senders'
+
+    pub inputs_senders: SharedMap<Uuid, WeakMessageSender>, // weak side of every input, for the host
+    pub outputs_channels: SharedMap<Uuid, WeakOutputChannels>, // weak side of every output, for the host
+
+    pub unconnected_inputs_senders: SharedMap<Uuid, MessageSender>, // inputs with no output connected, fed by the host
+
+    pub metrics: Arc<Metrics>, // counted by the primitives of every node
+    pub traces: Arc<Traces>,   // spans recorded by the primitives of every node
 }
 
 impl RuntimeFlows {
     /// Creates a new `RuntimeFlows` struct from a `DataflowLayout`.
     pub fn new(layout: Arc<DataflowLayout>) -> Result<Self> {
         let mut inputs_receivers = HashMap::new();
-        let mut outputs_senders = HashMap::new();
+        let mut outputs_senders = layout
+            .data
+            .outputs
+            .iter()
+            .map(|output| (*output, OutputChannels::default()))
+            .collect::<HashMap<_, _>>();
 
         let mut queries_senders = HashMap::new();
         let mut queries_receivers = HashMap::new();
@@ -42,6 +70,8 @@ impl RuntimeFlows {
         let mut queryables_senders = HashMap::new();
         let mut queryables_receivers = HashMap::new();
 
+        let mut inputs_senders = HashMap::new();
+
         for (a, b) in &layout.flows.connections {
             if layout.data.outputs.contains(a) && !inputs_receivers.contains_key(b) {
                 let (output, input) = (a, b);
@@ -49,10 +79,13 @@ impl RuntimeFlows {
                 let (sender, receiver) = tokio::sync::mpsc::channel(128);
 
                 inputs_receivers.insert(*input, receiver);
+                inputs_senders.insert(*input, sender.downgrade());
+
                 outputs_senders
                     .entry(*output)
-                    .or_insert_with(Vec::new)
-                    .push(sender);
+                    .or_default()
+                    .connections
+                    .insert(*input, sender);
             }
 
             if layout.data.queryables.contains(a) && !queries_receivers.contains_key(b) {
@@ -101,6 +134,31 @@ impl RuntimeFlows {
             }
         }
 
+        // Inputs with no output connected can still be fed by the host, the runtime keeps
+        // the sender so the channel stays open.
+        let mut unconnected_inputs_senders = HashMap::new();
+
+        for input in &layout.data.inputs {
+            if !inputs_receivers.contains_key(input) {
+                let (sender, receiver) = tokio::sync::mpsc::channel(128);
+
+                inputs_receivers.insert(*input, receiver);
+                inputs_senders.insert(*input, sender.downgrade());
+
+                unconnected_inputs_senders.insert(*input, sender);
+            }
+        }
+
+        let outputs_senders = outputs_senders
+            .into_iter()
+            .map(|(output, channels)| (output, Arc::new(RwLock::new(channels))))
+            .collect::<HashMap<_, _>>();
+
+        let outputs_channels = outputs_senders
+            .iter()
+            .map(|(output, channels)| (*output, Arc::downgrade(channels)))
+            .collect::<HashMap<_, _>>();
+
         Ok(Self {
             inputs_receivers: Arc::new(Mutex::new(inputs_receivers)),
             outputs_senders: Arc::new(Mutex::new(outputs_senders)),
@@ -110,30 +168,173 @@ impl RuntimeFlows {
 
             queryables_senders: Arc::new(Mutex::new(queryables_senders)),
             queryables_receivers: Arc::new(Mutex::new(queryables_receivers)),
+
+            inputs_senders: Arc::new(Mutex::new(inputs_senders)),
+            outputs_channels: Arc::new(Mutex::new(outputs_channels)),
+
+            unconnected_inputs_senders: Arc::new(Mutex::new(unconnected_inputs_senders)),
+
+            metrics: Arc::default(),
+            traces: Arc::default(),
         })
     }
 
     /// Extracts the `Inputs`, `Outputs`, `Queries` and `Queryables` for a given node
     pub fn node_primitives(
-        &mut self,
+        &self,
         clock: Arc<HLC>,
         node: NodeID,
     ) -> (Inputs, Outputs, Queries, Queryables) {
-        let inputs = Inputs::new(self.inputs_receivers.clone(), node.clone());
-        let outputs = Outputs::new(self.outputs_senders.clone(), clock.clone(), node.clone());
+        let tracer = NodeTracer::new(&self.traces);
+
+        let inputs = Inputs::new(self.inputs_receivers.clone(), node.clone())
+            .with_metrics(self.metrics.clone())
+            .with_tracer(tracer.clone());
+        let outputs = Outputs::new(self.outputs_senders.clone(), clock.clone(), node.clone())
+            .with_metrics(self.metrics.clone())
+            .with_tracer(tracer.clone());
         let queries = Queries::new(
             self.queries_senders.clone(),
             self.queries_receivers.clone(),
             clock.clone(),
             node.clone(),
-        );
+        )
+        .with_metrics(self.metrics.clone())
+        .with_tracer(tracer.clone());
         let queryables = Queryables::new(
             self.queryables_senders.clone(),
             self.queryables_receivers.clone(),
             clock.clone(),
             node.clone(),
-        );
+        )
+        .with_metrics(self.metrics.clone())
+        .with_tracer(tracer);
 
         (inputs, outputs, queries, queryables)
     }
+
+    /// Drops every primitive that has not been claimed by a node, so the channels
+    /// connected to them are closed.
+    pub async fn release_unclaimed(&self) {
+        self.inputs_receivers.lock().await.clear();
+        self.outputs_senders.lock().await.clear();
+
+        self.queries_senders.lock().await.clear();
+        self.queries_receivers.lock().await.clear();
+
+        self.queryables_senders.lock().await.clear();
+        self.queryables_receivers.lock().await.clear();
+    }
+
+    /// Get a sender to an input of the dataflow. Fails if the input has already been closed.
+    pub async fn input_sender(
+        &self,
+        layout: &DataflowLayout,
+        input: impl Into<Uuid>,
+    ) -> Result<MessageSender> {
+        let input = input.into();
+
+        self.inputs_senders
+            .lock()
+            .await
+            .get(&input)
+            .ok_or_eyre(report_primitive_not_in_layout(layout, input))?
+            .upgrade()
+            .ok_or_eyre(report_primitive_closed(layout, input))
+    }
+
+    /// Attach a new receiver to an output of the dataflow. It will receive every message
+    /// sent by this output from now on. Fails if the output has already been closed.
+    pub async fn subscribe(
+        &self,
+        layout: &DataflowLayout,
+        output: impl Into<Uuid>,
+    ) -> Result<MessageReceiver> {
+        let output = output.into();
+
+        let channels = self
+            .outputs_channels
+            .lock()
+            .await
+            .get(&output)
+            .ok_or_eyre(report_primitive_not_in_layout(layout, output))?
+            .upgrade()
+            .ok_or_eyre(report_primitive_closed(layout, output))?;
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(128);
+
+        channels.write().await.taps.insert(
+            Uuid::new_v4(),
+            Tap {
+                tx: sender,
+                overflow: TapOverflow::Backpressure,
+            },
+        );
+
+        Ok(receiver)
+    }
+
+    /// Attach a tap to the connection between an output and an input. The connection itself
+    /// is left untouched, the tap only receives a copy of every message sent through it.
+    pub async fn tap(
+        &self,
+        layout: &DataflowLayout,
+        output: impl Into<Uuid>,
+        input: impl Into<Uuid>,
+        overflow: TapOverflow,
+        capacity: usize,
+    ) -> Result<ConnectionTap> {
+        let (output, input) = (output.into(), input.into());
+
+        if capacity == 0 {
+            eyre::bail!("The capacity of a tap must be at least 1");
+        }
+
+        let channels = self
+            .outputs_channels
+            .lock()
+            .await
+            .get(&output)
+            .ok_or_eyre(report_primitive_not_in_layout(layout, output))?
+            .upgrade()
+            .ok_or_eyre(report_primitive_closed(layout, output))?;
+
+        let mut channels = channels.write().await;
+
+        if !channels.connections.contains_key(&input) {
+            return Err(report_no_connection(layout, output, input));
+        }
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(capacity);
+
+        let uuid = Uuid::new_v4();
+        channels.taps.insert(
+            uuid,
+            Tap {
+                tx: sender,
+                overflow,
+            },
+        );
+
+        Ok(ConnectionTap {
+            uuid,
+            output,
+            input,
+            rx: receiver,
+        })
+    }
+
+    /// Detach a tap from its connection. The messages already received by the tap can still be read.
+    pub async fn untap(&self, tap: &ConnectionTap) {
+        let channels = self
+            .outputs_channels
+            .lock()
+            .await
+            .get(&tap.output)
+            .and_then(|channels| channels.upgrade());
+
+        if let Some(channels) = channels {
+            channels.write().await.taps.remove(&tap.uuid);
+        }
+    }
 }
@@ -0,0 +1,528 @@
+//! This module defines the `DataflowHandle`, returned by `Runtime::spawn`. It lets
+//! the user control and observe a running dataflow from the host application.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use tracing::Instrument;
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::Uuid,
+    thirdparty::tokio::{
+        self,
+        sync::{Mutex, broadcast, mpsc, oneshot, watch},
+        task::JoinHandle,
+    },
+    *,
+};
+
+/// The status of a node inside a running dataflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeStatus {
+    /// The node is currently running its `start` function.
+    Running,
+    /// The node has been paused by the host, its `start` function is not polled.
+    Paused,
+    /// The node returned from its `start` function without error.
+    Finished,
+    /// The node has been stopped by the runtime before it could finish.
+    Stopped,
+    /// The node returned an error, the report is stored as a string.
+    Failed(String),
+}
+
+/// Events emitted by the runtime during the life of a dataflow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeEvent {
+    NodeStarted(NodeID),
+    NodeFinished(NodeID),
+    NodeStopped(NodeID),
+    NodeFailed(NodeID, String),
+    NodePaused(NodeID),
+    NodeResumed(NodeID),
+}
+
+/// Handle to a running dataflow. It can be used to stop the dataflow, `await` for its
+/// completion, inspect the status of each node and subscribe to runtime events.
+///
+/// Dropping the handle does not stop the dataflow, the nodes keep running in the background.
+pub struct DataflowHandle {
+    pub(crate) layout: Arc<Mutex<Arc<DataflowLayout>>>,
+    pub(crate) clock: Arc<RuntimeClock>,
+    pub(crate) flows: Arc<RuntimeFlows>,
+
+    pub(crate) file_ext: Arc<FileExtManager>,
+    pub(crate) url_scheme: Arc<UrlSchemeManager>,
+    pub(crate) executors: Arc<Executors>,
+
+    pub(crate) status: Arc<Mutex<HashMap<NodeID, NodeStatus>>>,
+    pub(crate) stops: Mutex<HashMap<NodeID, watch::Sender<bool>>>,
+    pub(crate) lifecycles: Arc<Mutex<HashMap<NodeID, mpsc::UnboundedSender<LifecycleEvent>>>>,
+
+    pub(crate) events: broadcast::Sender<RuntimeEvent>,
+    pub(crate) initial: std::sync::Mutex<Option<broadcast::Receiver<RuntimeEvent>>>,
+    pub(crate) stop: watch::Sender<bool>,
+
+    pub(crate) spawner: mpsc::UnboundedSender<JoinHandle<Result<()>>>,
+    pub(crate) task: JoinHandle<Result<()>>,
+}
+
+/// Stops a running dataflow, like `DataflowHandle::stop`. It can be cloned and moved to another
+/// task, while the `DataflowHandle` is consumed by `wait`.
+#[derive(Debug, Clone)]
+pub struct DataflowStopper {
+    stop: watch::Sender<bool>,
+}
+
+impl DataflowStopper {
+    /// Ask every node of the dataflow to stop, see `DataflowHandle::stop`.
+    pub fn stop(&self) {
+        self.stop.send_replace(true);
+    }
+}
+
+/// Resolves once the signal is set. If the sender is dropped it never resolves.
+async fn stopped(mut signal: watch::Receiver<bool>) {
+    if signal.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+impl DataflowHandle {
+    /// Spawn all the nodes on the current `tokio` runtime and return the handle that controls them.
+    pub(crate) fn new(
+        layout: Arc<DataflowLayout>,
+        clock: Arc<RuntimeClock>,
+        flows: Arc<RuntimeFlows>,
+        file_ext: Arc<FileExtManager>,
+        url_scheme: Arc<UrlSchemeManager>,
+        executors: Arc<Executors>,
+        nodes: HashMap<NodeID, LoadedNode>,
+    ) -> Self {
+        // Keep the events emitted before `spawn` returns for the first call to `events`
+        let (events, initial) = broadcast::channel(128);
+        let (stop, _) = watch::channel(false);
+
+        let (spawner, mut tasks) = mpsc::unbounded_channel::<JoinHandle<Result<()>>>();
+
+        // The pools must live as long as the nodes, even if the handle is dropped
+        let pools = executors.clone();
+
+        let task = tokio::spawn(async move {
+            let _pools = pools;
+
+            let mut reports: Vec<eyre::Report> = Vec::new();
+
+            let mut is_ok = true;
+
+            // Every task is awaited, even after one of them panicked
+            while let Some(task) = tasks.recv().await {
+                let result = task
+                    .await
+                    .wrap_err("A node task panicked or was cancelled")
+                    .and_then(|result| result);
+
+                if let Err(report) = result {
+                    is_ok = false;
+                    reports.push(report);
+                }
+            }
+
+            let reports: eyre::Report = {
+                let report_str: String = reports.iter().fold(
+                    "The runtime encountered multiple errors:".to_string(),
+                    |acc, report| format!("{}\n\n{:?}", acc, report),
+                );
+
+                eyre::Report::msg(report_str)
+            };
+
+            match is_ok {
+                true => Ok(()),
+                false => Err(reports),
+            }
+        });
+
+        let status = nodes
+            .keys()
+            .map(|layout| (layout.clone(), NodeStatus::Running))
+            .collect::<HashMap<_, _>>();
+
+        let (start, _) = watch::channel(false);
+        let mut lifecycles = HashMap::new();
+        let mut readies = Vec::new();
+
+        let nodes = nodes
+            .into_iter()
+            .map(|(layout, node)| {
+                let (lifecycle, sender, ready) = Lifecycle::new(start.subscribe());
+
+                lifecycles.insert(layout.clone(), sender.clone());
+                readies.push(ready);
+
+                (layout, node, lifecycle, sender)
+            })
+            .collect::<Vec<_>>();
+
+        let mut handle = Self {
+            layout: Arc::new(Mutex::new(layout)),
+            clock,
+            flows,
+            file_ext,
+            url_scheme,
+            executors,
+            status: Arc::new(Mutex::new(status)),
+            stops: Mutex::new(HashMap::new()),
+            lifecycles: Arc::new(Mutex::new(lifecycles)),
+            events,
+            initial: std::sync::Mutex::new(Some(initial)),
+            stop,
+            spawner,
+            task,
+        };
+
+        for (layout, node, lifecycle, sender) in nodes {
+            let stop = handle.supervise(layout.clone(), node, lifecycle, sender);
+
+            handle.stops.get_mut().insert(layout, stop);
+        }
+
+        Self::start_when_ready(readies, start);
+
+        handle
+    }
+
+    /// Allow a batch of nodes to start once all of them are ready, or gone.
+    fn start_when_ready(readies: Vec<oneshot::Receiver<()>>, start: watch::Sender<bool>) {
+        tokio::spawn(async move {
+            for ready in readies {
+                let _ = ready.await;
+            }
+
+            start.send_replace(true);
+        });
+    }
+
+    /// Spawn a task that runs the node and reports its status. Returns the signal that
+    /// stops this node only. When the node fails, the other nodes receive `LifecycleEvent::PeerError`.
+    fn supervise(
+        &self,
+        layout: NodeID,
+        node: LoadedNode,
+        lifecycle: Lifecycle,
+        lifecycle_events: mpsc::UnboundedSender<LifecycleEvent>,
+    ) -> watch::Sender<bool> {
+        let (remove, signal) = watch::channel(false);
+
+        let status = self.status.clone();
+        let lifecycles = self.lifecycles.clone();
+        let events = self.events.clone();
+        let stop = self.stop.subscribe();
+
+        // Every event of the node is emitted in its span
+        let span = tracing::info_span!("node", label = %layout.label, uuid = %layout.uuid);
+
+        let executor = self.executors.take(&layout);
+        let handle = executor.as_ref().map(|executor| executor.handle().clone());
+
+        let supervisor = async move {
+            // A dedicated executor ends with its node
+            let _executor = executor;
+
+            let _ = events.send(RuntimeEvent::NodeStarted(layout.clone()));
+
+            // Only a node that ends because of the stop request is reported as stopped
+            let interrupted = Arc::new(AtomicBool::new(false));
+
+            let (global, own, flag) = (stop.clone(), signal.clone(), interrupted.clone());
+            let result = node
+                .run_with(lifecycle, lifecycle_events, async move {
+                    tokio::select! {
+                        _ = stopped(global) => {},
+                        _ = stopped(own) => {},
+                    }
+
+                    flag.store(true, Ordering::Relaxed);
+                })
+                .await
+                .wrap_err(format!(
+                    "Node '{}' (uuid: {}) failed",
+                    layout.label, layout.uuid,
+                ));
+
+            let (node_status, event) = match &result {
+                Ok(()) if interrupted.load(Ordering::Relaxed) => (
+                    NodeStatus::Stopped,
+                    RuntimeEvent::NodeStopped(layout.clone()),
+                ),
+                Ok(()) => (
+                    NodeStatus::Finished,
+                    RuntimeEvent::NodeFinished(layout.clone()),
+                ),
+                Err(report) => (
+                    NodeStatus::Failed(format!("{:?}", report)),
+                    RuntimeEvent::NodeFailed(layout.clone(), format!("{:?}", report)),
+                ),
+            };
+
+            {
+                let mut lifecycles = lifecycles.lock().await;
+                lifecycles.remove(&layout);
+
+                if let NodeStatus::Failed(report) = &node_status {
+                    for peer in lifecycles.values() {
+                        let _ =
+                            peer.send(LifecycleEvent::PeerError(layout.clone(), report.clone()));
+                    }
+                }
+            }
+
+            status.lock().await.insert(layout.clone(), node_status);
+            let _ = events.send(event);
+
+            result
+        };
+
+        let supervisor = supervisor.instrument(span);
+
+        let task = match handle {
+            Some(handle) => handle.spawn(supervisor),
+            None => tokio::spawn(supervisor),
+        };
+
+        let _ = self.spawner.send(task);
+
+        remove
+    }
+
+    /// Add already loaded nodes to the running dataflow and start them, once all of them are ready.
+    pub(crate) async fn start_nodes(&self, nodes: HashMap<NodeID, LoadedNode>) {
+        let mut status = self.status.lock().await;
+        let mut stops = self.stops.lock().await;
+
+        let (start, _) = watch::channel(false);
+        let mut readies = Vec::new();
+
+        for (layout, node) in nodes {
+            let (lifecycle, sender, ready) = Lifecycle::new(start.subscribe());
+
+            self.lifecycles
+                .lock()
+                .await
+                .insert(layout.clone(), sender.clone());
+            readies.push(ready);
+
+            status.insert(layout.clone(), NodeStatus::Running);
+            stops.insert(
+                layout.clone(),
+                self.supervise(layout, node, lifecycle, sender),
+            );
+        }
+
+        Self::start_when_ready(readies, start);
+    }
+
+    /// Stop some nodes of the running dataflow, `await` for them to end and forget them.
+    pub(crate) async fn remove_nodes(&self, nodes: impl IntoIterator<Item = NodeID>) {
+        let mut removed = Vec::new();
+
+        {
+            let mut stops = self.stops.lock().await;
+
+            for node in nodes {
+                if let Some(stop) = stops.remove(&node) {
+                    stop.send_replace(true);
+                    removed.push((node, stop));
+                }
+            }
+        }
+
+        for (node, stop) in removed {
+            stop.closed().await;
+
+            self.status.lock().await.remove(&node);
+        }
+    }
+
+    /// Pause a running node: it receives `LifecycleEvent::Pause` and is expected to stop
+    /// processing its inputs. Only nodes with lifecycle hooks can be paused.
+    pub async fn pause(&self, node: &NodeID) -> Result<()> {
+        self.send_lifecycle(node, LifecycleEvent::Pause, NodeStatus::Running)
+            .await?;
+
+        if let Some(status) = self
+            .status
+            .lock()
+            .await
+            .get_mut(node)
+            .filter(|status| **status == NodeStatus::Running)
+        {
+            *status = NodeStatus::Paused;
+            let _ = self.events.send(RuntimeEvent::NodePaused(node.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Resume a paused node: it receives `LifecycleEvent::Resume`.
+    pub async fn resume(&self, node: &NodeID) -> Result<()> {
+        self.send_lifecycle(node, LifecycleEvent::Resume, NodeStatus::Paused)
+            .await?;
+
+        if let Some(status) = self
+            .status
+            .lock()
+            .await
+            .get_mut(node)
+            .filter(|status| **status == NodeStatus::Paused)
+        {
+            *status = NodeStatus::Running;
+            let _ = self.events.send(RuntimeEvent::NodeResumed(node.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Send a lifecycle event to a node in the expected status.
+    async fn send_lifecycle(
+        &self,
+        node: &NodeID,
+        event: LifecycleEvent,
+        expected: NodeStatus,
+    ) -> Result<()> {
+        let status = self.status(node).await.ok_or_eyre(format!(
+            "Node '{}' (uuid: {}) is not part of the dataflow",
+            node.label, node.uuid
+        ))?;
+
+        if status != expected {
+            eyre::bail!(
+                "Node '{}' (uuid: {}) is {:?}, expected {:?}",
+                node.label,
+                node.uuid,
+                status,
+                expected
+            );
+        }
+
+        let lifecycles = self.lifecycles.lock().await;
+
+        lifecycles
+            .get(node)
+            .and_then(|sender| sender.send(event).ok())
+            .ok_or_eyre(format!(
+                "Node '{}' (uuid: {}) has no lifecycle hooks",
+                node.label, node.uuid
+            ))
+    }
+
+    /// Ask every node of the dataflow to stop. Nodes with lifecycle hooks receive
+    /// `LifecycleEvent::Stop` and are aborted if they don't end in time, other nodes that are
+    /// still running are aborted.
+    /// Use `wait` to `await` for all of them to be dropped.
+    pub fn stop(&self) {
+        self.stop.send_replace(true);
+    }
+
+    /// Get a `DataflowStopper`, to stop the dataflow from another task while `wait` is
+    /// `await`ed.
+    pub fn stopper(&self) -> DataflowStopper {
+        DataflowStopper {
+            stop: self.stop.clone(),
+        }
+    }
+
+    /// `await` for all the nodes of the dataflow to end, either because they finished,
+    /// failed or have been stopped. Inputs with no output connected are closed first, so
+    /// nodes only fed by the host can finish once the host drops its senders.
+    pub async fn wait(self) -> Result<()> {
+        self.flows.unconnected_inputs_senders.lock().await.clear();
+
+        let Self { spawner, task, .. } = self;
+
+        drop(spawner);
+
+        task.await?
+    }
+
+    /// Get the current status of a node. Returns `None` if the node is not part of this dataflow.
+    pub async fn status(&self, node: &NodeID) -> Option<NodeStatus> {
+        self.status.lock().await.get(node).cloned()
+    }
+
+    /// Get the current status of every node of the dataflow.
+    pub async fn statuses(&self) -> HashMap<NodeID, NodeStatus> {
+        self.status.lock().await.clone()
+    }
+
+    /// Get a sender to any input of the running dataflow, to inject messages from the host.
+    /// Use `clock` to stamp the `Header` of those messages.
+    pub async fn input_sender(&self, input: impl Into<Uuid>) -> Result<MessageSender> {
+        let layout = self.layout().await;
+
+        self.flows.input_sender(&layout, input).await
+    }
+
+    /// Get a receiver tapped on any output of the running dataflow. It receives a copy of
+    /// every message sent by this output from now on, with the same backpressure as a connected input.
+    pub async fn subscribe(&self, output: impl Into<Uuid>) -> Result<MessageReceiver> {
+        let layout = self.layout().await;
+
+        self.flows.subscribe(&layout, output).await
+    }
+
+    /// Attach a tap to an existing connection of the running dataflow, without modifying
+    /// the layout. With `TapOverflow::Drop` the tap never applies backpressure to the
+    /// connected input: messages are dropped for the tap when its `capacity` is reached.
+    /// Fails if `capacity` is 0.
+    pub async fn tap(
+        &self,
+        output: impl Into<Uuid>,
+        input: impl Into<Uuid>,
+        overflow: TapOverflow,
+        capacity: usize,
+    ) -> Result<ConnectionTap> {
+        let layout = self.layout().await;
+
+        self.flows
+            .tap(&layout, output, input, overflow, capacity)
+            .await
+    }
+
+    /// Detach a tap from its connection, the connection keeps running as before.
+    pub async fn untap(&self, tap: &ConnectionTap) {
+        self.flows.untap(tap).await
+    }
+
+    /// The clock shared by every node of the dataflow.
+    pub fn clock(&self) -> Arc<HLC> {
+        self.clock.hlc()
+    }
+
+    /// The drift of the clock of every process or machine this runtime received messages
+    /// from, by peer.
+    pub fn clock_drifts(&self) -> HashMap<String, ClockDrift> {
+        self.clock.drifts()
+    }
+
+    /// The current layout of the running dataflow.
+    pub async fn layout(&self) -> Arc<DataflowLayout> {
+        self.layout.lock().await.clone()
+    }
+
+    /// Subscribe to the events emitted by the runtime. The first call receives every event since
+    /// the dataflow was spawned (up to 128 of them), the next calls only the events emitted
+    /// after the call: use `statuses` to get the current state of the dataflow.
+    pub fn events(&self) -> broadcast::Receiver<RuntimeEvent> {
+        match self.initial.lock() {
+            Ok(mut initial) => initial.take(),
+            Err(_) => None,
+        }
+        .unwrap_or_else(|| self.events.subscribe())
+    }
+}
@@ -1,19 +1,48 @@
 //! This module contains the `iridis` runtime. It can be used to
 //! load and run a `DataflowLayout`
 
+pub(crate) mod clock;
+pub(crate) mod cluster;
+pub(crate) mod executor;
 pub(crate) mod flows;
+pub(crate) mod handle;
 pub(crate) mod loader;
+pub(crate) mod metrics;
+pub(crate) mod otlp;
+#[cfg(unix)]
+pub(crate) mod process;
+pub(crate) mod prometheus;
+pub(crate) mod reconfiguration;
+pub(crate) mod record;
+pub(crate) mod reload;
 pub(crate) mod report;
 pub(crate) mod runtime;
+#[cfg(feature = "simulation")]
+pub(crate) mod simulation;
+pub(crate) mod transport;
 
 pub(crate) mod plugins;
 
 /// This prelude contains everything you need to use this crate.
 pub mod prelude {
+    pub use crate::clock::*;
+    pub use crate::cluster::*;
+    pub use crate::executor::*;
     pub use crate::flows::*;
+    pub use crate::handle::*;
     pub use crate::loader::*;
+    pub use crate::metrics::*;
+    pub use crate::otlp::*;
     pub use crate::plugins::*;
+    #[cfg(unix)]
+    pub use crate::process::*;
+    pub use crate::prometheus::*;
+    pub use crate::reconfiguration::*;
+    pub use crate::record::*;
+    pub use crate::reload::*;
     pub use crate::runtime::*;
+    #[cfg(feature = "simulation")]
+    pub use crate::simulation::*;
 
     pub(crate) use crate::report::*;
 
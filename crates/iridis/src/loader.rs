@@ -5,41 +5,135 @@ use std::{collections::HashMap, sync::Arc};
 
 use crate::prelude::{thirdparty::tokio::task::JoinSet, *};
 
+/// The manifest of the node behind a `file://` URL to a dynamic library, if it declares one.
+/// Any error is left to the plugin that will load the URL.
+async fn url_manifest(url: &Url) -> Option<NodeManifest> {
+    let path = url.to_file_path().ok().filter(|_| url.scheme() == "file")?;
+
+    if path.extension()? != std::env::consts::DLL_EXTENSION {
+        return None;
+    }
+
+    library_manifest(path).await.ok().flatten()
+}
+
 /// Loader struct passed to the user closure to load nodes
 pub struct Loader {
     pub file_ext: Arc<FileExtManager>,
     pub url_scheme: Arc<UrlSchemeManager>,
 
-    pub clock: Arc<HLC>,
+    pub clock: Arc<RuntimeClock>,
+
+    pub flows: Arc<RuntimeFlows>,
+    pub layout: Arc<DataflowLayout>,
+
+    pub executors: Arc<Executors>,
+    pub placements: HashMap<NodeID, Placement>,
 
-    pub flows: RuntimeFlows,
+    /// The machine of this runtime in a distributed dataflow, nodes of other machines are
+    /// not loaded
+    pub machine: Option<String>,
 
-    pub futures: JoinSet<Result<(NodeID, RuntimeNode)>>,
+    pub futures: JoinSet<Result<(NodeID, LoadedNode)>>,
 }
 
 impl Loader {
     pub fn new(
         file_ext: Arc<FileExtManager>,
         url_scheme: Arc<UrlSchemeManager>,
-        clock: Arc<HLC>,
-        flows: RuntimeFlows,
+        clock: Arc<RuntimeClock>,
+        flows: Arc<RuntimeFlows>,
+        layout: Arc<DataflowLayout>,
+        executors: Arc<Executors>,
     ) -> Self {
         Self {
             file_ext,
             url_scheme,
             clock,
             flows,
+            layout,
+            executors,
+            placements: HashMap::new(),
+            machine: None,
             futures: JoinSet::new(),
         }
     }
 
+    /// Set the executor of a node, overriding the placement of the layout. It must be called
+    /// before the node is loaded.
+    pub fn place(&mut self, node: &NodeID, placement: Placement) {
+        self.placements.insert(node.clone(), placement);
+    }
+
+    /// Spawn the loading of a node on its executor, so the tasks spawned by the node
+    /// run on it too.
+    fn spawn(
+        &mut self,
+        source: &NodeID,
+        future: impl Future<Output = Result<(NodeID, LoadedNode)>> + Send + 'static,
+    ) {
+        if let Some(machine) = &self.machine {
+            if self.layout.machine(source) != Some(machine.as_str()) {
+                return;
+            }
+        }
+
+        let placement = self
+            .placements
+            .get(source)
+            .cloned()
+            .unwrap_or_else(|| self.layout.placement(source));
+
+        let executor = self
+            .executors
+            .executor(source, &placement)
+            .wrap_err(format!(
+                "Failed to create the executor of node '{}' (uuid: {})",
+                source.label, source.uuid,
+            ));
+
+        match executor {
+            Ok(Some(executor)) => {
+                let handle = executor.handle().clone();
+                let executors = self.executors.clone();
+
+                self.futures.spawn_on(
+                    async move {
+                        let (source, node) = future.await?;
+
+                        executors.insert(source.clone(), executor);
+
+                        Ok((source, node))
+                    },
+                    &handle,
+                );
+            }
+            Ok(None) => {
+                self.futures.spawn(future);
+            }
+            Err(report) => {
+                self.futures.spawn(async move { Err(report) });
+            }
+        }
+    }
+
     /// Load a node from a Rust struct directly (statically linked)
     pub fn load<T: Node + 'static>(&mut self, source: NodeID, configuration: serde_yml::Value) {
-        let (inputs, outputs, queries, queryables) = self
-            .flows
-            .node_primitives(self.clock.clone(), source.clone());
+        let (inputs, outputs, queries, queryables) =
+            self.flows.node_primitives(self.clock.hlc(), source.clone());
+
+        let layout = self.layout.clone();
+
+        self.spawn(&source.clone(), async move {
+            if let Some(manifest) = T::manifest() {
+                manifest.check(&layout, &source)?;
+            }
+
+            T::check_configuration(&configuration).wrap_err(format!(
+                "Invalid configuration for node '{}' (uuid: {})",
+                source.label, source.uuid,
+            ))?;
 
-        self.futures.spawn(async move {
             let node = RuntimeNode::StaticallyLinked(
                 T::new(inputs, outputs, queries, queryables, configuration)
                     .await?
@@ -56,21 +150,26 @@ impl Loader {
                 std::any::type_name::<T>()
             );
 
-            Ok((source, node))
+            Ok((source, LoadedNode::Node(node)))
         });
     }
 
     /// Load a node from an URL. Be careful, you must ensure that the runtime has the necessary plugins to process this URL.
     /// By default you can pass all URL for the builtins nodes (builtin://) and all URL for dynamic libraries on the computer (file:///path/to/library.so)
     pub fn load_url(&mut self, url: Url, source: NodeID, configuration: serde_yml::Value) {
-        let (inputs, outputs, queries, queryables) = self
-            .flows
-            .node_primitives(self.clock.clone(), source.clone());
+        let (inputs, outputs, queries, queryables) =
+            self.flows.node_primitives(self.clock.hlc(), source.clone());
 
         let file_ext = self.file_ext.clone();
         let url_scheme = self.url_scheme.clone();
 
-        self.futures.spawn(async move {
+        let layout = self.layout.clone();
+
+        self.spawn(&source.clone(), async move {
+            if let Some(manifest) = url_manifest(&url).await {
+                manifest.check(&layout, &source)?;
+            }
+
             let node = url_scheme
                 .load(
                     url.clone(),
@@ -81,7 +180,11 @@ impl Loader {
                     configuration,
                     file_ext,
                 )
-                .await?;
+                .await
+                .wrap_err(format!(
+                    "Node '{}' (uuid: {}) failed to load from URL {}",
+                    source.label, source.uuid, url
+                ))?;
 
             tracing::debug!(
                 "Node '{}' (uuid: {}) loaded from URL {:?}",
@@ -90,12 +193,116 @@ impl Loader {
                 url
             );
 
-            Ok((source, node))
+            Ok((source, LoadedNode::Node(node)))
+        });
+    }
+
+    /// Load a dynamically linked node from a `file://` URL, and reload it every time the library
+    /// file changes. The channels of the node are kept by the runtime, so each new instance is
+    /// connected exactly like the previous one. Messages not yet received by the previous
+    /// instance are lost.
+    pub fn load_url_reloadable(
+        &mut self,
+        url: Url,
+        source: NodeID,
+        configuration: serde_yml::Value,
+    ) {
+        let flows = self.flows.clone();
+        let layout = self.layout.clone();
+
+        let clock = self.clock.hlc();
+        let file_ext = self.file_ext.clone();
+
+        self.spawn(&source.clone(), async move {
+            let path = match url.scheme() {
+                "file" => url
+                    .to_file_path()
+                    .map_err(|_| eyre::eyre!("Url '{}' cannot be made into a path buf", url))?,
+                scheme => eyre::bail!(
+                    "Url scheme '{}' can't be reloaded, only 'file' is supported",
+                    scheme
+                ),
+            };
+
+            let node = ReloadableNode::new(
+                &flows,
+                &layout,
+                clock,
+                file_ext,
+                path,
+                source.clone(),
+                configuration,
+            )
+            .await?;
+
+            tracing::debug!(
+                "Node '{}' (uuid: {}) loaded from URL {:?}, with hot reload",
+                source.label,
+                source.uuid,
+                url
+            );
+
+            Ok((source, LoadedNode::Reloadable(node)))
+        });
+    }
+
+    /// Load a node from an URL in a child process, launched as described by `process`. The
+    /// process loads the node with the default plugins, so only `builtin://` and `file://`
+    /// URLs are supported, and the node can't use queries or queryables. If the process
+    /// crashes it's launched again up to `process.restarts` times, messages in flight are lost.
+    #[cfg(unix)]
+    pub fn load_url_process(
+        &mut self,
+        url: Url,
+        source: NodeID,
+        configuration: serde_yml::Value,
+        process: NodeProcess,
+    ) {
+        let (inputs, outputs, _, _) = self.flows.node_primitives(self.clock.hlc(), source.clone());
+
+        let clock = self.clock.clone();
+        let layout = self.layout.clone();
+
+        self.spawn(&source.clone(), async move {
+            if let Some(manifest) = url_manifest(&url).await {
+                manifest.check(&layout, &source)?;
+            }
+
+            let program = process.program.clone();
+
+            let node = ProcessNode::launch(
+                url.clone(),
+                source.clone(),
+                configuration,
+                process,
+                &layout,
+                inputs,
+                outputs,
+                clock,
+            )
+            .await
+            .wrap_err(format!(
+                "Node '{}' (uuid: {}) failed to load from URL {} in a process",
+                source.label, source.uuid, url
+            ))?;
+
+            tracing::debug!(
+                "Node '{}' (uuid: {}) loaded from URL {:?}, in process {:?}",
+                source.label,
+                source.uuid,
+                url,
+                program
+            );
+
+            Ok((
+                source,
+                LoadedNode::Node(RuntimeNode::StaticallyLinked(Box::new(node))),
+            ))
         });
     }
 
     /// `await` for all nodes to be loaded and return them
-    pub async fn finish(mut self) -> Result<HashMap<NodeID, RuntimeNode>> {
+    pub async fn finish(mut self) -> Result<HashMap<NodeID, LoadedNode>> {
         let mut nodes = HashMap::new();
 
         while let Some(res) = self.futures.join_next().await {
@@ -0,0 +1,227 @@
+//! This module builds snapshots of the metrics counted by the primitives of a running
+//! dataflow, by connection, by node and by path, so the bottlenecks of a dataflow can be found.
+
+use std::{collections::HashMap, sync::atomic::Ordering, time::Duration};
+
+use crate::cluster::{primitive_nodes, qualified};
+
+use crate::prelude::{iridis_node::prelude::thirdparty::Uuid, *};
+
+/// The metrics of a connection between an output and an input.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionMetrics {
+    pub output: Uuid,
+    pub input: Uuid,
+    /// The connection as `node/output -> node/input`
+    pub label: String,
+
+    /// The messages sent by the output, and their size in bytes
+    pub sent: u64,
+    pub bytes: u64,
+    /// The messages the output failed to send, because the input was closed
+    pub errors: u64,
+    /// How long the output waited for room in the channel of the input
+    pub blocked: HistogramSnapshot,
+
+    /// The messages received by the input
+    pub received: u64,
+    /// How long the messages waited between being sent and being received
+    pub latency: HistogramSnapshot,
+
+    /// The messages waiting in the channel of the input, which is shared by every output
+    /// connected to it, and its capacity
+    pub queued: usize,
+    pub capacity: usize,
+}
+
+impl ConnectionMetrics {
+    /// How full the channel of the input is, between 0 and 1
+    pub fn occupancy(&self) -> f64 {
+        match self.capacity {
+            0 => 0.0,
+            capacity => self.queued as f64 / capacity as f64,
+        }
+    }
+}
+
+/// The end-to-end latency of the messages sent by a source output and received by an input,
+/// through any number of nodes. Only the messages whose `Lineage` is tracked are counted.
+#[derive(Debug, Clone, Default)]
+pub struct PathMetrics {
+    pub source: Uuid,
+    pub sink: Uuid,
+    /// The path as `node/output -> node/input`
+    pub label: String,
+
+    /// How long the messages took from being first sent by the source to being received
+    pub latency: HistogramSnapshot,
+}
+
+impl PathMetrics {
+    /// Whether every message received so far took at most `bound` from the source
+    pub fn within(&self, bound: Duration) -> bool {
+        self.latency.max <= bound
+    }
+}
+
+/// The metrics of a node, summed over its primitives.
+#[derive(Debug, Clone, Default)]
+pub struct NodeMetrics {
+    /// The messages sent by the outputs, and their size in bytes
+    pub sent: u64,
+    pub sent_bytes: u64,
+    /// How long the outputs waited for every connection to accept a message
+    pub send_time: HistogramSnapshot,
+
+    /// The messages received by the inputs, and their size in bytes
+    pub received: u64,
+    pub received_bytes: u64,
+    /// How long the messages waited before being received by the inputs
+    pub latency: HistogramSnapshot,
+
+    /// The requests sent by the queries, and their round trip
+    pub queries: u64,
+    pub query_time: HistogramSnapshot,
+
+    /// The requests answered by the queryables, and the time taken to answer them
+    pub answered: u64,
+    pub answer_time: HistogramSnapshot,
+
+    /// The failed sends, queries and answers
+    pub errors: u64,
+}
+
+/// The metrics of a running dataflow, see `DataflowHandle::metrics`.
+#[derive(Debug, Clone, Default)]
+pub struct DataflowMetrics {
+    pub nodes: HashMap<NodeID, NodeMetrics>,
+    pub connections: Vec<ConnectionMetrics>,
+    pub paths: Vec<PathMetrics>,
+}
+
+impl DataflowHandle {
+    /// Take a snapshot of the metrics of the running dataflow. Every counter starts when the
+    /// dataflow is loaded, the nodes running in a process of their own are only counted from
+    /// the side of the runtime.
+    pub async fn metrics(&self) -> DataflowMetrics {
+        let layout = self.layout().await;
+
+        snapshot(&layout, &self.flows).await
+    }
+}
+
+/// Take a snapshot of the metrics counted in `flows`
+pub(crate) async fn snapshot(layout: &DataflowLayout, flows: &RuntimeFlows) -> DataflowMetrics {
+    let nodes = primitive_nodes(layout);
+    let metrics = &flows.metrics;
+
+    let channels = flows
+        .inputs_senders
+        .lock()
+        .await
+        .iter()
+        .filter_map(|(input, sender)| {
+            sender
+                .upgrade()
+                .map(|sender| (*input, (sender.max_capacity(), sender.capacity())))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut connections = Vec::new();
+    for ((output, input), counters) in metrics.connections() {
+        // Messages sent by the host don't go through a connection
+        if !layout.data.outputs.contains(&output) {
+            continue;
+        }
+
+        let (capacity, available) = channels.get(&input).cloned().unwrap_or_default();
+
+        connections.push(ConnectionMetrics {
+            output,
+            input,
+            label: format!(
+                "{} -> {}",
+                qualified(layout, &nodes, &output),
+                qualified(layout, &nodes, &input)
+            ),
+            sent: counters.sent.load(Ordering::Relaxed),
+            bytes: counters.bytes.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+            blocked: counters.blocked.snapshot(),
+            received: counters.received.load(Ordering::Relaxed),
+            latency: counters.latency.snapshot(),
+            queued: capacity - available,
+            capacity,
+        });
+    }
+
+    connections.sort_by(|a, b| a.label.cmp(&b.label));
+
+    let mut paths = Vec::new();
+    for ((source, sink), latency) in metrics.paths() {
+        // Messages sent by the host or received by a subscriber aren't on a path
+        if !layout.data.outputs.contains(&source) || !layout.data.inputs.contains(&sink) {
+            continue;
+        }
+
+        paths.push(PathMetrics {
+            source,
+            sink,
+            label: format!(
+                "{} -> {}",
+                qualified(layout, &nodes, &source),
+                qualified(layout, &nodes, &sink)
+            ),
+            latency: latency.snapshot(),
+        });
+    }
+
+    paths.sort_by(|a, b| a.label.cmp(&b.label));
+
+    let mut result = HashMap::<NodeID, NodeMetrics>::new();
+    for (primitive, counters) in metrics.primitives() {
+        let Some(node) = nodes.get(&primitive) else {
+            continue;
+        };
+
+        let node = result
+            .entry(NodeID {
+                label: layout.label(node),
+                uuid: *node,
+            })
+            .or_default();
+
+        let messages = counters.messages.load(Ordering::Relaxed);
+        let bytes = counters.bytes.load(Ordering::Relaxed);
+        let time = counters.time.snapshot();
+
+        node.errors += counters.errors.load(Ordering::Relaxed);
+
+        match primitive {
+            p if layout.data.outputs.contains(&p) => {
+                node.sent += messages;
+                node.sent_bytes += bytes;
+                node.send_time.merge(&time);
+            }
+            p if layout.data.inputs.contains(&p) => {
+                node.received += messages;
+                node.received_bytes += bytes;
+                node.latency.merge(&time);
+            }
+            p if layout.data.queries.contains(&p) => {
+                node.queries += messages;
+                node.query_time.merge(&time);
+            }
+            _ => {
+                node.answered += messages;
+                node.answer_time.merge(&time);
+            }
+        }
+    }
+
+    DataflowMetrics {
+        nodes: result,
+        connections,
+        paths,
+    }
+}
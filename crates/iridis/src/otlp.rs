@@ -0,0 +1,192 @@
+//! This module exports the spans recorded by the primitives of a running dataflow to an
+//! OpenTelemetry collector, with OTLP over HTTP in JSON. Every node is a service of its own,
+//! and every send, receive, query and answer is a span of the trace of its message.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{Value, json};
+
+use crate::prelude::{
+    thirdparty::tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::mpsc,
+        task::JoinHandle,
+    },
+    *,
+};
+
+/// The address OpenTelemetry collectors usually receive OTLP over HTTP on
+pub const DEFAULT_OTLP_ADDRESS: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 4318);
+
+/// How many spans are exported at once at most
+const OTLP_BATCH: usize = 512;
+
+/// How long the spans wait before being exported
+const OTLP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the collector has to accept a batch of spans
+const OTLP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Spans being exported, see `DataflowHandle::export_traces`.
+pub struct TraceExport {
+    traces: Arc<Traces>,
+    task: JoinHandle<u64>,
+}
+
+impl TraceExport {
+    /// The spans dropped so far because the collector couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.traces.dropped()
+    }
+
+    /// Stop recording spans, export the ones already recorded and return how many spans
+    /// have been exported.
+    pub async fn stop(self) -> Result<u64> {
+        self.traces.detach();
+
+        Ok(self.task.await?)
+    }
+}
+
+impl DataflowHandle {
+    /// Record a span for every message sent, received, queried and answered from now on,
+    /// and export them to the OpenTelemetry collector at `address`, see `DEFAULT_OTLP_ADDRESS`.
+    /// Spans the collector refuses or doesn't accept in time are dropped with a warning, and
+    /// so are the spans recorded while `MAX_PENDING_SPANS` are waiting to be exported.
+    pub fn export_traces(&self, address: SocketAddr) -> TraceExport {
+        let traces = self.flows.traces.clone();
+        let spans = traces.export();
+
+        let task = tokio::spawn(export(spans, address));
+
+        TraceExport { traces, task }
+    }
+}
+
+/// Export the spans in batches to the collector at `address`, until the exporter is
+/// detached. Returns the number of spans exported.
+async fn export(mut spans: mpsc::Receiver<SpanRecord>, address: SocketAddr) -> u64 {
+    let mut exported = 0;
+
+    loop {
+        let Some(first) = spans.recv().await else {
+            return exported;
+        };
+
+        let mut batch = vec![first];
+        let deadline = tokio::time::Instant::now() + OTLP_INTERVAL;
+
+        while batch.len() < OTLP_BATCH {
+            match tokio::time::timeout_at(deadline, spans.recv()).await {
+                Ok(Some(span)) => batch.push(span),
+                _ => break,
+            }
+        }
+
+        match tokio::time::timeout(OTLP_TIMEOUT, post(address, &otlp(&batch))).await {
+            Ok(Ok(())) => exported += batch.len() as u64,
+            Ok(Err(report)) => {
+                tracing::warn!("Failed to export {} spans: {:?}", batch.len(), report)
+            }
+            Err(_) => tracing::warn!(
+                "Failed to export {} spans: the collector didn't answer in {:?}",
+                batch.len(),
+                OTLP_TIMEOUT
+            ),
+        }
+    }
+}
+
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn attribute(key: &str, value: impl ToString) -> Value {
+    json!({ "key": key, "value": { "stringValue": value.to_string() } })
+}
+
+/// The OTLP request exporting `spans`, with one resource per node
+fn otlp(spans: &[SpanRecord]) -> Value {
+    let mut nodes = HashMap::<NodeID, Vec<Value>>::new();
+
+    for span in spans {
+        // PRODUCER, CONSUMER, CLIENT and SERVER
+        let (name, kind) = match span.kind {
+            SpanKind::Send => ("send", 4),
+            SpanKind::Receive => ("receive", 5),
+            SpanKind::Query => ("query", 3),
+            SpanKind::Answer => ("answer", 2),
+        };
+
+        nodes.entry(span.node.clone()).or_default().push(json!({
+            "traceId": format!("{:032x}", span.context.trace_id),
+            "spanId": format!("{:016x}", span.context.span_id),
+            "parentSpanId": span.parent.map(|parent| format!("{:016x}", parent)).unwrap_or_default(),
+            "name": format!("{} {}", name, span.port),
+            "kind": kind,
+            "startTimeUnixNano": nanos(span.start),
+            "endTimeUnixNano": nanos(span.end.max(span.start)),
+            "attributes": [attribute("iridis.port", &span.port)],
+            "status": { "code": match span.error { true => 2, false => 0 } },
+        }));
+    }
+
+    let resources = nodes
+        .into_iter()
+        .map(|(node, spans)| {
+            json!({
+                "resource": {
+                    "attributes": [
+                        attribute("service.name", &node.label),
+                        attribute("iridis.node.uuid", node.uuid),
+                    ],
+                },
+                "scopeSpans": [{ "scope": { "name": "iridis" }, "spans": spans }],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({ "resourceSpans": resources })
+}
+
+/// Post an OTLP request to the collector at `address`
+async fn post(address: SocketAddr, request: &Value) -> Result<()> {
+    let body = serde_json::to_vec(request)?;
+
+    let mut stream = TcpStream::connect(address)
+        .await
+        .wrap_err(format!("Failed to connect to {}", address))?;
+
+    let head = format!(
+        "POST /v1/traces HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        address,
+        body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response.split_whitespace().nth(1).unwrap_or_default();
+
+    match status.starts_with('2') {
+        true => Ok(()),
+        false => eyre::bail!(
+            "The collector answered '{}'",
+            response.lines().next().unwrap_or_default()
+        ),
+    }
+}
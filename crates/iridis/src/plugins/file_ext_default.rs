@@ -36,7 +36,7 @@ impl FileExtPlugin for DefaultFileExtPlugin {
             Some(ext) => {
                 if ext == std::env::consts::DLL_EXTENSION {
                     let path_buf = path.clone();
-                    let (library, constructor) = tokio::task::spawn_blocking(move || {
+                    let (library, abi) = tokio::task::spawn_blocking(move || {
                         let library = unsafe {
                             #[cfg(target_family = "unix")]
                             let library = libloading::os::unix::Library::open(
@@ -52,28 +52,64 @@ impl FileExtPlugin for DefaultFileExtPlugin {
                             library
                         };
 
-                        let constructor = unsafe {
-                            library
-                                .get::<*mut DynamicallyLinkedNodeInstance>(b"IRIDIS_NODE")
-                                .wrap_err(format!(
-                                    "Failed to load symbol 'IRIDIS_NODE' from dylib {:?}",
-                                    path_buf
-                                ))?
-                                .read()
-                        };
+                        // Nodes exported through the C ABI are preferred, they don't depend
+                        // on the compiler and crates versions of the runtime
+                        let abi =
+                            unsafe { library.get::<*const AbiNode>(b"IRIDIS_NODE_ABI") }.is_ok();
 
-                        Ok::<_, eyre::Report>((library, constructor))
+                        Ok::<_, eyre::Report>((library, abi))
                     })
                     .await??;
 
+                    if abi {
+                        return Ok(RuntimeNode::DynamicallyLinked(DynamicallyLinkedNode {
+                            handle: Box::new(
+                                AbiLinkedNode::load(
+                                    &path,
+                                    inputs,
+                                    outputs,
+                                    queries,
+                                    queryables,
+                                    configuration,
+                                )
+                                .await?,
+                            ),
+                            _library: library,
+                        }));
+                    }
+
+                    let metadata = check_metadata(
+                        &library,
+                        LibraryKind::Node,
+                        &Metadata::current(NODE_INTERFACE),
+                        &path,
+                    )?;
+
+                    let constructor = unsafe {
+                        library
+                            .get::<*mut DynamicallyLinkedNodeInstance>(b"IRIDIS_NODE")
+                            .wrap_err(format!(
+                                "Failed to load symbol 'IRIDIS_NODE' from dylib {:?}",
+                                path
+                            ))?
+                            .read()
+                    };
+
                     Ok(RuntimeNode::DynamicallyLinked(DynamicallyLinkedNode {
                         _library: library,
-                        handle: (constructor)(inputs, outputs, queries, queryables, configuration)
-                            .await?
-                            .wrap_err(format!(
-                                "Failed to create dynamically linked node from dylib {:?}",
-                                path,
-                            ))?,
+                        handle: (constructor)(
+                            HostRuntime::new(tokio::runtime::Handle::current(), &metadata)?,
+                            inputs,
+                            outputs,
+                            queries,
+                            queryables,
+                            configuration,
+                        )
+                        .await?
+                        .wrap_err(format!(
+                            "Failed to create dynamically linked node from dylib {:?}",
+                            path,
+                        ))?,
                     }))
                 } else {
                     Err(eyre::eyre!(
@@ -0,0 +1,626 @@
+//! This module runs nodes in child processes, so a crash of a node doesn't take down the
+//! dataflow. The runtime claims the primitives of the node and launches the process, which
+//! loads the node from its URL and calls `serve_node_process`. Both sides exchange
+//! `DataflowMessage`s over a Unix domain socket, without copying the buffers allocated in a
+//! `SharedArena`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::transport::{self, SharedLink, spawn_reader};
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::{
+        Uuid,
+        serde::{Deserialize, Serialize},
+    },
+    thirdparty::tokio::{
+        self,
+        net::{UnixListener, UnixStream, unix::OwnedWriteHalf},
+        process::{Child, Command},
+        sync::mpsc,
+        task::{JoinHandle, JoinSet},
+    },
+    *,
+};
+
+/// The environment variable that gives the path of the socket to a node process
+pub const NODE_PROCESS_ENV: &str = "IRIDIS_NODE_PROCESS";
+
+/// How long the runtime waits for a node process to connect
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The pid of the process on the other side of the socket.
+fn peer(stream: &UnixStream) -> Result<u32> {
+    stream
+        .peer_cred()
+        .wrap_err("Failed to read the credentials of the socket")?
+        .pid()
+        .map(|pid| pid as u32)
+        .ok_or_eyre("Failed to identify the process on the other side of the socket")
+}
+
+/// The frames that are not messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "iridis_node::prelude::thirdparty::serde")]
+enum Control {
+    /// The node to load, sent by the runtime once the process is connected
+    Init {
+        url: String,
+        label: String,
+        uuid: String,
+        configuration: serde_yml::Value,
+
+        inputs: Vec<String>,
+        outputs: Vec<String>,
+    },
+    /// The node is loaded and started
+    Ready,
+    /// An input of the node has been closed in the runtime
+    Close(String),
+    /// The node ended, with its report if it failed
+    Finished(Option<String>),
+}
+
+type Frame = transport::Frame<Control>;
+
+async fn write_frame(writer: &mut OwnedWriteHalf, frame: Frame, link: &SharedLink) -> Result<()> {
+    transport::write_frame(writer, frame, Some(link)).await
+}
+
+/// How to launch the process of a node. The program must call `serve_node_process` when
+/// it starts.
+#[derive(Debug, Clone)]
+pub struct NodeProcess {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+
+    /// How many times the process is launched again after it crashed
+    pub restarts: usize,
+}
+
+impl NodeProcess {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            restarts: 0,
+        }
+    }
+
+    /// Launch the current executable again
+    pub fn current_exe() -> Result<Self> {
+        Ok(Self::new(
+            std::env::current_exe().wrap_err("Failed to get the current executable")?,
+        ))
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn restarts(mut self, restarts: usize) -> Self {
+        self.restarts = restarts;
+        self
+    }
+}
+
+/// How a node process ended
+enum Exit {
+    Finished(Option<String>),
+    Crashed(String),
+}
+
+/// A running node process. The buffers lent to it are released when it's dropped.
+struct Running {
+    child: Child,
+
+    frames: mpsc::Receiver<Result<Frame>>,
+    writer: OwnedWriteHalf,
+
+    link: Arc<SharedLink>,
+    returned: mpsc::UnboundedReceiver<u64>,
+}
+
+/// A node running in a child process. It holds the primitives of the node in the runtime, and
+/// forwards their messages to the process.
+pub struct ProcessNode {
+    source: NodeID,
+    process: NodeProcess,
+    init: Control,
+
+    clock: Arc<RuntimeClock>,
+
+    inputs: Vec<(String, RawInput)>,
+    outputs: HashMap<String, RawOutput>,
+
+    running: Option<Running>,
+}
+
+impl ProcessNode {
+    /// Claim the primitives of the node and launch its process, returns once the node is
+    /// loaded in the process. Queries and queryables are not supported.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn launch(
+        url: Url,
+        source: NodeID,
+        configuration: serde_yml::Value,
+        process: NodeProcess,
+        layout: &DataflowLayout,
+        mut inputs: Inputs,
+        mut outputs: Outputs,
+        clock: Arc<RuntimeClock>,
+    ) -> Result<Self> {
+        let primitives = layout
+            .debug
+            .nodes
+            .get(&source.uuid)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut raw_inputs = Vec::new();
+        let mut raw_outputs = HashMap::new();
+
+        for uuid in primitives {
+            let label = layout.label(uuid);
+
+            if layout.data.inputs.contains(&uuid) {
+                raw_inputs.push((label.clone(), inputs.raw(label).await?));
+            } else if layout.data.outputs.contains(&uuid) {
+                raw_outputs.insert(label.clone(), outputs.raw(label).await?);
+            } else {
+                eyre::bail!(
+                    "'{}' can't be used by node '{}' (uuid: {}), queries and queryables are not supported in a process",
+                    label,
+                    source.label,
+                    source.uuid
+                );
+            }
+        }
+
+        let init = Control::Init {
+            url: url.to_string(),
+            label: source.label.clone(),
+            uuid: source.uuid.to_string(),
+            configuration,
+            inputs: raw_inputs.iter().map(|(label, _)| label.clone()).collect(),
+            outputs: raw_outputs.keys().cloned().collect(),
+        };
+
+        let mut node = Self {
+            source,
+            process,
+            init,
+            clock,
+            inputs: raw_inputs,
+            outputs: raw_outputs,
+            running: None,
+        };
+
+        node.running = Some(node.spawn().await?);
+
+        Ok(node)
+    }
+
+    /// Spawn the process, send it the node to load and `await` for it to be ready.
+    async fn spawn(&self) -> Result<Running> {
+        let path = std::env::temp_dir().join(format!("iridis-{}.sock", Uuid::new_v4().simple()));
+        let listener =
+            UnixListener::bind(&path).wrap_err(format!("Failed to bind socket {:?}", path))?;
+
+        let mut child = Command::new(&self.process.program)
+            .args(&self.process.args)
+            .env(NODE_PROCESS_ENV, &path)
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err(format!("Failed to launch {:?}", self.process.program))?;
+
+        let accepted = tokio::select! {
+            accepted = tokio::time::timeout(CONNECT_TIMEOUT, listener.accept()) => accepted
+                .map_err(|_| eyre::eyre!("The process didn't connect in {:?}", CONNECT_TIMEOUT))
+                .and_then(|accepted| Ok(accepted?)),
+            status = child.wait() => Err(eyre::eyre!("The process exited before connecting: {}", status?)),
+        };
+
+        let _ = std::fs::remove_file(&path);
+
+        let (stream, _) = accepted?;
+        let (link, returned) = SharedLink::new(peer(&stream)?);
+        let (reader, mut writer) = stream.into_split();
+
+        write_frame(&mut writer, Frame::Control(self.init.clone()), &link).await?;
+
+        let mut frames = spawn_reader(reader, Some(link.clone()));
+
+        match frames.recv().await {
+            Some(Ok(Frame::Control(Control::Ready))) => Ok(Running {
+                child,
+                frames,
+                writer,
+                link,
+                returned,
+            }),
+            Some(Ok(Frame::Control(Control::Finished(Some(report))))) => Err(eyre::eyre!(report)),
+            Some(Err(report)) => Err(report),
+            _ => Err(eyre::eyre!(
+                "The process exited before loading the node: {}",
+                child.wait().await?
+            )),
+        }
+    }
+
+    /// Forward the messages between the primitives and the process, until it ends.
+    async fn serve(
+        &self,
+        running: &mut Running,
+        pending: &mut mpsc::Receiver<Frame>,
+        closed: &mut HashSet<String>,
+    ) -> Result<Exit> {
+        let Running {
+            child,
+            frames,
+            writer,
+            link,
+            returned,
+        } = running;
+
+        for label in closed.iter() {
+            write_frame(writer, Frame::Control(Control::Close(label.clone())), link).await?;
+        }
+
+        let write = async {
+            loop {
+                // The link keeps `returned` open, only `pending` ends the loop
+                let frame = tokio::select! {
+                    frame = pending.recv() => match frame {
+                        Some(frame) => frame,
+                        None => break,
+                    },
+                    Some(id) = returned.recv() => Frame::Release(id),
+                };
+
+                if let Frame::Control(Control::Close(label)) = &frame {
+                    closed.insert(label.clone());
+                }
+
+                // A broken socket is reported by the reader
+                if write_frame(writer, frame, link).await.is_err() {
+                    break;
+                }
+            }
+
+            std::future::pending::<Infallible>().await
+        };
+
+        let peer = format!("process '{}'", self.source.label);
+
+        let read = async {
+            loop {
+                match frames.recv().await {
+                    Some(Ok(Frame::Message(label, message))) => {
+                        self.clock.observe(&peer, &message.header.timestamp);
+
+                        self.outputs
+                            .get(&label)
+                            .ok_or_eyre(format!("Unknown output '{}'", label))?
+                            .forward(message)
+                            .await?;
+                    }
+                    Some(Ok(Frame::Control(Control::Finished(report)))) => {
+                        child.wait().await?;
+
+                        return Ok(Exit::Finished(report));
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(report)) => return Err(report),
+                    None => return Ok(Exit::Crashed(child.wait().await?.to_string())),
+                }
+            }
+        };
+
+        tokio::select! {
+            exit = read => exit,
+            never = write => match never {},
+        }
+    }
+
+    async fn run_process(mut self) -> Result<()> {
+        let (sender, mut pending) = mpsc::channel(128);
+        let mut forwarders = JoinSet::new();
+
+        for (label, mut input) in std::mem::take(&mut self.inputs) {
+            let sender = sender.clone();
+
+            forwarders.spawn(async move {
+                while let Ok(message) = input.recv().await {
+                    if sender
+                        .send(Frame::Message(label.clone(), message))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+
+                let _ = sender.send(Frame::Control(Control::Close(label))).await;
+            });
+        }
+
+        drop(sender);
+
+        let mut closed = HashSet::new();
+        let mut restarts = 0;
+
+        let mut running = match self.running.take() {
+            Some(running) => running,
+            None => self.spawn().await?,
+        };
+
+        loop {
+            match self.serve(&mut running, &mut pending, &mut closed).await? {
+                Exit::Finished(None) => return Ok(()),
+                Exit::Finished(Some(report)) => return Err(eyre::eyre!(report)),
+                Exit::Crashed(status) if restarts < self.process.restarts => {
+                    restarts += 1;
+
+                    tracing::warn!(
+                        "The process of node '{}' (uuid: {}) crashed ({}), restarting it ({}/{})",
+                        self.source.label,
+                        self.source.uuid,
+                        status,
+                        restarts,
+                        self.process.restarts
+                    );
+
+                    running = self.spawn().await?;
+                }
+                Exit::Crashed(status) => eyre::bail!(
+                    "The process of node '{}' (uuid: {}) crashed: {}",
+                    self.source.label,
+                    self.source.uuid,
+                    status
+                ),
+            }
+        }
+    }
+}
+
+impl Node for ProcessNode {
+    fn new(
+        _: Inputs,
+        _: Outputs,
+        _: Queries,
+        _: Queryables,
+        _: serde_yml::Value,
+    ) -> JoinHandle<Result<Box<dyn Node>>> {
+        tokio::spawn(async { Err(eyre::eyre!("A ProcessNode is launched by the Loader")) })
+    }
+
+    fn start(self: Box<Self>) -> JoinHandle<Result<()>> {
+        tokio::spawn(self.run_process())
+    }
+}
+
+/// The layout of the node alone, with the uuids it has in the runtime.
+fn process_layout(node: &NodeID, inputs: &[String], outputs: &[String]) -> DataflowLayout {
+    let inputs = inputs
+        .iter()
+        .map(|label| (node.input(label).uuid, label.clone()))
+        .collect::<HashMap<_, _>>();
+    let outputs = outputs
+        .iter()
+        .map(|label| (node.output(label).uuid, label.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut labels = inputs.clone();
+    labels.extend(outputs.clone());
+
+    let primitives = labels.keys().cloned().collect();
+    labels.insert(node.uuid, node.label.clone());
+
+    DataflowLayout {
+        data: DataLayout {
+            inputs: inputs.into_keys().collect(),
+            outputs: outputs.into_keys().collect(),
+            queries: HashSet::new(),
+            queryables: HashSet::new(),
+            placements: HashMap::new(),
+            machines: HashMap::new(),
+        },
+        debug: DebugLayout {
+            labels,
+            nodes: HashMap::from([(node.uuid, primitives)]),
+        },
+        flows: FlowLayout {
+            connections: HashSet::new(),
+        },
+    }
+}
+
+/// Load the node sent by the runtime and forward its messages, until it ends.
+async fn serve_node(
+    init: Control,
+    frames: &mut mpsc::Receiver<Result<Frame>>,
+    writer: &mut OwnedWriteHalf,
+    link: &SharedLink,
+    returned: &mut mpsc::UnboundedReceiver<u64>,
+) -> Result<()> {
+    let Control::Init {
+        url,
+        label,
+        uuid,
+        configuration,
+        inputs,
+        outputs,
+    } = init
+    else {
+        eyre::bail!("Expected the node to load");
+    };
+
+    let node = NodeID {
+        label,
+        uuid: Uuid::parse_str(&uuid).map_err(eyre::Report::msg)?,
+    };
+
+    let layout = Arc::new(process_layout(&node, &inputs, &outputs));
+    let flows = Arc::new(RuntimeFlows::new(layout.clone())?);
+
+    let runtime = Runtime::new(async |_: &mut FileExtLoader, _: &mut UrlSchemeLoader| Ok(()))
+        .await?
+        .with_id(&format!("process-{}", node.uuid));
+
+    // Subscribe before the node starts, so no message is lost
+    let (sender, mut outgoing) = mpsc::channel(128);
+    for label in outputs {
+        let mut tap = flows.subscribe(&layout, node.output(&label)).await?;
+        let sender = sender.clone();
+
+        tokio::spawn(async move {
+            while let Some(message) = tap.recv().await {
+                if sender.send((label.clone(), message)).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    drop(sender);
+
+    let mut loader = Loader::new(
+        runtime.file_ext.clone(),
+        runtime.url_scheme.clone(),
+        runtime.clock.clone(),
+        flows.clone(),
+        layout.clone(),
+        runtime.executors.clone(),
+    );
+
+    loader.load_url(Url::parse(&url)?, node.clone(), configuration);
+
+    let nodes = loader.finish().await?;
+
+    flows.release_unclaimed().await;
+
+    let mut senders = HashMap::new();
+    for label in inputs {
+        senders.insert(
+            label.clone(),
+            flows.input_sender(&layout, node.input(&label)).await?,
+        );
+    }
+
+    // Only the runtime keeps the inputs open
+    flows.unconnected_inputs_senders.lock().await.clear();
+
+    let clock = runtime.clock.clone();
+    let handle = DataflowHandle::new(
+        layout,
+        runtime.clock,
+        flows,
+        runtime.file_ext,
+        runtime.url_scheme,
+        runtime.executors,
+        nodes,
+    );
+
+    write_frame(writer, Frame::Control(Control::Ready), link).await?;
+
+    let stopper = handle.stopper();
+
+    let read = async {
+        while let Some(Ok(frame)) = frames.recv().await {
+            match frame {
+                Frame::Message(label, message) => {
+                    clock.observe("runtime", &message.header.timestamp);
+
+                    if let Some(sender) = senders.get(&label) {
+                        let _ = sender.send(message).await;
+                    }
+                }
+                Frame::Control(Control::Close(label)) => {
+                    senders.remove(&label);
+                }
+                Frame::Control(_) | Frame::Release(_) => {}
+            }
+        }
+
+        // The runtime is gone
+        stopper.stop();
+
+        std::future::pending::<Infallible>().await
+    };
+
+    let write = async {
+        loop {
+            // The link keeps `returned` open, only `outgoing` ends the loop
+            let frame = tokio::select! {
+                message = outgoing.recv() => match message {
+                    Some((label, message)) => Frame::Message(label, message),
+                    None => break,
+                },
+                Some(id) = returned.recv() => Frame::Release(id),
+            };
+
+            write_frame(writer, frame, link).await?;
+        }
+
+        Ok::<_, eyre::Report>(())
+    };
+
+    let (written, result) = tokio::select! {
+        never = read => match never {},
+        both = async { tokio::join!(write, handle.wait()) } => both,
+    };
+
+    written.and(result)
+}
+
+/// Serve the node sent by the runtime if this process has been launched as a node process,
+/// and return `true` once the node has ended. Returns `false` otherwise, so it can be called
+/// at the start of `main`:
+///
+/// ```rust,ignore
+/// if serve_node_process().await? {
+///     return Ok(());
+/// }
+/// ```
+///
+/// The node is loaded with the default plugins, from a `builtin://` or `file://` URL.
+pub async fn serve_node_process() -> Result<bool> {
+    let Ok(path) = std::env::var(NODE_PROCESS_ENV) else {
+        return Ok(false);
+    };
+
+    let stream = UnixStream::connect(&path)
+        .await
+        .wrap_err(format!("Failed to connect to socket {:?}", path))?;
+
+    let (link, mut returned) = SharedLink::new(peer(&stream)?);
+    let (reader, mut writer) = stream.into_split();
+    let mut frames = spawn_reader(reader, Some(link.clone()));
+
+    let init = match frames.recv().await {
+        Some(Ok(Frame::Control(init))) => init,
+        _ => eyre::bail!("The runtime didn't send the node to load"),
+    };
+
+    let report = serve_node(init, &mut frames, &mut writer, &link, &mut returned)
+        .await
+        .err()
+        .map(|report| format!("{:?}", report));
+
+    let _ = write_frame(
+        &mut writer,
+        Frame::Control(Control::Finished(report)),
+        &link,
+    )
+    .await;
+
+    Ok(true)
+}
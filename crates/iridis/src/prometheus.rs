@@ -0,0 +1,411 @@
+//! This module serves the metrics of a running dataflow over HTTP, in the Prometheus text
+//! format. Every port is labelled with its node, its label and its direction, and every
+//! connection and path with the ports at both ends, as written in the `DebugLayout`.
+
+use std::{
+    fmt::Write,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
+
+use crate::cluster::primitive_nodes;
+use crate::metrics::snapshot;
+
+use crate::prelude::{
+    thirdparty::tokio::{
+        self,
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+        task::JoinHandle,
+    },
+    *,
+};
+
+/// The address Prometheus exporters usually listen on
+pub const DEFAULT_METRICS_ADDRESS: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9464);
+
+/// The largest request the server reads
+const MAX_REQUEST: usize = 8192;
+
+/// An HTTP server exporting the metrics of a running dataflow, it stops when dropped.
+pub struct MetricsServer {
+    /// The address the server listens on
+    pub address: SocketAddr,
+
+    task: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// Stop serving the metrics.
+    pub fn stop(self) {}
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl DataflowHandle {
+    /// Serve the metrics of the running dataflow in the Prometheus text format, on
+    /// `http://{address}/metrics`, see `DEFAULT_METRICS_ADDRESS`. The server keeps running
+    /// until the returned `MetricsServer` is dropped.
+    pub async fn serve_metrics(&self, address: SocketAddr) -> Result<MetricsServer> {
+        let listener = TcpListener::bind(address)
+            .await
+            .wrap_err(format!("Failed to listen on {}", address))?;
+
+        let address = listener.local_addr()?;
+
+        let layout = self.layout.clone();
+        let flows = self.flows.clone();
+
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept a metrics request: {}", e);
+
+                        continue;
+                    }
+                };
+
+                let (layout, flows) = (layout.clone(), flows.clone());
+
+                tokio::spawn(async move {
+                    if let Err(e) = respond(stream, layout, flows).await {
+                        tracing::debug!("Failed to serve the metrics: {:?}", e);
+                    }
+                });
+            }
+        });
+
+        tracing::info!("Serving the metrics on http://{}/metrics", address);
+
+        Ok(MetricsServer { address, task })
+    }
+
+    /// The metrics of the running dataflow, in the Prometheus text format.
+    pub async fn prometheus(&self) -> String {
+        let layout = self.layout().await;
+
+        render(&layout, &self.flows).await
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    layout: Arc<Mutex<Arc<DataflowLayout>>>,
+    flows: Arc<RuntimeFlows>,
+) -> Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !request.windows(4).any(|end| end == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+            match stream.read(&mut buffer).await? {
+                0 => break,
+                n => request.extend_from_slice(&buffer[..n]),
+            }
+        }
+
+        Ok::<_, std::io::Error>(())
+    })
+    .await??;
+
+    let request = String::from_utf8_lossy(&request);
+    let mut line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+
+    let (status, body) = match (line.next(), line.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let layout = layout.lock().await.clone();
+
+            ("200 OK", render(&layout, &flows).await)
+        }
+        (Some("GET"), _) => ("404 Not Found", "Not Found\n".to_string()),
+        _ => ("405 Method Not Allowed", "Method Not Allowed\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Escape a label value
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The labels of a sample, as `{name="value",...}`
+fn labels(labels: &[(&str, &str)]) -> String {
+    let labels = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect::<Vec<_>>();
+
+    format!("{{{}}}", labels.join(","))
+}
+
+/// The metrics of one family, written once every sample is known
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    samples: String,
+}
+
+impl Family {
+    fn new(name: &'static str, kind: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            kind,
+            samples: String::new(),
+        }
+    }
+
+    fn sample(&mut self, labels: &str, value: impl std::fmt::Display) {
+        let _ = writeln!(self.samples, "{}{} {}", self.name, labels, value);
+    }
+
+    /// Write the cumulative buckets, the sum and the count of a histogram, in seconds
+    fn histogram(&mut self, names: &[(&str, &str)], histogram: &HistogramSnapshot) {
+        let mut cumulative = 0;
+
+        for (bucket, count) in histogram.buckets.iter().enumerate() {
+            cumulative += count;
+
+            let le = match bucket + 1 < histogram.buckets.len() {
+                true => HistogramSnapshot::bound(bucket).as_secs_f64().to_string(),
+                false => "+Inf".to_string(),
+            };
+
+            let mut names = names.to_vec();
+            names.push(("le", &le));
+
+            let _ = writeln!(
+                self.samples,
+                "{}_bucket{} {}",
+                self.name,
+                labels(&names),
+                cumulative
+            );
+        }
+
+        let names = labels(names);
+
+        let _ = writeln!(
+            self.samples,
+            "{}_sum{} {}",
+            self.name,
+            names,
+            histogram.sum.as_secs_f64()
+        );
+        let _ = writeln!(
+            self.samples,
+            "{}_count{} {}",
+            self.name, names, histogram.count
+        );
+    }
+
+    fn write(&self, output: &mut String) {
+        let _ = writeln!(output, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(output, "# TYPE {} {}", self.name, self.kind);
+
+        output.push_str(&self.samples);
+    }
+}
+
+/// Render the metrics counted in `flows` in the Prometheus text format
+async fn render(layout: &DataflowLayout, flows: &RuntimeFlows) -> String {
+    let nodes = primitive_nodes(layout);
+
+    let mut messages = Family::new(
+        "iridis_port_messages_total",
+        "counter",
+        "Messages sent by an output, received by an input, or requests sent by a query or answered by a queryable.",
+    );
+    let mut bytes = Family::new(
+        "iridis_port_bytes_total",
+        "counter",
+        "Size of the Arrow data of the messages that went through a port.",
+    );
+    let mut errors = Family::new(
+        "iridis_port_errors_total",
+        "counter",
+        "Failed sends, queries and answers of a port.",
+    );
+    let mut durations = Family::new(
+        "iridis_port_duration_seconds",
+        "histogram",
+        "Time an output waited for its connections, a message waited before an input received it, the round trip of a query, or the time a queryable took to answer.",
+    );
+
+    let mut primitives = flows.metrics.primitives().into_iter().collect::<Vec<_>>();
+    primitives.sort_by_key(|(primitive, _)| {
+        (
+            nodes.get(primitive).map(|node| layout.label(node)),
+            layout.label(primitive),
+        )
+    });
+
+    for (primitive, counters) in primitives {
+        let Some(node) = nodes.get(&primitive) else {
+            continue;
+        };
+
+        let direction = match primitive {
+            p if layout.data.inputs.contains(&p) => "input",
+            p if layout.data.outputs.contains(&p) => "output",
+            p if layout.data.queries.contains(&p) => "query",
+            _ => "queryable",
+        };
+
+        let (node, port) = (layout.label(node), layout.label(primitive));
+        let names = [
+            ("node", node.as_str()),
+            ("port", port.as_str()),
+            ("direction", direction),
+        ];
+
+        messages.sample(&labels(&names), counters.messages.load(Ordering::Relaxed));
+        bytes.sample(&labels(&names), counters.bytes.load(Ordering::Relaxed));
+        errors.sample(&labels(&names), counters.errors.load(Ordering::Relaxed));
+        durations.histogram(&names, &counters.time.snapshot());
+    }
+
+    let mut sent = Family::new(
+        "iridis_connection_sent_total",
+        "counter",
+        "Messages sent through a connection.",
+    );
+    let mut received = Family::new(
+        "iridis_connection_received_total",
+        "counter",
+        "Messages received from a connection.",
+    );
+    let mut connection_bytes = Family::new(
+        "iridis_connection_bytes_total",
+        "counter",
+        "Size of the Arrow data of the messages sent through a connection.",
+    );
+    let mut connection_errors = Family::new(
+        "iridis_connection_errors_total",
+        "counter",
+        "Messages an output failed to send through a connection, because its input was closed.",
+    );
+    let mut queued = Family::new(
+        "iridis_connection_queued",
+        "gauge",
+        "Messages waiting in the channel of the input of a connection.",
+    );
+    let mut capacity = Family::new(
+        "iridis_connection_capacity",
+        "gauge",
+        "Capacity of the channel of the input of a connection.",
+    );
+    let mut blocked = Family::new(
+        "iridis_connection_blocked_seconds",
+        "histogram",
+        "Time the output of a connection waited for room in the channel of its input.",
+    );
+    let mut latency = Family::new(
+        "iridis_connection_latency_seconds",
+        "histogram",
+        "Time between a message being stamped and its reception by the input of a connection.",
+    );
+
+    let mut path_latency = Family::new(
+        "iridis_path_latency_seconds",
+        "histogram",
+        "Time between the source of a message with a tracked lineage first sending its data and an input receiving it.",
+    );
+
+    let label = |primitive| {
+        (
+            nodes
+                .get(&primitive)
+                .map(|node| layout.label(node))
+                .unwrap_or_default(),
+            layout.label(primitive),
+        )
+    };
+
+    let snapshot = snapshot(layout, flows).await;
+
+    for connection in snapshot.connections {
+        let (output_node, output) = label(connection.output);
+        let (input_node, input) = label(connection.input);
+
+        let names = [
+            ("output_node", output_node.as_str()),
+            ("output", output.as_str()),
+            ("input_node", input_node.as_str()),
+            ("input", input.as_str()),
+        ];
+
+        sent.sample(&labels(&names), connection.sent);
+        received.sample(&labels(&names), connection.received);
+        connection_bytes.sample(&labels(&names), connection.bytes);
+        connection_errors.sample(&labels(&names), connection.errors);
+        queued.sample(&labels(&names), connection.queued);
+        capacity.sample(&labels(&names), connection.capacity);
+        blocked.histogram(&names, &connection.blocked);
+        latency.histogram(&names, &connection.latency);
+    }
+
+    for path in snapshot.paths {
+        let (source_node, source) = label(path.source);
+        let (sink_node, sink) = label(path.sink);
+
+        let names = [
+            ("source_node", source_node.as_str()),
+            ("source", source.as_str()),
+            ("sink_node", sink_node.as_str()),
+            ("sink", sink.as_str()),
+        ];
+
+        path_latency.histogram(&names, &path.latency);
+    }
+
+    let mut output = String::new();
+
+    for family in [
+        messages,
+        bytes,
+        errors,
+        durations,
+        sent,
+        received,
+        connection_bytes,
+        connection_errors,
+        queued,
+        capacity,
+        blocked,
+        latency,
+        path_latency,
+    ] {
+        family.write(&mut output);
+    }
+
+    output
+}
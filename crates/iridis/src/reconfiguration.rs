@@ -0,0 +1,313 @@
+//! This module defines the reconfiguration of a running dataflow: nodes and
+//! connections can be added or removed without stopping the other nodes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::prelude::{iridis_node::prelude::thirdparty::Uuid, *};
+
+/// The changes to apply to the connections of a running dataflow.
+#[derive(Debug, Clone)]
+pub struct FlowChanges {
+    pub connect: FlowLayout,
+    pub disconnect: FlowLayout,
+
+    pub remove: HashSet<NodeID>,
+}
+
+impl FlowChanges {
+    fn new() -> Self {
+        Self {
+            connect: FlowLayout {
+                connections: HashSet::new(),
+            },
+            disconnect: FlowLayout {
+                connections: HashSet::new(),
+            },
+            remove: HashSet::new(),
+        }
+    }
+
+    /// Connects two primitives in the graph. The order does not matter. Queries and
+    /// queryables can only be connected between new nodes.
+    pub fn connect(&mut self, a: impl Into<PrimitiveID>, b: impl Into<PrimitiveID>) -> Result<()> {
+        self.connect.connect(a, b)
+    }
+
+    /// Removes an existing connection between an output and an input. The order does not matter.
+    pub fn disconnect(
+        &mut self,
+        a: impl Into<PrimitiveID>,
+        b: impl Into<PrimitiveID>,
+    ) -> Result<()> {
+        self.disconnect.connect(a, b)
+    }
+
+    /// Stops a node and removes it from the dataflow, along with all its connections.
+    pub fn remove_node(&mut self, node: NodeID) {
+        self.remove.insert(node);
+    }
+}
+
+fn contains(data: &DataLayout, uuid: &Uuid) -> bool {
+    data.inputs.contains(uuid)
+        || data.outputs.contains(uuid)
+        || data.queries.contains(uuid)
+        || data.queryables.contains(uuid)
+}
+
+impl DataflowHandle {
+    /// Reconfigure the running dataflow. The new nodes are declared in `nodes` (use
+    /// `DataflowLayout::empty()` to declare them), the connections to add and remove
+    /// are described with the `flows` closure and the new nodes are loaded with the
+    /// `loader` closure, just like in `Runtime::spawn`.
+    ///
+    /// Everything is validated and loaded before the running dataflow is modified: if
+    /// this function fails, the dataflow is left untouched. Then the connections are
+    /// updated all at once, the new nodes are started and the removed nodes are stopped.
+    ///
+    /// Removing the last connection of an input closes its channel, the node will
+    /// receive nothing more on this input.
+    pub async fn reconfigure(
+        &self,
+        nodes: SharedDataLayout,
+        flows: impl AsyncFnOnce(&mut FlowChanges) -> Result<()>,
+        loader: impl AsyncFnOnce(&mut Loader) -> Result<()>,
+    ) -> Result<()> {
+        // Only one reconfiguration at a time
+        let mut layout = self.layout.lock().await;
+
+        let mut changes = FlowChanges::new();
+        flows(&mut changes)
+            .await
+            .wrap_err("Failed to build flow changes")?;
+
+        let data = nodes.data.lock().await.clone();
+        let debug = nodes.debug.lock().await.clone();
+
+        let mut next = layout.as_ref().clone();
+
+        next.data.inputs.extend(data.inputs.iter().cloned());
+        next.data.outputs.extend(data.outputs.iter().cloned());
+        next.data.queries.extend(data.queries.iter().cloned());
+        next.data.queryables.extend(data.queryables.iter().cloned());
+        next.data.placements.extend(data.placements.clone());
+        next.data.machines.extend(data.machines.clone());
+
+        next.debug.labels.extend(debug.labels.clone());
+        next.debug.nodes.extend(debug.nodes.clone());
+
+        let mut removed = HashSet::new();
+        for node in &changes.remove {
+            let primitives = layout.debug.nodes.get(&node.uuid).ok_or_eyre(format!(
+                "Node '{}' (uuid: {}) is not part of the dataflow",
+                node.label, node.uuid
+            ))?;
+
+            removed.extend(primitives.iter().cloned());
+        }
+
+        for (a, b) in &changes.connect.connections {
+            if removed.contains(a) || removed.contains(b) {
+                eyre::bail!(
+                    "Invalid connection between '{}' and '{}', one of them is removed",
+                    next.label(a),
+                    next.label(b)
+                );
+            }
+
+            let valid = (next.data.outputs.contains(a) && next.data.inputs.contains(b))
+                || (contains(&data, a) && contains(&data, b));
+
+            if !valid {
+                eyre::bail!(
+                    "Invalid connection between '{}' and '{}'. Queries and queryables can only be connected between new nodes",
+                    next.label(a),
+                    next.label(b)
+                );
+            }
+        }
+
+        for (output, input) in &changes.disconnect.connections {
+            if !layout.data.outputs.contains(output)
+                || !layout.flows.connections.contains(&(*output, *input))
+            {
+                return Err(report_no_connection(&layout, *output, *input));
+            }
+        }
+
+        for (a, b) in &layout.flows.connections {
+            if layout.data.outputs.contains(a) || removed.contains(a) == removed.contains(b) {
+                continue;
+            }
+
+            eyre::bail!(
+                "Can't remove the connection between '{}' and '{}', queries and queryables can't be disconnected",
+                layout.label(a),
+                layout.label(b)
+            );
+        }
+
+        // Load the new nodes with their own flows, only connected between themselves
+
+        let internal = changes
+            .connect
+            .connections
+            .iter()
+            .filter(|(a, b)| contains(&data, a) && contains(&data, b))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        let sub = Arc::new(DataflowLayout {
+            data,
+            debug,
+            flows: FlowLayout {
+                connections: internal.clone(),
+            },
+        });
+
+        let mut added = RuntimeFlows::new(sub.clone())?;
+        added.metrics = self.flows.metrics.clone();
+        added.traces = self.flows.traces.clone();
+
+        let added = Arc::new(added);
+
+        let mut node_loader = Loader::new(
+            self.file_ext.clone(),
+            self.url_scheme.clone(),
+            self.clock.clone(),
+            added.clone(),
+            sub,
+            self.executors.clone(),
+        );
+
+        loader(&mut node_loader).await?;
+
+        let new_nodes = node_loader.finish().await?;
+
+        added.release_unclaimed().await;
+
+        // Resolve every channel involved before modifying anything
+
+        let mut inputs_senders = self.flows.inputs_senders.lock().await.clone();
+        inputs_senders.extend(added.inputs_senders.lock().await.clone());
+
+        let mut outputs_channels = self.flows.outputs_channels.lock().await.clone();
+        outputs_channels.extend(added.outputs_channels.lock().await.clone());
+
+        let mut connections = Vec::new();
+        for (output, input) in changes.connect.connections.difference(&internal) {
+            if !next.data.outputs.contains(output) {
+                continue;
+            }
+
+            let sender = inputs_senders
+                .get(input)
+                .and_then(|sender| sender.upgrade())
+                .ok_or_eyre(report_primitive_closed(&next, *input))?;
+
+            connections.push((*output, *input, sender));
+        }
+
+        let mut disconnections = changes.disconnect.connections.clone();
+        for (a, b) in &layout.flows.connections {
+            if layout.data.outputs.contains(a) && (removed.contains(a) || removed.contains(b)) {
+                disconnections.insert((*a, *b));
+            }
+        }
+
+        let mut channels = HashMap::new();
+        for output in connections
+            .iter()
+            .map(|(output, _, _)| output)
+            .chain(disconnections.iter().map(|(output, _)| output))
+        {
+            if channels.contains_key(output) {
+                continue;
+            }
+
+            match outputs_channels
+                .get(output)
+                .and_then(|channels| channels.upgrade())
+            {
+                Some(output_channels) => {
+                    channels.insert(*output, output_channels);
+                }
+                // An output that has ended has no connection left to remove
+                None if !connections.iter().any(|(o, _, _)| o == output) => {}
+                None => return Err(report_primitive_closed(&next, *output)),
+            }
+        }
+
+        // Commit all the changes at once
+
+        let mut guards = HashMap::new();
+        for (output, output_channels) in channels {
+            guards.insert(output, output_channels.write_owned().await);
+        }
+
+        {
+            let mut inputs_senders = self.flows.inputs_senders.lock().await;
+            let mut outputs_channels = self.flows.outputs_channels.lock().await;
+            let mut unconnected_inputs_senders = self.flows.unconnected_inputs_senders.lock().await;
+
+            inputs_senders.extend(added.inputs_senders.lock().await.drain());
+            outputs_channels.extend(added.outputs_channels.lock().await.drain());
+            unconnected_inputs_senders
+                .extend(added.unconnected_inputs_senders.lock().await.drain());
+
+            for (output, input) in &disconnections {
+                if let Some(guard) = guards.get_mut(output) {
+                    guard.connections.remove(input);
+                }
+            }
+
+            for (output, input, sender) in connections {
+                unconnected_inputs_senders.remove(&input);
+
+                if let Some(guard) = guards.get_mut(&output) {
+                    guard.connections.insert(input, sender);
+                }
+            }
+
+            for primitive in &removed {
+                inputs_senders.remove(primitive);
+                outputs_channels.remove(primitive);
+                unconnected_inputs_senders.remove(primitive);
+            }
+        }
+
+        drop(guards);
+
+        next.flows.connections.retain(|(a, b)| {
+            !disconnections.contains(&(*a, *b)) && !removed.contains(a) && !removed.contains(b)
+        });
+        next.flows
+            .connections
+            .extend(changes.connect.connections.iter().cloned());
+
+        next.data.inputs.retain(|uuid| !removed.contains(uuid));
+        next.data.outputs.retain(|uuid| !removed.contains(uuid));
+        next.data.queries.retain(|uuid| !removed.contains(uuid));
+        next.data.queryables.retain(|uuid| !removed.contains(uuid));
+
+        for node in &changes.remove {
+            next.data.placements.remove(&node.uuid);
+            next.data.machines.remove(&node.uuid);
+            next.debug.nodes.remove(&node.uuid);
+            next.debug.labels.remove(&node.uuid);
+        }
+        next.debug.labels.retain(|uuid, _| !removed.contains(uuid));
+
+        *layout = Arc::new(next);
+
+        drop(layout);
+
+        self.start_nodes(new_nodes).await;
+        self.remove_nodes(changes.remove).await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,447 @@
+//! This module records the messages sent through the connections of a running dataflow to
+//! a file, and replays them into another dataflow. A recording is a single Arrow IPC stream with
+//! a fixed schema, one row per message in the order it was received: the input it was sent to,
+//! the fields of its `Header`, and its data. The connections of a dataflow can carry different
+//! types, so the data of every message is itself an Arrow IPC stream in the `data` column. The
+//! snapshot of the layout is kept in the metadata of the schema.
+
+use std::{
+    collections::HashMap,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use arrow_array::{
+    Array, ArrayRef, RecordBatch,
+    builder::{BinaryBuilder, FixedSizeBinaryBuilder, StringBuilder, UInt64Builder},
+    cast::AsArray,
+    types::UInt64Type,
+};
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter};
+use arrow_schema::{DataType, Field, Schema};
+use uhlc::{ID, NTP64, Timestamp};
+
+use crate::cluster::{describe, primitive_nodes, qualified};
+use crate::transport::{decode_data, encode_data};
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::Uuid,
+    thirdparty::tokio::{
+        self,
+        fs::File,
+        sync::{mpsc, watch},
+        task::JoinHandle,
+    },
+    *,
+};
+
+/// How many messages a recorded connection can buffer before it applies backpressure
+const RECORDING_CAPACITY: usize = 1024;
+
+/// The keys of the snapshot in the metadata of the schema
+const LAYOUT_KEY: &str = "iridis.layout";
+const INPUTS_KEY: &str = "iridis.inputs";
+
+/// The columns of a recording, the snapshot is added to its metadata
+fn recording_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("input", DataType::Utf8, false),
+        Field::new("timestamp", DataType::UInt64, false),
+        Field::new("clock", DataType::FixedSizeBinary(16), false),
+        Field::new("source_node", DataType::FixedSizeBinary(16), false),
+        Field::new("source_primitive", DataType::FixedSizeBinary(16), false),
+        Field::new("trace", DataType::FixedSizeBinary(24), true),
+        Field::new("sequence", DataType::UInt64, false),
+        Field::new("metadata", DataType::Binary, false),
+        Field::new("data", DataType::Binary, false),
+    ])
+}
+
+/// The layout of the dataflow a recording was made from.
+#[derive(Debug, Clone)]
+pub struct RecordingSnapshot {
+    /// The description of the whole layout, with labels only
+    pub layout: String,
+    /// The label of every recorded input, as `node/input`, by uuid
+    pub inputs: HashMap<String, String>,
+}
+
+/// A recording in progress, it ends when every recorded connection is closed or when it's stopped.
+pub struct Recording {
+    stop: watch::Sender<bool>,
+    writer: JoinHandle<Result<u64>>,
+}
+
+impl Recording {
+    /// Stop recording, the messages already received are still written. Returns the
+    /// number of messages recorded.
+    pub async fn stop(self) -> Result<u64> {
+        self.stop.send_replace(true);
+
+        self.wait().await
+    }
+
+    /// `await` for every recorded connection to be closed. Returns the number of messages recorded.
+    pub async fn wait(self) -> Result<u64> {
+        self.writer.await?
+    }
+}
+
+/// Encode messages as the rows of a batch
+fn encode_batch(
+    schema: &Arc<Schema>,
+    messages: Vec<(Uuid, DataflowMessage)>,
+) -> Result<RecordBatch> {
+    let len = messages.len();
+
+    let mut input = StringBuilder::new();
+    let mut timestamp = UInt64Builder::with_capacity(len);
+    let mut clock = FixedSizeBinaryBuilder::with_capacity(len, 16);
+    let mut source_node = FixedSizeBinaryBuilder::with_capacity(len, 16);
+    let mut source_primitive = FixedSizeBinaryBuilder::with_capacity(len, 16);
+    let mut trace = FixedSizeBinaryBuilder::with_capacity(len, 24);
+    let mut sequence = UInt64Builder::with_capacity(len);
+    let mut metadata = BinaryBuilder::new();
+    let mut data = BinaryBuilder::new();
+
+    for (uuid, message) in messages {
+        let header = message.header;
+
+        input.append_value(uuid.to_string());
+        timestamp.append_value(header.timestamp.get_time().as_u64());
+        clock.append_value(header.timestamp.get_id().to_le_bytes())?;
+        source_node.append_value(header.source.0.as_bytes())?;
+        source_primitive.append_value(header.source.1.as_bytes())?;
+
+        match header.trace {
+            Some(context) => trace.append_value(context.to_bytes())?,
+            None => trace.append_null(),
+        }
+
+        sequence.append_value(header.sequence);
+        metadata.append_value(header.metadata.encode());
+
+        let mut bytes = Vec::new();
+        encode_data(message.data, &mut bytes)?;
+        data.append_value(bytes);
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(input.finish()),
+        Arc::new(timestamp.finish()),
+        Arc::new(clock.finish()),
+        Arc::new(source_node.finish()),
+        Arc::new(source_primitive.finish()),
+        Arc::new(trace.finish()),
+        Arc::new(sequence.finish()),
+        Arc::new(metadata.finish()),
+        Arc::new(data.finish()),
+    ];
+
+    RecordBatch::try_new(schema.clone(), columns).wrap_err("Failed to encode recorded messages")
+}
+
+/// Decode the rows of a batch written by `encode_batch`, as the recorded input and its message
+fn decode_batch(batch: &RecordBatch) -> Result<Vec<(String, DataflowMessage)>> {
+    let column = |index: usize| batch.column(index);
+
+    let input = column(0).as_string::<i32>();
+    let timestamp = column(1).as_primitive::<UInt64Type>();
+    let clock = column(2).as_fixed_size_binary();
+    let source_node = column(3).as_fixed_size_binary();
+    let source_primitive = column(4).as_fixed_size_binary();
+    let trace = column(5).as_fixed_size_binary();
+    let sequence = column(6).as_primitive::<UInt64Type>();
+    let metadata = column(7).as_binary::<i32>();
+    let data = column(8).as_binary::<i32>();
+
+    let mut messages = Vec::with_capacity(batch.num_rows());
+
+    for row in 0..batch.num_rows() {
+        let id: [u8; 16] = clock.value(row).try_into()?;
+
+        let header = Header {
+            timestamp: Timestamp::new(
+                NTP64(timestamp.value(row)),
+                ID::try_from(id).map_err(eyre::Report::msg)?,
+            ),
+            source: (
+                Uuid::from_bytes(source_node.value(row).try_into()?),
+                Uuid::from_bytes(source_primitive.value(row).try_into()?),
+            ),
+            trace: match trace.is_null(row) {
+                true => None,
+                false => TraceContext::from_bytes(trace.value(row).try_into()?),
+            },
+            sequence: sequence.value(row),
+            metadata: HeaderMetadata::decode(metadata.value(row))?,
+        };
+
+        messages.push((
+            input.value(row).to_string(),
+            DataflowMessage {
+                header,
+                data: decode_data(data.value(row))?,
+            },
+        ));
+    }
+
+    Ok(messages)
+}
+
+impl DataflowHandle {
+    /// Record every message sent through `connections` to the file at `path`, which is
+    /// created or truncated. Connections are given as `(output, input)` pairs. The file is an
+    /// Arrow IPC stream: the header of every message is kept, and its data is written in
+    /// Arrow IPC.
+    ///
+    /// A recorded connection applies backpressure when the file can't be written fast enough.
+    pub async fn record(
+        &self,
+        path: impl AsRef<Path>,
+        connections: impl IntoIterator<Item = (OutputID, InputID)>,
+    ) -> Result<Recording> {
+        let layout = self.layout().await;
+        let nodes = primitive_nodes(&layout);
+
+        let mut inputs = HashMap::new();
+        let mut taps = Vec::new();
+
+        for (output, input) in connections {
+            let tap = self
+                .tap(
+                    output,
+                    input.clone(),
+                    TapOverflow::Backpressure,
+                    RECORDING_CAPACITY,
+                )
+                .await?;
+
+            inputs.insert(
+                input.uuid.to_string(),
+                qualified(&layout, &nodes, &input.uuid),
+            );
+            taps.push(tap);
+        }
+
+        let schema = Arc::new(recording_schema().with_metadata(HashMap::from([
+            (LAYOUT_KEY.to_string(), describe(&layout)),
+            (INPUTS_KEY.to_string(), serde_yml::to_string(&inputs)?),
+        ])));
+
+        let path = path.as_ref();
+        let file = File::create(path)
+            .await
+            .wrap_err(format!("Failed to create recording '{}'", path.display()))?
+            .into_std()
+            .await;
+
+        let mut file = StreamWriter::try_new(BufWriter::new(file), &schema)?;
+
+        let (stop, _) = watch::channel(false);
+        let (sender, mut messages) = mpsc::channel(RECORDING_CAPACITY);
+
+        for ConnectionTap { input, mut rx, .. } in taps {
+            let sender = sender.clone();
+            let mut stop = stop.subscribe();
+
+            tokio::spawn(async move {
+                loop {
+                    let message = tokio::select! {
+                        message = rx.recv() => message,
+                        _ = async { stop.wait_for(|stop| *stop).await.map(|_| ()) } => {
+                            // Closing the tap detaches it, keep what it already received
+                            rx.close();
+
+                            rx.recv().await
+                        }
+                    };
+
+                    let Some(message) = message else {
+                        return;
+                    };
+
+                    if sender.send((input, message)).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+
+        drop(sender);
+
+        // The Arrow IPC writer is synchronous, the messages waiting are written as one batch
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut recorded = 0;
+
+            while let Some(message) = messages.blocking_recv() {
+                let mut batch = vec![message];
+
+                while batch.len() < RECORDING_CAPACITY {
+                    match messages.try_recv() {
+                        Ok(message) => batch.push(message),
+                        Err(_) => break,
+                    }
+                }
+
+                recorded += batch.len() as u64;
+
+                file.write(&encode_batch(&schema, batch)?)?;
+            }
+
+            file.into_inner()?.flush()?;
+
+            Ok(recorded)
+        });
+
+        Ok(Recording { stop, writer })
+    }
+}
+
+/// A recording opened to be replayed into a running dataflow.
+pub struct Replay {
+    pub snapshot: RecordingSnapshot,
+
+    reader: StreamReader<BufReader<std::fs::File>>,
+    speed: f64,
+}
+
+impl Replay {
+    /// Open the recording at `path`, it's replayed at its original speed by default.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .await
+            .wrap_err(format!("Failed to open recording '{}'", path.display()))?
+            .into_std()
+            .await;
+
+        let not_a_recording = || eyre::eyre!("'{}' is not a recording", path.display());
+
+        let reader = StreamReader::try_new(BufReader::new(file), None)
+            .map_err(|error| not_a_recording().wrap_err(error))?;
+
+        let schema = reader.schema();
+        if schema.fields() != recording_schema().fields() {
+            return Err(not_a_recording());
+        }
+
+        let (Some(layout), Some(inputs)) = (
+            schema.metadata().get(LAYOUT_KEY),
+            schema.metadata().get(INPUTS_KEY),
+        ) else {
+            return Err(not_a_recording());
+        };
+
+        let snapshot = RecordingSnapshot {
+            layout: layout.clone(),
+            inputs: serde_yml::from_str(inputs)?,
+        };
+
+        Ok(Self {
+            snapshot,
+            reader,
+            speed: 1.0,
+        })
+    }
+
+    /// Scale the speed of the replay: `2.0` replays twice as fast as the messages were
+    /// recorded, and `f64::INFINITY` replays them without waiting.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Send every recorded message to its input in the running dataflow, keeping the
+    /// original headers and the time between them. Inputs are matched by uuid, then by
+    /// label: the dataflow can be built again without the nodes that sent the messages,
+    /// leaving the recorded inputs unconnected. Inputs missing from the dataflow are skipped.
+    ///
+    /// Returns the number of messages replayed, the inputs are released once it's done.
+    pub async fn run(self, handle: &DataflowHandle) -> Result<u64> {
+        if self.speed.is_nan() || self.speed <= 0.0 {
+            eyre::bail!("Invalid replay speed {}", self.speed);
+        }
+
+        let layout = handle.layout().await;
+        let nodes = primitive_nodes(&layout);
+
+        let labels = layout
+            .data
+            .inputs
+            .iter()
+            .map(|input| (qualified(&layout, &nodes, input), *input))
+            .collect::<HashMap<_, _>>();
+
+        let mut senders = HashMap::new();
+        for (recorded, label) in &self.snapshot.inputs {
+            let uuid = Uuid::parse_str(recorded).map_err(eyre::Report::msg)?;
+
+            let input = match layout.data.inputs.contains(&uuid) {
+                true => Some(uuid),
+                false => labels.get(label).cloned(),
+            };
+
+            match input {
+                Some(input) => {
+                    senders.insert(recorded.clone(), handle.input_sender(input).await?);
+                }
+                None => tracing::warn!("Input '{}' is not in the dataflow, skipping it", label),
+            }
+        }
+
+        // The Arrow IPC reader is synchronous, it stops once the replay is dropped
+        let (sender, mut messages) = mpsc::channel(RECORDING_CAPACITY);
+        let reader = self.reader;
+
+        tokio::task::spawn_blocking(move || {
+            for batch in reader {
+                let decoded = batch
+                    .map_err(eyre::Report::from)
+                    .and_then(|batch| decode_batch(&batch));
+
+                let messages = match decoded {
+                    Ok(messages) => messages.into_iter().map(Ok).collect(),
+                    Err(error) => vec![Err(error)],
+                };
+
+                for message in messages {
+                    if sender.blocking_send(message).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let mut start = None;
+        let mut replayed = 0;
+
+        while let Some(message) = messages.recv().await {
+            let (input, message) = message?;
+
+            let Some(sender) = senders.get(&input) else {
+                continue;
+            };
+
+            let time = message.header.timestamp.get_time().to_duration();
+            let (origin, started) = *start.get_or_insert((time, tokio::time::Instant::now()));
+
+            if self.speed.is_finite() {
+                let elapsed = time.saturating_sub(origin).as_secs_f64() / self.speed;
+
+                tokio::time::sleep_until(started + Duration::from_secs_f64(elapsed)).await;
+            }
+
+            sender.send(message).await.wrap_err(format!(
+                "Input '{}' has been closed",
+                self.snapshot.inputs[&input]
+            ))?;
+
+            replayed += 1;
+        }
+
+        Ok(replayed)
+    }
+}
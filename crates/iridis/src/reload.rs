@@ -0,0 +1,476 @@
+//! This module defines the hot reload of dynamically linked nodes. The runtime watches
+//! the library file of the node and reloads it when it changes, keeping its channels.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::Uuid,
+    thirdparty::tokio::{
+        self,
+        sync::{Mutex, mpsc, oneshot, watch},
+        task::JoinSet,
+    },
+    *,
+};
+
+/// How often the library file of a reloadable node is checked for changes.
+pub const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+type SharedReceiver = Arc<Mutex<MessageReceiver>>;
+type Receivers = Arc<Mutex<HashMap<Uuid, MessageReceiver>>>;
+
+/// A node loaded by the `Loader`. It's either a regular `RuntimeNode`, or a node that
+/// is reloaded every time its library file changes.
+pub enum LoadedNode {
+    Node(RuntimeNode),
+    Reloadable(ReloadableNode),
+}
+
+impl LoadedNode {
+    /// Run the node until it ends or until the `stop` future completes.
+    pub async fn run_until(self, stop: impl Future<Output = ()>) -> Result<()> {
+        match self {
+            LoadedNode::Node(node) => node.run_until(stop).await,
+            LoadedNode::Reloadable(node) => node.run_until(stop).await,
+        }
+    }
+
+    /// Run the node with its lifecycle, see `RuntimeNode::run_with`. A reloadable node is
+    /// started once every node is ready, then each of its instances receives the events of
+    /// the node.
+    pub async fn run_with(
+        self,
+        lifecycle: Lifecycle,
+        events: mpsc::UnboundedSender<LifecycleEvent>,
+        stop: impl Future<Output = ()>,
+    ) -> Result<()> {
+        match self {
+            LoadedNode::Node(node) => node.run_with(lifecycle, events, stop).await,
+            LoadedNode::Reloadable(node) => node.run_with(lifecycle, stop).await,
+        }
+    }
+}
+
+/// A dynamically linked node that is reloaded when its library file changes. The runtime
+/// keeps the channels of the node, so every new instance receives the same receivers
+/// and senders as the previous one.
+pub struct ReloadableNode {
+    pub path: PathBuf,
+    pub source: NodeID,
+    pub configuration: serde_yml::Value,
+
+    pub clock: Arc<HLC>,
+    pub file_ext: Arc<FileExtManager>,
+
+    metrics: Arc<Metrics>,
+    traces: Arc<Traces>,
+
+    inputs: HashMap<Uuid, SharedReceiver>,
+    outputs: HashMap<Uuid, SharedOutputChannels>,
+
+    queries_senders: HashMap<Uuid, MessageSender>,
+    queries_receivers: HashMap<Uuid, SharedReceiver>,
+
+    queryables_senders: HashMap<Uuid, HashMap<Uuid, MessageSender>>,
+    queryables_receivers: HashMap<Uuid, SharedReceiver>,
+}
+
+/// An instance of a reloadable node, with the tasks relaying its receivers.
+struct Instance {
+    node: RuntimeNode,
+    relays: JoinSet<()>,
+    library: PathBuf,
+
+    /// The receivers given back by the primitives of the instance when it ends
+    returned: ReturnedReceivers,
+    /// The receivers the instance didn't claim
+    unclaimed: Vec<Receivers>,
+    /// Where the receivers of the previous instance are handed to the relays of this one
+    previous: HashMap<Uuid, oneshot::Sender<MessageReceiver>>,
+}
+
+/// Forward every message to the current instance: first the messages the previous instance
+/// didn't read, then the messages from the receiver kept by the runtime. A message is only
+/// received once the instance has room for it, so none is lost when the relay is aborted.
+async fn relay(
+    previous: oneshot::Receiver<MessageReceiver>,
+    receiver: SharedReceiver,
+    sender: MessageSender,
+) {
+    if let Ok(mut previous) = previous.await {
+        loop {
+            let Ok(permit) = sender.reserve().await else {
+                return;
+            };
+
+            let Some(message) = previous.recv().await else {
+                break;
+            };
+
+            permit.send(message);
+        }
+    }
+
+    let mut receiver = receiver.lock().await;
+
+    loop {
+        let Ok(permit) = sender.reserve().await else {
+            return;
+        };
+
+        let Some(message) = receiver.recv().await else {
+            return;
+        };
+
+        permit.send(message);
+    }
+}
+
+fn relayed(
+    receivers: &HashMap<Uuid, SharedReceiver>,
+    relays: &mut JoinSet<()>,
+    previous: &mut HashMap<Uuid, oneshot::Sender<MessageReceiver>>,
+) -> Receivers {
+    let receivers = receivers
+        .iter()
+        .map(|(uuid, receiver)| {
+            let (sender, instance) = mpsc::channel(128);
+            let (handover, handed) = oneshot::channel();
+
+            relays.spawn(relay(handed, receiver.clone(), sender));
+            previous.insert(*uuid, handover);
+
+            (*uuid, instance)
+        })
+        .collect();
+
+    Arc::new(Mutex::new(receivers))
+}
+
+async fn modified(path: &Path) -> Result<SystemTime> {
+    tokio::fs::metadata(path)
+        .await
+        .wrap_err(format!("Failed to read metadata of {:?}", path))?
+        .modified()
+        .wrap_err(format!("Failed to read modification time of {:?}", path))
+}
+
+/// Resolves once the file has been modified since `since`, and its modification time
+/// stayed the same for one poll, so a library that is still being written is not loaded.
+async fn changed(path: &Path, since: SystemTime) -> SystemTime {
+    let mut last = since;
+
+    loop {
+        tokio::time::sleep(RELOAD_POLL_INTERVAL).await;
+
+        // The file may be missing for a moment while it's rebuilt
+        let Ok(current) = modified(path).await else {
+            continue;
+        };
+
+        if current != since && current == last {
+            return current;
+        }
+
+        last = current;
+    }
+}
+
+impl ReloadableNode {
+    /// Claim all the primitives of the node from the flows, so they're kept by the runtime
+    /// instead of a node instance.
+    pub async fn new(
+        flows: &RuntimeFlows,
+        layout: &DataflowLayout,
+        clock: Arc<HLC>,
+        file_ext: Arc<FileExtManager>,
+        path: PathBuf,
+        source: NodeID,
+        configuration: serde_yml::Value,
+    ) -> Result<Self> {
+        let primitives = layout.debug.nodes.get(&source.uuid).ok_or_eyre(format!(
+            "Node '{}' (uuid: {}) is not part of the dataflow",
+            source.label, source.uuid
+        ))?;
+
+        let mut node = Self {
+            path,
+            source,
+            configuration,
+            clock,
+            file_ext,
+            metrics: flows.metrics.clone(),
+            traces: flows.traces.clone(),
+            inputs: HashMap::new(),
+            outputs: HashMap::new(),
+            queries_senders: HashMap::new(),
+            queries_receivers: HashMap::new(),
+            queryables_senders: HashMap::new(),
+            queryables_receivers: HashMap::new(),
+        };
+
+        let mut inputs_receivers = flows.inputs_receivers.lock().await;
+        let mut outputs_senders = flows.outputs_senders.lock().await;
+        let mut queries_senders = flows.queries_senders.lock().await;
+        let mut queries_receivers = flows.queries_receivers.lock().await;
+        let mut queryables_senders = flows.queryables_senders.lock().await;
+        let mut queryables_receivers = flows.queryables_receivers.lock().await;
+
+        for uuid in primitives {
+            if let Some(receiver) = inputs_receivers.remove(uuid) {
+                node.inputs.insert(*uuid, Arc::new(Mutex::new(receiver)));
+            }
+
+            if let Some(senders) = outputs_senders.remove(uuid) {
+                node.outputs.insert(*uuid, senders);
+            }
+
+            if let Some(sender) = queries_senders.remove(uuid) {
+                node.queries_senders.insert(*uuid, sender);
+            }
+
+            if let Some(receiver) = queries_receivers.remove(uuid) {
+                node.queries_receivers
+                    .insert(*uuid, Arc::new(Mutex::new(receiver)));
+            }
+
+            if let Some(senders) = queryables_senders.remove(uuid) {
+                node.queryables_senders.insert(*uuid, senders);
+            }
+
+            if let Some(receiver) = queryables_receivers.remove(uuid) {
+                node.queryables_receivers
+                    .insert(*uuid, Arc::new(Mutex::new(receiver)));
+            }
+        }
+
+        Ok(node)
+    }
+
+    /// Load a new instance of the node from a copy of its library, so the library is really
+    /// loaded again even if the previous one has not been unloaded by the system.
+    async fn instantiate(&self) -> Result<Instance> {
+        let file_name = self
+            .path
+            .file_name()
+            .ok_or_eyre(format!("Invalid library path {:?}", self.path))?
+            .to_string_lossy();
+
+        let library = std::env::temp_dir().join(format!("iridis-{}-{}", Uuid::new_v4(), file_name));
+
+        tokio::fs::copy(&self.path, &library)
+            .await
+            .wrap_err(format!("Failed to copy library {:?}", self.path))?;
+
+        let mut relays = JoinSet::new();
+        let mut previous = HashMap::new();
+        let returned = ReturnedReceivers::default();
+        let tracer = NodeTracer::new(&self.traces);
+
+        let inputs_receivers = relayed(&self.inputs, &mut relays, &mut previous);
+        let queries_receivers = relayed(&self.queries_receivers, &mut relays, &mut previous);
+        let queryables_receivers = relayed(&self.queryables_receivers, &mut relays, &mut previous);
+
+        let inputs = Inputs::new(inputs_receivers.clone(), self.source.clone())
+            .with_metrics(self.metrics.clone())
+            .with_tracer(tracer.clone())
+            .with_returned(returned.clone());
+        let outputs = Outputs::new(
+            Arc::new(Mutex::new(self.outputs.clone())),
+            self.clock.clone(),
+            self.source.clone(),
+        )
+        .with_metrics(self.metrics.clone())
+        .with_tracer(tracer.clone());
+        let queries = Queries::new(
+            Arc::new(Mutex::new(self.queries_senders.clone())),
+            queries_receivers.clone(),
+            self.clock.clone(),
+            self.source.clone(),
+        )
+        .with_metrics(self.metrics.clone())
+        .with_tracer(tracer.clone())
+        .with_returned(returned.clone());
+        let queryables = Queryables::new(
+            Arc::new(Mutex::new(self.queryables_senders.clone())),
+            queryables_receivers.clone(),
+            self.clock.clone(),
+            self.source.clone(),
+        )
+        .with_metrics(self.metrics.clone())
+        .with_tracer(tracer)
+        .with_returned(returned.clone());
+
+        let node = self
+            .file_ext
+            .load(
+                library.clone(),
+                inputs,
+                outputs,
+                queries,
+                queryables,
+                self.configuration.clone(),
+            )
+            .await;
+
+        match node {
+            Ok(node) => Ok(Instance {
+                node,
+                relays,
+                library,
+                returned,
+                unclaimed: vec![inputs_receivers, queries_receivers, queryables_receivers],
+                previous,
+            }),
+            Err(report) => {
+                let _ = tokio::fs::remove_file(&library).await;
+
+                Err(report)
+            }
+        }
+    }
+
+    /// Run the node until it ends or until the `stop` future completes, see `run_with`.
+    pub async fn run_until(self, stop: impl Future<Output = ()>) -> Result<()> {
+        self.run_instances(None, stop).await
+    }
+
+    /// Run the node with its lifecycle: it's started once every node of the dataflow is ready.
+    /// Every time the library file changes, a new instance is loaded with the same channels
+    /// while the current one keeps running. The current instance is then stopped like any node
+    /// when the `stop` future completes, see `RuntimeNode::run_with`, and the messages it didn't
+    /// read are handed to the new one. It's kept if the new one can't be loaded.
+    pub async fn run_with(
+        self,
+        mut lifecycle: Lifecycle,
+        stop: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let mut stop = std::pin::pin!(stop);
+
+        let started = tokio::select! {
+            started = lifecycle.ready() => started,
+            _ = stop.as_mut() => false,
+        };
+
+        match started {
+            true => self.run_instances(Some(lifecycle), stop).await,
+            false => Ok(()),
+        }
+    }
+
+    async fn run_instances(
+        self,
+        mut lifecycle: Option<Lifecycle>,
+        stop: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let mut stop = std::pin::pin!(stop);
+        let mut since = modified(&self.path).await?;
+
+        let mut instance = self.instantiate().await.wrap_err(format!(
+            "Failed to load node '{}' (uuid: {}) from {:?}",
+            self.source.label, self.source.uuid, self.path
+        ))?;
+
+        // There's no previous instance, the relays of the first one read the runtime receivers
+        instance.previous.clear();
+
+        loop {
+            let Instance {
+                node,
+                relays,
+                library,
+                returned,
+                unclaimed,
+                ..
+            } = instance;
+
+            // Every instance is started right away, and receives the events of the node
+            let (_start, started) = watch::channel(true);
+            let (instance_lifecycle, events, _) = Lifecycle::new(started);
+
+            let mut next = None;
+            let result = node
+                .run_with(instance_lifecycle, events.clone(), async {
+                    loop {
+                        tokio::select! {
+                            _ = stop.as_mut() => return,
+                            time = changed(&self.path, since) => since = time,
+                            event = async {
+                                match lifecycle.as_mut() {
+                                    Some(lifecycle) => lifecycle.recv().await,
+                                    None => std::future::pending().await,
+                                }
+                            } => {
+                                match event {
+                                    Some(event) => {
+                                        let _ = events.send(event);
+                                    }
+                                    None => lifecycle = None,
+                                }
+
+                                continue;
+                            }
+                        }
+
+                        tracing::info!(
+                            "Reloading node '{}' (uuid: {}) from {:?}",
+                            self.source.label,
+                            self.source.uuid,
+                            self.path
+                        );
+
+                        match self.instantiate().await {
+                            Ok(instance) => {
+                                next = Some(instance);
+
+                                return;
+                            }
+                            Err(report) => tracing::warn!(
+                                "Failed to reload node '{}' (uuid: {}), keeping the current instance: {:?}",
+                                self.source.label,
+                                self.source.uuid,
+                                report
+                            ),
+                        }
+                    }
+                })
+                .await;
+
+            // The instance is gone, its primitives gave their receivers back
+            let mut receivers = returned.take();
+            for unclaimed in unclaimed {
+                receivers.extend(unclaimed.lock().await.drain());
+            }
+
+            drop(relays);
+            let _ = tokio::fs::remove_file(&library).await;
+
+            let Some(mut next) = next else {
+                return result;
+            };
+
+            if let Err(report) = result {
+                tracing::warn!(
+                    "Instance of node '{}' (uuid: {}) failed while being reloaded: {:?}",
+                    self.source.label,
+                    self.source.uuid,
+                    report
+                );
+            }
+
+            for (uuid, receiver) in receivers {
+                if let Some(handover) = next.previous.remove(&uuid) {
+                    let _ = handover.send(receiver);
+                }
+            }
+
+            next.previous.clear();
+            instance = next;
+        }
+    }
+}
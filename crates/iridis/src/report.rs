@@ -10,3 +10,29 @@ pub fn report_primitive_not_found(uuid: Uuid) -> eyre::Report {
         uuid
     ))
 }
+
+pub fn report_primitive_not_in_layout(layout: &DataflowLayout, uuid: Uuid) -> eyre::Report {
+    eyre::Report::msg(format!(
+        "Primitive '{}' (uuid: {}) is not part of the dataflow, or is not of the expected kind",
+        layout.label(uuid),
+        uuid
+    ))
+}
+
+pub fn report_primitive_closed(layout: &DataflowLayout, uuid: Uuid) -> eyre::Report {
+    eyre::Report::msg(format!(
+        "Primitive '{}' (uuid: {}) is closed, the nodes using this channel have ended",
+        layout.label(uuid),
+        uuid
+    ))
+}
+
+pub fn report_no_connection(layout: &DataflowLayout, output: Uuid, input: Uuid) -> eyre::Report {
+    eyre::Report::msg(format!(
+        "There is no connection between output '{}' (uuid: {}) and input '{}' (uuid: {})",
+        layout.label(output),
+        output,
+        layout.label(input),
+        input
+    ))
+}
@@ -3,16 +3,18 @@
 
 use std::{collections::HashMap, sync::Arc};
 
-use crate::prelude::{thirdparty::tokio::task::JoinSet, *};
+use crate::prelude::*;
 
 /// Represents a runtime, with a clock, a set of nodes, and a set of plugins.
 pub struct Runtime {
-    pub clock: Arc<HLC>,
+    pub clock: Arc<RuntimeClock>,
 
     pub file_ext: Arc<FileExtManager>,
     pub url_scheme: Arc<UrlSchemeManager>,
 
-    pub nodes: HashMap<NodeID, RuntimeNode>,
+    pub executors: Arc<Executors>,
+
+    pub nodes: HashMap<NodeID, LoadedNode>,
 }
 
 impl Runtime {
@@ -29,70 +31,122 @@ impl Runtime {
         plugins(&mut file_ext, &mut url_scheme).await?;
 
         Ok(Self {
-            clock: Arc::new(HLC::default()),
+            clock: Arc::new(RuntimeClock::default()),
             file_ext: Arc::new(FileExtManager::new(file_ext.finish().await?)),
             url_scheme: Arc::new(UrlSchemeManager::new(url_scheme.finish().await?)),
+            executors: Arc::new(Executors::default()),
             nodes: HashMap::new(),
         })
     }
 
-    /// Load all nodes with the layout provided and run them all.
-    pub async fn run(
+    /// Set the ID of the runtime, the ID of its clock is derived from it. Give each runtime
+    /// of a distributed dataflow its own ID, so their timestamps never collide.
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.clock = Arc::new(RuntimeClock::new(id));
+        self
+    }
+
+    /// Declare a named pool of `workers` threads, pinned round-robin to `cores` if any. Nodes
+    /// are placed on it with `Placement::Pool`.
+    pub fn with_pool(
+        self,
+        name: impl Into<String>,
+        workers: usize,
+        cores: impl IntoIterator<Item = usize>,
+    ) -> Result<Self> {
+        self.executors
+            .pool(name, workers, cores.into_iter().collect())?;
+
+        Ok(self)
+    }
+
+    /// Load all nodes with the layout provided and spawn them all on the current `tokio` runtime.
+    /// It returns a `DataflowHandle` that can be used to control the running dataflow.
+    pub async fn spawn(
+        self,
+        layout: Arc<DataflowLayout>,
+        nodes: impl AsyncFnOnce(&mut Loader) -> Result<()>,
+    ) -> Result<DataflowHandle> {
+        self.spawn_on(layout, None, nodes).await
+    }
+
+    /// Spawn the part of a distributed dataflow that runs on `cluster.machine`. Every machine
+    /// loads the same layout with the same `nodes` function, only the nodes of this machine are
+    /// loaded. It returns once every machine of the cluster is loaded, and the nodes started.
+    pub async fn spawn_distributed(
+        self,
+        layout: Arc<DataflowLayout>,
+        cluster: Cluster,
+        nodes: impl AsyncFnOnce(&mut Loader) -> Result<()>,
+    ) -> Result<DataflowHandle> {
+        self.spawn_on(layout, Some(cluster), nodes).await
+    }
+
+    async fn spawn_on(
         mut self,
         layout: Arc<DataflowLayout>,
+        cluster: Option<Cluster>,
         nodes: impl AsyncFnOnce(&mut Loader) -> Result<()>,
-    ) -> Result<()> {
-        let flows = RuntimeFlows::new(layout)?;
+    ) -> Result<DataflowHandle> {
+        let flows = Arc::new(RuntimeFlows::new(layout.clone())?);
+
+        let peers = match &cluster {
+            Some(cluster) => Some(cluster.connect(&layout).await?),
+            None => None,
+        };
+
+        let mut node_loader = Loader::new(
+            self.file_ext.clone(),
+            self.url_scheme.clone(),
+            self.clock.clone(),
+            flows.clone(),
+            layout.clone(),
+            self.executors.clone(),
+        );
 
-        let mut node_loader =
-            Loader::new(self.file_ext, self.url_scheme, self.clock.clone(), flows);
+        node_loader.machine = cluster.map(|cluster| cluster.machine);
 
         nodes(&mut node_loader).await?;
 
         self.nodes.extend(node_loader.finish().await?);
 
-        let mut tasks = JoinSet::new();
-        for (layout, node) in self.nodes {
-            tasks.spawn(async move {
-                node.run().await.wrap_err(format!(
-                    "Node '{}' (uuid: {}) failed",
-                    layout.label, layout.uuid,
-                ))
-            });
+        if let Some(peers) = peers {
+            peers.bridge(&flows, &layout, self.clock.clone()).await?;
         }
 
-        let join_all = tokio::spawn(async move {
-            let mut reports: Vec<eyre::Report> = Vec::new();
-
-            let mut is_ok = true;
+        flows.release_unclaimed().await;
+
+        Ok(DataflowHandle::new(
+            layout,
+            self.clock,
+            flows,
+            self.file_ext,
+            self.url_scheme,
+            self.executors,
+            self.nodes,
+        ))
+    }
 
-            while let Some(result) = tasks.join_next().await {
-                let result = result?;
+    /// Load all nodes with the layout provided and run them all. This will `await` until
+    /// every node ends, or until `Ctrl-C` is pressed, in which case all nodes are stopped.
+    pub async fn run(
+        self,
+        layout: Arc<DataflowLayout>,
+        nodes: impl AsyncFnOnce(&mut Loader) -> Result<()>,
+    ) -> Result<()> {
+        let handle = self.spawn(layout, nodes).await?;
 
-                if let Err(report) = result {
-                    is_ok = false;
-                    reports.push(report);
-                }
+        let stopper = handle.stopper();
+        let ctrl_c = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                stopper.stop();
             }
+        });
 
-            let reports: eyre::Report = {
-                let report_str: String = reports.iter().fold(
-                    "The runtime encountered multiple errors:".to_string(),
-                    |acc, report| format!("{}\n\n{:?}", acc, report),
-                );
+        let result = handle.wait().await;
 
-                eyre::Report::msg(report_str)
-            };
+        ctrl_c.abort();
 
-            match is_ok {
-                true => Ok(()),
-                false => Err(reports),
-            }
-        });
-
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => { Ok(()) },
-            results = join_all => { results? }
-        }
+        result
     }
 }
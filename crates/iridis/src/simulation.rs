@@ -0,0 +1,52 @@
+//! This module defines the `Simulation`, to run a dataflow deterministically: every node runs
+//! on a single thread, the time is virtual and the randomness of the nodes is seeded. Two runs
+//! with the same seed and the same inputs send the same messages, with the same timestamps.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::prelude::{thirdparty::tokio::runtime::Builder, *};
+
+/// A deterministic run of dataflows.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    /// The seed of the randomness of the nodes, and of the clock ID of the runtimes
+    pub seed: u64,
+    /// The virtual time when the simulation starts
+    pub epoch: SystemTime,
+}
+
+impl Simulation {
+    /// A simulation starting at the UNIX epoch.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            epoch: UNIX_EPOCH,
+        }
+    }
+
+    pub fn epoch(mut self, epoch: SystemTime) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// Run `main` to completion on the current thread, it must not be called from a `tokio`
+    /// runtime. Every `Runtime` created in `main` runs its nodes on this thread, whatever
+    /// their placement. The virtual time only advances when every task is waiting, jumping
+    /// to the next timer: a run takes the time needed to compute it, not its virtual duration.
+    ///
+    /// Only statically linked nodes are simulated, nodes must use `NodeClock` or `tokio::time`
+    /// to read the time and `NodeClock::random` for their randomness.
+    pub fn run<T>(self, main: impl Future<Output = Result<T>>) -> Result<T> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .wrap_err("Failed to create the simulation runtime")?;
+
+        runtime.block_on(async {
+            let _simulation = SimulationGuard::enter(self.seed, self.epoch);
+
+            main.await
+        })
+    }
+}
@@ -0,0 +1,362 @@
+//! This module defines the framing shared by the transports of the runtime, to send
+//! `DataflowMessage`s over a byte stream: every frame is its length, its kind, then either a
+//! control message in YAML or a message. A message is its label, the `Header`, then the data
+//! in Arrow IPC or, over a local link when all its buffers have been allocated in a
+//! `SharedArena`, a reference to them. The sender keeps these buffers until the other side
+//! returns them with a release frame, or until the link is dropped.
+
+use std::{io::Cursor, sync::Arc};
+
+use arrow_array::{Array, RecordBatch, make_array};
+use arrow_ipc::{reader::StreamReader, writer::StreamWriter};
+use arrow_schema::{Field, Schema};
+use uhlc::{ID, NTP64, Timestamp};
+
+#[cfg(target_os = "linux")]
+use crate::prelude::iridis_node::prelude::thirdparty::arrow_buffer::alloc::Allocation;
+
+use crate::prelude::{
+    iridis_node::prelude::thirdparty::{
+        Uuid,
+        arrow_data::ArrayData,
+        serde::{Serialize, de::DeserializeOwned},
+    },
+    thirdparty::tokio::{
+        self,
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+        sync::mpsc,
+    },
+    *,
+};
+
+/// The largest frame read from the other side, larger ones are refused
+const MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+
+const CONTROL_FRAME: u8 = 0;
+const MESSAGE_FRAME: u8 = 1;
+const SHARED_FRAME: u8 = 2;
+const RELEASE_FRAME: u8 = 3;
+
+pub(crate) enum Frame<C> {
+    Control(C),
+    /// A message received by an input, or sent by an output, by label
+    Message(String, DataflowMessage),
+    /// Return the buffers of a message sent by reference, handled by `read_frame`
+    Release(u64),
+}
+
+/// The buffers exchanged by reference over a local link: the ones lent to the other side, and
+/// the channel of the ones it lent, to send back with `Frame::Release` once they're dropped.
+/// Only the shared memory of this process and of the process on the other side, `peer`, is
+/// exchanged.
+pub(crate) struct SharedLink {
+    #[cfg(target_os = "linux")]
+    peer: u32,
+    #[cfg(target_os = "linux")]
+    lent: SharedLeases,
+    returned: mpsc::UnboundedSender<u64>,
+}
+
+impl SharedLink {
+    pub(crate) fn new(peer: u32) -> (Arc<Self>, mpsc::UnboundedReceiver<u64>) {
+        #[cfg(not(target_os = "linux"))]
+        let _ = peer;
+
+        let (returned, receiver) = mpsc::unbounded_channel();
+
+        (
+            Arc::new(Self {
+                #[cfg(target_os = "linux")]
+                peer,
+                #[cfg(target_os = "linux")]
+                lent: SharedLeases::default(),
+                returned,
+            }),
+            receiver,
+        )
+    }
+}
+
+/// Returns the buffers of a message to the side that lent them, once they're all dropped.
+#[cfg(target_os = "linux")]
+struct Returned {
+    id: u64,
+    returned: mpsc::UnboundedSender<u64>,
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Returned {
+    fn drop(&mut self) {
+        let _ = self.returned.send(self.id);
+    }
+}
+
+/// Encode a message, with the id of its buffers if they're lent to the other side of `link`
+fn encode_message(
+    label: &str,
+    message: DataflowMessage,
+    link: Option<&SharedLink>,
+) -> Result<(Vec<u8>, Option<u64>)> {
+    let mut frame = vec![MESSAGE_FRAME];
+    frame.extend((label.len() as u16).to_le_bytes());
+    frame.extend(label.as_bytes());
+    frame.extend(message.header.timestamp.get_time().as_u64().to_le_bytes());
+    frame.extend(message.header.timestamp.get_id().to_le_bytes());
+    frame.extend(message.header.source.0.into_bytes());
+    frame.extend(message.header.source.1.into_bytes());
+    frame.extend(
+        message
+            .header
+            .trace
+            .map(|trace| trace.to_bytes())
+            .unwrap_or_default(),
+    );
+    frame.extend(message.header.sequence.to_le_bytes());
+
+    let metadata = message.header.metadata.encode();
+    frame.extend((metadata.len() as u32).to_le_bytes());
+    frame.extend(metadata);
+
+    // Buffers allocated in a `SharedArena` are sent by reference, unless they come from a third
+    // process the other side can't map
+    #[cfg(target_os = "linux")]
+    if let Some(link) = link {
+        if let Some(shared) = SharedArray::locate(&message.data)
+            .filter(|shared| shared.created_by(&[std::process::id(), link.peer]))
+        {
+            let id = link.lent.lend(message.data);
+
+            frame[0] = SHARED_FRAME;
+            frame.extend(id.to_le_bytes());
+            frame.extend(serde_yml::to_string(&shared)?.into_bytes());
+
+            return Ok((frame, Some(id)));
+        }
+    }
+
+    encode_data(message.data, &mut frame)?;
+
+    Ok((frame, None))
+}
+
+/// Append the data of a message to `bytes`, as an Arrow IPC stream of a single column
+pub(crate) fn encode_data(data: ArrayData, bytes: &mut Vec<u8>) -> Result<()> {
+    let array = make_array(data);
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "data",
+        array.data_type().clone(),
+        true,
+    )]));
+
+    let batch =
+        RecordBatch::try_new(schema.clone(), vec![array]).wrap_err("Failed to encode message")?;
+
+    let mut writer = StreamWriter::try_new(bytes, &schema)?;
+    writer.write(&batch)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Read the data written by `encode_data`
+pub(crate) fn decode_data(bytes: &[u8]) -> Result<ArrayData> {
+    Ok(StreamReader::try_new(Cursor::new(bytes), None)?
+        .next()
+        .ok_or_eyre("Message frame without data")??
+        .column(0)
+        .to_data())
+}
+
+fn decode_message(
+    kind: u8,
+    frame: &[u8],
+    link: Option<&SharedLink>,
+) -> Result<(String, DataflowMessage)> {
+    fn take<'a>(frame: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+        if frame.len() < len {
+            eyre::bail!("Truncated message frame");
+        }
+
+        let (head, tail) = frame.split_at(len);
+        *frame = tail;
+
+        Ok(head)
+    }
+
+    let mut frame = frame;
+
+    let len = u16::from_le_bytes(take(&mut frame, 2)?.try_into()?) as usize;
+    let label = String::from_utf8(take(&mut frame, len)?.to_vec())?;
+
+    let time = u64::from_le_bytes(take(&mut frame, 8)?.try_into()?);
+    let id: [u8; 16] = take(&mut frame, 16)?.try_into()?;
+    let node: [u8; 16] = take(&mut frame, 16)?.try_into()?;
+    let primitive: [u8; 16] = take(&mut frame, 16)?.try_into()?;
+    let trace: [u8; 24] = take(&mut frame, 24)?.try_into()?;
+    let sequence = u64::from_le_bytes(take(&mut frame, 8)?.try_into()?);
+
+    let len = u32::from_le_bytes(take(&mut frame, 4)?.try_into()?) as usize;
+    let metadata = HeaderMetadata::decode(take(&mut frame, len)?)?;
+
+    let data = match kind {
+        #[cfg(target_os = "linux")]
+        SHARED_FRAME => {
+            let link = link.ok_or_eyre("Unexpected shared memory message")?;
+            let id = u64::from_le_bytes(take(&mut frame, 8)?.try_into()?);
+
+            let lender: Arc<dyn Allocation> = Arc::new(Returned {
+                id,
+                returned: link.returned.clone(),
+            });
+
+            serde_yml::from_slice::<SharedArray>(frame)?.into_data(link.peer, &lender)?
+        }
+        _ => decode_data(frame)?,
+    };
+
+    Ok((
+        label,
+        DataflowMessage {
+            header: Header {
+                timestamp: Timestamp::new(
+                    NTP64(time),
+                    ID::try_from(id).map_err(eyre::Report::msg)?,
+                ),
+                source: (Uuid::from_bytes(node), Uuid::from_bytes(primitive)),
+                trace: TraceContext::from_bytes(trace),
+                sequence,
+                metadata,
+            },
+            data,
+        },
+    ))
+}
+
+/// Write a frame, messages are only sent by reference to shared memory over a `link`, when
+/// the other side runs on the same machine.
+pub(crate) async fn write_frame<C: Serialize>(
+    writer: &mut (impl AsyncWrite + Unpin),
+    frame: Frame<C>,
+    link: Option<&SharedLink>,
+) -> Result<()> {
+    let (frame, lent) = match frame {
+        Frame::Control(control) => {
+            let mut frame = vec![CONTROL_FRAME];
+            frame.extend(serde_yml::to_string(&control)?.into_bytes());
+
+            (frame, None)
+        }
+        Frame::Message(label, message) => encode_message(&label, message, link)?,
+        Frame::Release(id) => {
+            let mut frame = vec![RELEASE_FRAME];
+            frame.extend(id.to_le_bytes());
+
+            (frame, None)
+        }
+    };
+
+    let written = write_bytes(writer, frame).await;
+
+    // The other side never received the buffers
+    #[cfg(target_os = "linux")]
+    if let (Err(_), Some(link), Some(id)) = (&written, link, lent) {
+        link.lent.release(id);
+    }
+
+    written
+}
+
+async fn write_bytes(writer: &mut (impl AsyncWrite + Unpin), frame: Vec<u8>) -> Result<()> {
+    if frame.len() > MAX_FRAME_SIZE {
+        eyre::bail!(
+            "The frame is {} bytes long, larger than {} bytes",
+            frame.len(),
+            MAX_FRAME_SIZE
+        );
+    }
+
+    writer
+        .write_all(&(frame.len() as u32).to_le_bytes())
+        .await?;
+    writer.write_all(&frame).await?;
+
+    Ok(())
+}
+
+/// Read the next frame, `None` when the other side is gone. The buffers returned by the other
+/// side of the `link` are released here, so `Frame::Release` is never returned.
+pub(crate) async fn read_frame<C: DeserializeOwned>(
+    reader: &mut (impl AsyncRead + Unpin),
+    link: Option<&SharedLink>,
+) -> Result<Option<Frame<C>>> {
+    loop {
+        let frame = read_bytes(reader).await?;
+
+        match frame.as_deref().map(|frame| frame.split_first()) {
+            None => return Ok(None),
+            Some(Some((&CONTROL_FRAME, control))) => {
+                return Ok(Some(Frame::Control(serde_yml::from_slice(control)?)));
+            }
+            Some(Some((&kind, message))) if kind == MESSAGE_FRAME || kind == SHARED_FRAME => {
+                let (label, message) = decode_message(kind, message, link)?;
+
+                return Ok(Some(Frame::Message(label, message)));
+            }
+            #[cfg(target_os = "linux")]
+            Some(Some((&RELEASE_FRAME, id))) => {
+                let link = link.ok_or_eyre("Unexpected release frame")?;
+
+                link.lent.release(u64::from_le_bytes(id.try_into()?));
+            }
+            _ => eyre::bail!("Invalid frame"),
+        }
+    }
+}
+
+/// Read the bytes of the next frame, `None` when the other side is gone.
+async fn read_bytes(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    if reader.read_exact(&mut len).await.is_err() {
+        return Ok(None);
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        eyre::bail!(
+            "Refused a frame of {} bytes, larger than {} bytes",
+            len,
+            MAX_FRAME_SIZE
+        );
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+
+    Ok(Some(frame))
+}
+
+/// Read the frames in a task, so reading is never cancelled in the middle of a frame.
+pub(crate) fn spawn_reader<C: DeserializeOwned + Send + 'static>(
+    mut reader: impl AsyncRead + Unpin + Send + 'static,
+    link: Option<Arc<SharedLink>>,
+) -> mpsc::Receiver<Result<Frame<C>>> {
+    let (sender, receiver) = mpsc::channel(128);
+
+    tokio::spawn(async move {
+        loop {
+            let frame = read_frame(&mut reader, link.as_deref()).await.transpose();
+            let end = !matches!(frame, Some(Ok(_)));
+
+            let sent = match frame {
+                Some(frame) => sender.send(frame).await.is_ok(),
+                None => false,
+            };
+
+            if end || !sent {
+                return;
+            }
+        }
+    });
+
+    receiver
+}
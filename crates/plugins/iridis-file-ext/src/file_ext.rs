@@ -113,6 +113,13 @@ impl FileExtLoader {
                                 library
                             };
 
+                            check_metadata(
+                                &library,
+                                LibraryKind::FileExtPlugin,
+                                &Metadata::current(FILE_EXT_PLUGIN_INTERFACE),
+                                &path_buf,
+                            )?;
+
                             let constructor = unsafe {
                                 library
                                     .get::<*mut DynamicallyLinkedFileExtPluginInstance>(
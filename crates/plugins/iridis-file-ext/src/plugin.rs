@@ -31,3 +31,7 @@ pub trait FileExtPlugin: Send + Sync {
 /// This type is used to represent the return type of the `C` symbolic function that instantiates the plugin.
 pub type DynamicallyLinkedFileExtPluginInstance =
     fn() -> JoinHandle<Result<Box<dyn FileExtPlugin>>>;
+
+/// The interface of the symbol that instantiates the plugin, exported in its metadata
+pub const FILE_EXT_PLUGIN_INTERFACE: extern "C" fn() -> u64 =
+    interface_hash::<DynamicallyLinkedFileExtPluginInstance>;
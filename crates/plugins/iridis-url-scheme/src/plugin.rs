@@ -34,3 +34,7 @@ pub trait UrlSchemePlugin: Send + Sync {
 /// This type is used to represent the return type of the `C` symbolic function that instantiates the plugin.
 pub type DynamicallyLinkedUrlSchemePluginInstance =
     fn() -> JoinHandle<Result<Box<dyn UrlSchemePlugin>>>;
+
+/// The interface of the symbol that instantiates the plugin, exported in its metadata
+pub const URL_SCHEME_PLUGIN_INTERFACE: extern "C" fn() -> u64 =
+    interface_hash::<DynamicallyLinkedUrlSchemePluginInstance>;
@@ -120,6 +120,13 @@ impl UrlSchemeLoader {
                                     library
                                 };
 
+                                check_metadata(
+                                    &library,
+                                    LibraryKind::UrlSchemePlugin,
+                                    &Metadata::current(URL_SCHEME_PLUGIN_INTERFACE),
+                                    &path_buf,
+                                )?;
+
                                 let constructor = unsafe {
                                     library
                                     .get::<*mut DynamicallyLinkedUrlSchemePluginInstance>(